@@ -0,0 +1,7 @@
+// dependents' build scripts get `DEP_COVERS_TESTING` when the `testing`
+// feature is enabled, see `covers::build::testing`
+fn main() {
+    if std::env::var_os("CARGO_FEATURE_TESTING").is_some() {
+        println!("cargo:testing=1");
+    }
+}
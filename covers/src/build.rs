@@ -0,0 +1,45 @@
+//! Helpers for build scripts of crates with mocked functions.
+//!
+//! `testing` feature enabled via `[dev-dependencies]` dispatches to mocks in
+//! builds of the library for its integration tests (`tests/` directory), but
+//! only for crates opting in from their `build.rs`:
+//! ```rust,ignore
+//! fn main() {
+//!     covers::build::testing();
+//! }
+//! ```
+//! The feature is unified across the dependency graph, the cfg is emitted for
+//! the crate calling the helper alone. Likewise, a library declaring
+//! `covers-active` feature calls [`active_feature`] to dispatch to mocks
+//! whenever the feature is enabled.
+
+/// Cfg of the crate dispatching to mocks under its integration tests.
+pub const TESTING_CFG: &str = "covers_testing";
+
+/// Environment variable telling the macros that the crate being compiled
+/// expects [`TESTING_CFG`].
+pub const TESTING_ENV: &str = "COVERS_TESTING";
+
+/// Emits [`TESTING_CFG`] for the crate when `testing` feature of `covers` is
+/// enabled, e.g. by `[dev-dependencies]` under `cargo test`
+pub fn testing() {
+    println!("cargo:rustc-check-cfg=cfg({})", TESTING_CFG);
+    println!("cargo:rustc-env={}=1", TESTING_ENV);
+    if std::env::var_os("DEP_COVERS_TESTING").is_some() {
+        println!("cargo:rustc-cfg={}", TESTING_CFG);
+    }
+}
+
+/// Feature of the crate dispatching to mocks outside of its unit tests: for
+/// its integration tests and for tests of dependent crates.
+pub const ACTIVE_FEATURE: &str = "covers-active";
+
+/// Environment variable telling the macros that the crate being compiled
+/// declares [`ACTIVE_FEATURE`].
+pub const ACTIVE_FEATURE_ENV: &str = "COVERS_ACTIVE_FEATURE";
+
+/// Dispatches to mocks of the crate whenever its [`ACTIVE_FEATURE`] is
+/// enabled, in any profile. The crate declares the feature in `[features]`
+pub fn active_feature() {
+    println!("cargo:rustc-env={}=1", ACTIVE_FEATURE_ENV);
+}
@@ -0,0 +1,43 @@
+//! Guard against mocks leaking into release builds.
+//!
+//! With `features = ["canary"]` every function wrapped with `#[mocked]` embeds
+//! [`CANARY`] bytes next to the dispatch to mocks, i.e. only into builds where
+//! mocks are wired. A test or an xtask could check the release binary:
+//! ```rust
+//! assert!(!covers::canary::find("target/release/app").unwrap(), "mocks shipped to prod");
+//! ```
+//! The checker keeps the bytes masked, so a binary calling it is not reported
+//! unless the dispatch is compiled in.
+
+use std::io;
+use std::path::Path;
+
+/// Bytes embedded into binaries with compiled dispatch to mocks
+pub const CANARY: &[u8; 32] = b"__covers_mock_dispatch_canary_v1";
+
+const MASK: u8 = 0xA5;
+
+const MASKED: [u8; 32] = mask(CANARY);
+
+const fn mask(bytes: &[u8; 32]) -> [u8; 32] {
+    let mut masked = [0; 32];
+    let mut index = 0;
+    while index < masked.len() {
+        masked[index] = bytes[index] ^ MASK;
+        index += 1;
+    }
+    masked
+}
+
+/// Checks whether the binary at the path contains [`CANARY`]
+pub fn find(binary: impl AsRef<Path>) -> io::Result<bool> {
+    Ok(contains(&std::fs::read(binary)?))
+}
+
+/// Checks whether the content of a binary contains [`CANARY`]
+pub fn contains(content: &[u8]) -> bool {
+    // unmasked at runtime, otherwise the constant is folded into the binary
+    let mask = std::hint::black_box(MASK);
+    let canary = MASKED.map(|byte| byte ^ mask);
+    content.windows(canary.len()).any(|window| window == canary)
+}
@@ -0,0 +1,1077 @@
+//! Support of the code generated by macros.
+//!
+//! The items are public to be reachable from the expansion,
+//! only the guards returned by macros are meant to be used directly.
+
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread, ThreadId};
+
+/// Tracks calls of a mocked function: the counts of [`calls!`](crate::calls),
+/// the records of spies and captures, and the executions across threads for
+/// [`assert_not_concurrent!`](crate::assert_not_concurrent).
+#[derive(Debug, Default)]
+pub struct Tracker {
+    /// Threads of the executions in progress while the concurrency is
+    /// asserted, a thread once per call
+    executing: Mutex<Vec<ThreadId>>,
+    asserting: AtomicUsize,
+    violations: AtomicUsize,
+    calls: AtomicUsize,
+    mock_calls: AtomicUsize,
+    /// Calls of the threads owning the slots, see [`ThreadSlot`]
+    slot_calls: [AtomicUsize; THREAD_SLOTS],
+    slot_owners: [AtomicUsize; THREAD_SLOTS],
+}
+
+impl Tracker {
+    pub const fn new() -> Self {
+        Tracker {
+            executing: Mutex::new(Vec::new()),
+            asserting: AtomicUsize::new(0),
+            violations: AtomicUsize::new(0),
+            calls: AtomicUsize::new(0),
+            mock_calls: AtomicUsize::new(0),
+            slot_calls: [const { AtomicUsize::new(0) }; THREAD_SLOTS],
+            slot_owners: [const { AtomicUsize::new(0) }; THREAD_SLOTS],
+        }
+    }
+
+    /// Marks the function as executing until the result is dropped.
+    ///
+    /// The wiring is reported to stderr if the call panics,
+    /// e.g. with `unimplemented!()` left in a mock.
+    /// With `COVERS_TRACE` environment variable set, the call is traced to
+    /// stderr too, see [`tagged`].
+    pub fn enter(&'static self, explain: fn() -> Explanation) -> Execution {
+        let prior_calls = self.calls.fetch_add(1, Ordering::SeqCst);
+        self.count_thread_call();
+        // the records of the call get the tag set for it, nested calls have their own.
+        // Calls of the threads never tagging any are not recorded
+        let is_tagging = IS_TAGGING.with(Cell::get);
+        if is_tagging {
+            let tag = NEXT_TAGS.with(|tags| tags.borrow_mut().remove(&self.key()));
+            if let Some(tag) = &tag {
+                TAGGED_CALLS.with(|calls| *calls.borrow_mut().entry((self.key(), tag.clone())).or_insert(0) += 1);
+            }
+            CALL_TAGS.with(|tags| tags.borrow_mut().entry(self.key()).or_default().push(tag));
+        }
+        let is_asserting = self.asserting.load(Ordering::SeqCst) > 0;
+        if is_asserting {
+            // recursive calls in the same thread are not concurrent
+            let thread = thread::current().id();
+            let mut executing = self.executing.lock().unwrap_or_else(PoisonError::into_inner);
+            if executing.iter().any(|other| *other != thread) {
+                self.violations.fetch_add(1, Ordering::SeqCst);
+            }
+            executing.push(thread);
+        }
+        if is_tracing() {
+            let explanation = explain();
+            let target = if explanation.mock.is_empty() || explanation.original_override {
+                explanation.original
+            } else {
+                explanation.mock
+            };
+            eprintln!(
+                "{}",
+                tagged(&format!(
+                    "call #{} of `{}` => {}",
+                    prior_calls + 1,
+                    explanation.function,
+                    target
+                ))
+            );
+        }
+        Execution {
+            tracker: self,
+            explain,
+            prior_calls,
+            is_tagging,
+            is_asserting,
+        }
+    }
+
+    /// Number of calls of the function so far
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Number of calls of the function in the current thread so far,
+    /// see [`calls!`](crate::calls)
+    pub fn thread_calls(&self) -> usize {
+        let slot_calls = THREAD_SLOT.with(|slot| {
+            let slot = slot.as_ref()?;
+            let is_owned = self.slot_owners[slot.index].load(Ordering::Relaxed) == slot.owner;
+            Some(if is_owned {
+                self.slot_calls[slot.index].load(Ordering::Relaxed)
+            } else {
+                0
+            })
+        });
+        slot_calls.unwrap_or_else(|| THREAD_CALLS.with(|calls| calls.borrow().get(&self.key()).copied().unwrap_or(0)))
+    }
+
+    /// Counts the call in the slot of the current thread, without locking or
+    /// allocations, the threads without slots count calls in a map
+    fn count_thread_call(&self) {
+        let is_counted = THREAD_SLOT.with(|slot| {
+            let Some(slot) = slot else {
+                return false;
+            };
+            // the slot could be left with the calls of an exited thread
+            let owner = &self.slot_owners[slot.index];
+            if owner.load(Ordering::Relaxed) != slot.owner {
+                owner.store(slot.owner, Ordering::Relaxed);
+                self.slot_calls[slot.index].store(0, Ordering::Relaxed);
+            }
+            self.slot_calls[slot.index].fetch_add(1, Ordering::Relaxed);
+            true
+        });
+        if !is_counted {
+            THREAD_CALLS.with(|calls| *calls.borrow_mut().entry(self.key()).or_insert(0) += 1);
+        }
+    }
+
+    /// Records `Debug` representation of the arguments of a call of the
+    /// function marked with `capture` in the current thread
+    pub fn capture(&self, args: String) {
+        let record = (self.call_tag(), args);
+        CAPTURES.with(|captures| captures.borrow_mut().entry(self.key()).or_default().push(record));
+    }
+
+    /// Arguments of the calls recorded in the current thread so far,
+    /// see [`captured!`](crate::captured)
+    pub fn captured(&self) -> Vec<String> {
+        CAPTURES.with(|captures| records(captures.borrow().get(&self.key()), None))
+    }
+
+    /// Records `Debug` representation of the value returned by the original
+    /// of a function marked with `spy` in the current thread
+    pub fn spy<R: fmt::Debug>(&self, result: R) -> R {
+        let record = (self.call_tag(), format!("{:?}", result));
+        RETURNS.with(|returns| returns.borrow_mut().entry(self.key()).or_default().push(record));
+        result
+    }
+
+    /// Values returned by the calls recorded in the current thread so far,
+    /// see [`returned!`](crate::returned)
+    pub fn returned(&self) -> Vec<String> {
+        RETURNS.with(|returns| records(returns.borrow().get(&self.key()), None))
+    }
+
+    /// Tags the next call of the function in the current thread, see
+    /// [`tag_next_call!`](crate::tag_next_call)
+    pub fn tag_next_call(&self, tag: &str) {
+        IS_TAGGING.with(|is_tagging| is_tagging.set(true));
+        NEXT_TAGS.with(|tags| tags.borrow_mut().insert(self.key(), tag.to_string()));
+    }
+
+    /// Records of the calls tagged with `tag` in the current thread, see
+    /// [`tagged!`](crate::tagged)
+    pub fn tagged(&'static self, tag: &str) -> Tagged {
+        Tagged {
+            tracker: self,
+            tag: tag.to_string(),
+        }
+    }
+
+    /// Tag of the call executing in the current thread
+    fn call_tag(&self) -> Option<String> {
+        CALL_TAGS.with(|tags| {
+            tags.borrow()
+                .get(&self.key())
+                .and_then(|tags| tags.last().cloned().flatten())
+        })
+    }
+
+    fn key(&self) -> usize {
+        self as *const Tracker as usize
+    }
+
+    /// Counts the call dispatched to a mock, see [`report`]
+    pub fn hit(&self) {
+        self.mock_calls.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Starts checking that the function is never executing
+    /// in two threads simultaneously until the result is dropped
+    pub fn assert_not_concurrent(&'static self, name: &'static str) -> NotConcurrent {
+        self.asserting.fetch_add(1, Ordering::SeqCst);
+        NotConcurrent {
+            tracker: self,
+            name,
+            violations: self.violations.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Execution of a mocked function, see [`Tracker::enter`]
+#[must_use]
+pub struct Execution {
+    tracker: &'static Tracker,
+    explain: fn() -> Explanation,
+    prior_calls: usize,
+    is_tagging: bool,
+    is_asserting: bool,
+}
+
+impl Drop for Execution {
+    fn drop(&mut self) {
+        if self.is_asserting {
+            let thread = thread::current().id();
+            let mut executing = self.tracker.executing.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(index) = executing.iter().position(|other| *other == thread) {
+                executing.swap_remove(index);
+            }
+        }
+        if self.is_tagging {
+            CALL_TAGS.with(|tags| tags.borrow_mut().get_mut(&self.tracker.key()).map(Vec::pop));
+        }
+        if thread::panicking() {
+            let note = format!(
+                "panicked after {} prior call(s) of a wrapped function",
+                self.prior_calls
+            );
+            eprintln!("note: {}\n{}", tagged(&note), (self.explain)());
+        }
+    }
+}
+
+/// Number of threads counting calls in the slots of trackers at once, see
+/// [`ThreadSlot`]
+const THREAD_SLOTS: usize = 32;
+
+/// Slots released by exited threads
+static FREE_SLOTS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+/// Owners of the slots, unique for the process, `0` is never used
+static NEXT_OWNER: AtomicUsize = AtomicUsize::new(1);
+
+/// Index of the slot the thread counts calls in, held until the thread exits.
+/// The owner tells the calls of the thread from the ones of the threads that
+/// held the slot before
+struct ThreadSlot {
+    index: usize,
+    owner: usize,
+}
+
+impl ThreadSlot {
+    fn claim() -> Option<ThreadSlot> {
+        let index = FREE_SLOTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+            .or_else(|| Some(NEXT_SLOT.fetch_add(1, Ordering::Relaxed)).filter(|index| *index < THREAD_SLOTS))?;
+        Some(ThreadSlot {
+            index,
+            owner: NEXT_OWNER.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+}
+
+impl Drop for ThreadSlot {
+    fn drop(&mut self) {
+        FREE_SLOTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(self.index);
+    }
+}
+
+/// Environment variable enabling traces of calls of mocked functions.
+const TRACE_ENV: &str = "COVERS_TRACE";
+
+fn is_tracing() -> bool {
+    static TRACING: OnceLock<bool> = OnceLock::new();
+    *TRACING.get_or_init(|| std::env::var_os(TRACE_ENV).is_some_and(|value| !value.is_empty() && value != "0"))
+}
+
+/// Name of the test running in the current thread: libtest names threads
+/// after tests, `None` for the main thread and threads spawned by tests
+pub fn test_name() -> Option<String> {
+    thread::current()
+        .name()
+        .filter(|name| *name != "main")
+        .map(str::to_string)
+}
+
+/// Prefixes the message logged by covers with the current test name (or the
+/// thread id), so interleaved output of parallel tests could be attributed
+pub fn tagged(message: &str) -> String {
+    match test_name() {
+        Some(test) => format!("covers [{}]: {}", test, message),
+        None => format!("covers [{:?}]: {}", thread::current().id(), message),
+    }
+}
+
+/// Panics on drop if the function was executing concurrently since creation,
+/// see [`assert_not_concurrent!`](crate::assert_not_concurrent)
+#[must_use = "the check stops when the guard is dropped"]
+pub struct NotConcurrent {
+    tracker: &'static Tracker,
+    name: &'static str,
+    violations: usize,
+}
+
+impl Drop for NotConcurrent {
+    fn drop(&mut self) {
+        self.tracker.asserting.fetch_sub(1, Ordering::SeqCst);
+        let violations = self.tracker.violations.load(Ordering::SeqCst) - self.violations;
+        if violations > 0 && !std::thread::panicking() {
+            panic!(
+                "`{}` was executing in two or more threads simultaneously {} time(s)",
+                self.name, violations
+            );
+        }
+    }
+}
+
+/// Records of the calls of a function tagged with
+/// [`tag_next_call!`](crate::tag_next_call) in the current thread, see
+/// [`tagged!`](crate::tagged)
+pub struct Tagged {
+    tracker: &'static Tracker,
+    tag: String,
+}
+
+impl Tagged {
+    /// Number of the tagged calls
+    pub fn calls(&self) -> usize {
+        TAGGED_CALLS.with(|calls| {
+            let key = (self.tracker.key(), self.tag.clone());
+            calls.borrow().get(&key).copied().unwrap_or(0)
+        })
+    }
+
+    /// Arguments of the tagged calls recorded by `capture` or `spy`
+    pub fn captured(&self) -> Vec<String> {
+        CAPTURES.with(|captures| records(captures.borrow().get(&self.tracker.key()), Some(&self.tag)))
+    }
+
+    /// Values returned by the tagged calls recorded by `spy`
+    pub fn returned(&self) -> Vec<String> {
+        RETURNS.with(|returns| records(returns.borrow().get(&self.tracker.key()), Some(&self.tag)))
+    }
+}
+
+/// Wiring of a mocked function, see [`explain!`](crate::explain)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// Name of the mocked function
+    pub function: &'static str,
+    /// Active gate of the build: `test` (dispatches to mocks),
+    /// `debug_assertions` or `none`
+    pub gate: &'static str,
+    /// The mock selected for `test` builds, empty for others
+    pub mock: &'static str,
+    /// Name of the renamed original function
+    pub original: &'static str,
+    /// Prefix of the original function in use
+    pub prefix: &'static str,
+    /// Whether the original is forced with
+    /// [`with_original!`](crate::with_original) in the current thread
+    pub original_override: bool,
+    /// Number of calls of the function in tests so far
+    pub calls: usize,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "`{}` mocked with covers:", self.function)?;
+        writeln!(f, "  gate: {}", self.gate)?;
+        writeln!(f, "  mock: {}", if self.mock.is_empty() { "-" } else { self.mock })?;
+        writeln!(f, "  original: {} (prefix `{}`)", self.original, self.prefix)?;
+        writeln!(f, "  original override: {}", self.original_override)?;
+        write!(f, "  calls: {}", self.calls)
+    }
+}
+
+/// Mocked function tracked by [`report`]
+struct Registered {
+    function: &'static str,
+    mock: &'static str,
+    tracker: &'static Tracker,
+}
+
+static REGISTRY: Mutex<Vec<Registered>> = Mutex::new(Vec::new());
+
+/// Registers the function for [`report`]. With `features = ["report"]` it is
+/// called for every mocked function of test builds the first time its
+/// [`Tracker`] is used: by a call, or by a macro inspecting the function, e.g.
+/// [`calls!`](crate::calls).
+pub fn register(function: &'static str, mock: &'static str, tracker: &'static Tracker) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    registry.push(Registered {
+        function,
+        mock,
+        tracker,
+    });
+}
+
+/// Summary of calls of the registered functions and their mocks, the ones
+/// inspected but never called are listed separately as candidates for dead
+/// mocks or untested seams. Print it at the end of a test or of `main`, e.g.
+/// `eprint!("{}", covers::runtime::report())`
+pub fn report() -> String {
+    let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut rows: Vec<_> = registry
+        .iter()
+        .map(|registered| {
+            (
+                registered.function,
+                registered.mock,
+                registered.tracker.calls(),
+                registered.tracker.mock_calls.load(Ordering::SeqCst),
+            )
+        })
+        .collect();
+    rows.sort();
+
+    let mut report = format!("covers: {} mocked function(s)\n", rows.len());
+    let _ = writeln!(report, "{:>8} {:>8}  function => mock", "calls", "mocked");
+    for (function, mock, calls, mock_calls) in rows.iter().filter(|row| row.2 > 0) {
+        let _ = writeln!(report, "{:>8} {:>8}  {} => {}", calls, mock_calls, function, mock);
+    }
+    let unused: Vec<_> = rows.iter().filter(|row| row.2 == 0).collect();
+    if !unused.is_empty() {
+        let _ = writeln!(report, "never exercised:");
+        for (function, mock, ..) in unused {
+            let _ = writeln!(report, "  {} => {}", function, mock);
+        }
+    }
+    report
+}
+
+/// Arguments or the returned value of a call with the tag of the call, see
+/// [`tag_next_call!`](crate::tag_next_call)
+type Record = (Option<String>, String);
+
+/// Values of the records, of the ones with the tag only if it is passed
+fn records(records: Option<&Vec<Record>>, tag: Option<&str>) -> Vec<String> {
+    records
+        .into_iter()
+        .flatten()
+        .filter(|(record_tag, _)| tag.is_none() || record_tag.as_deref() == tag)
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+/// Mocks installed with [`scoped_mock!`](crate::scoped_mock) and stubs
+/// installed with [`returns!`](crate::returns) by scope, the last one wins
+type ScopedStack = Vec<(usize, Box<dyn Any>)>;
+
+thread_local! {
+    static INSTANCE_MOCKS: RefCell<HashMap<(usize, usize), Box<dyn Any>>> = RefCell::new(HashMap::new());
+    static INSTANCE_CALLS: RefCell<HashMap<(usize, usize), usize>> = RefCell::new(HashMap::new());
+    static SCOPED_MOCKS: RefCell<HashMap<usize, ScopedStack>> = RefCell::new(HashMap::new());
+    static STATES: RefCell<HashMap<TypeId, StateStack>> = RefCell::new(HashMap::new());
+    static DISABLED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static THREAD_SLOT: Option<ThreadSlot> = ThreadSlot::claim();
+    static THREAD_CALLS: RefCell<HashMap<usize, usize>> = RefCell::new(HashMap::new());
+    static IS_TAGGING: Cell<bool> = const { Cell::new(false) };
+    static CAPTURES: RefCell<HashMap<usize, Vec<Record>>> = RefCell::new(HashMap::new());
+    static RETURNS: RefCell<HashMap<usize, Vec<Record>>> = RefCell::new(HashMap::new());
+    static NEXT_TAGS: RefCell<HashMap<usize, String>> = RefCell::new(HashMap::new());
+    static CALL_TAGS: RefCell<HashMap<usize, Vec<Option<String>>>> = RefCell::new(HashMap::new());
+    static TAGGED_CALLS: RefCell<HashMap<(usize, String), usize>> = RefCell::new(HashMap::new());
+    static EXPECTATIONS: RefCell<Vec<Rc<RefCell<Expected>>>> = const { RefCell::new(Vec::new()) };
+}
+
+static SCOPES: AtomicUsize = AtomicUsize::new(0);
+
+/// Gets the innermost mock installed with [`scoped_mock!`](crate::scoped_mock)
+/// for the function identified by its tracker
+pub fn scoped_mock<F: Copy + 'static>(function: &'static Tracker) -> Option<F> {
+    let key = function as *const Tracker as usize;
+    SCOPED_MOCKS.with(|mocks| {
+        mocks
+            .borrow()
+            .get(&key)
+            .and_then(|stack| stack.last())
+            .and_then(|(_, mock)| mock.downcast_ref::<F>())
+            .copied()
+    })
+}
+
+/// Gets the value of the innermost stub installed with
+/// [`returns!`](crate::returns) for the function identified by its tracker
+pub fn scoped_return<R: 'static>(function: &'static Tracker) -> Option<R> {
+    let key = function as *const Tracker as usize;
+    SCOPED_MOCKS.with(|mocks| {
+        mocks
+            .borrow()
+            .get(&key)
+            .and_then(|stack| stack.last())
+            .and_then(|(_, stub)| stub.downcast_ref::<Box<dyn Fn() -> R>>())
+            .map(|stub| stub())
+    })
+}
+
+/// Removes the mock installed with [`scoped_mock!`](crate::scoped_mock) or
+/// [`returns!`](crate::returns) when dropped, restoring the one installed
+/// before it
+#[must_use = "the mock is removed when the guard is dropped"]
+pub struct ScopedMock {
+    key: usize,
+    scope: usize,
+}
+
+impl ScopedMock {
+    /// Installs the mock for the function identified by its tracker
+    pub fn push<F: Copy + 'static>(function: &'static Tracker, mock: F) -> ScopedMock {
+        Self::install(function, Box::new(mock))
+    }
+
+    /// Installs the stub producing return values for the function identified
+    /// by its tracker
+    pub fn push_return<R: 'static>(function: &'static Tracker, stub: Box<dyn Fn() -> R>) -> ScopedMock {
+        Self::install(function, Box::new(stub))
+    }
+
+    fn install(function: &'static Tracker, mock: Box<dyn Any>) -> ScopedMock {
+        let key = function as *const Tracker as usize;
+        let scope = SCOPES.fetch_add(1, Ordering::SeqCst);
+        SCOPED_MOCKS.with(|mocks| mocks.borrow_mut().entry(key).or_default().push((scope, mock)));
+        ScopedMock { key, scope }
+    }
+}
+
+impl Drop for ScopedMock {
+    fn drop(&mut self) {
+        // the thread-local could be already destroyed at the exit of the thread
+        let _ = SCOPED_MOCKS.try_with(|mocks| {
+            if let Some(stack) = mocks.borrow_mut().get_mut(&self.key) {
+                stack.retain(|(scope, _)| *scope != self.scope);
+            }
+        });
+    }
+}
+
+/// States installed with [`scoped_state`] by scope, the last one wins
+type StateStack = Vec<(usize, Rc<dyn Any>)>;
+
+/// Handle of the state shared by mocks of the current thread, see [`state`]
+pub struct State<T: 'static>(Rc<RefCell<T>>);
+
+impl<T: 'static> State<T> {
+    /// Gives access to the state, e.g. to fake DB contents
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.0.borrow_mut())
+    }
+
+    /// Gets a copy of the state
+    pub fn get(&self) -> T
+    where T: Clone {
+        self.0.borrow().clone()
+    }
+
+    /// Replaces the state returning the previous one
+    pub fn set(&self, value: T) -> T {
+        self.0.replace(value)
+    }
+}
+
+/// Gets the state of the type shared by mocks of the current thread, so
+/// several mocks of a test could coordinate without mutable globals.
+///
+/// The innermost state installed with [`scoped_state`] is returned, otherwise
+/// the thread's own one created with `Default` (tests run in own threads).
+///
+/// Example:
+/// ```rust
+/// fn mock_insert(user: User) {
+///     covers::state::<Users>().with(|users| users.push(user));
+/// }
+///
+/// fn mock_count() -> usize {
+///     covers::state::<Users>().with(|users| users.len())
+/// }
+/// ```
+pub fn state<T: Default + 'static>() -> State<T> {
+    STATES.with(|states| {
+        let mut states = states.borrow_mut();
+        let stack = states.entry(TypeId::of::<T>()).or_default();
+        if stack.is_empty() {
+            let scope = SCOPES.fetch_add(1, Ordering::SeqCst);
+            stack.push((scope, Rc::new(RefCell::new(T::default()))));
+        }
+        let (_, state) = stack.last().unwrap();
+        State(state.clone().downcast::<RefCell<T>>().unwrap())
+    })
+}
+
+/// Installs the state returned by [`state`] in the current thread until the
+/// guard is dropped, e.g. to seed mocks with fixture data
+pub fn scoped_state<T: 'static>(value: T) -> ScopedState {
+    let key = TypeId::of::<T>();
+    let scope = SCOPES.fetch_add(1, Ordering::SeqCst);
+    STATES.with(|states| {
+        let state: Rc<dyn Any> = Rc::new(RefCell::new(value));
+        states.borrow_mut().entry(key).or_default().push((scope, state));
+    });
+    ScopedState { key, scope }
+}
+
+/// Removes the state installed with [`scoped_state`] when dropped, restoring
+/// the one installed before it
+#[must_use = "the state is removed when the guard is dropped"]
+pub struct ScopedState {
+    key: TypeId,
+    scope: usize,
+}
+
+impl Drop for ScopedState {
+    fn drop(&mut self) {
+        // the thread-local could be already destroyed at the exit of the thread
+        let _ = STATES.try_with(|states| {
+            if let Some(stack) = states.borrow_mut().get_mut(&self.key) {
+                stack.retain(|(scope, _)| *scope != self.scope);
+            }
+        });
+    }
+}
+
+/// Turns mocks of the function off in the current thread, its calls run the
+/// original implementation until [`enable`] is called.
+///
+/// The function is named by its path, trailing segments are enough:
+/// `"foo"`, `"module::foo"` or `"Struct::baz"` for methods.
+///
+/// Example:
+/// ```rust
+/// covers::disable("foo");
+/// assert_eq!(foo("Real"), "Response: Foo = Real");
+/// covers::enable("foo");
+/// assert_eq!(foo("Mock"), "Response: Mocked(Foo = Mock)");
+/// ```
+pub fn disable(function: &str) {
+    DISABLED.with(|disabled| {
+        let mut disabled = disabled.borrow_mut();
+        if !disabled.iter().any(|name| name == function) {
+            disabled.push(function.to_string());
+        }
+    });
+}
+
+/// Turns mocks of the function disabled with [`disable`] back on in the
+/// current thread
+pub fn enable(function: &str) {
+    DISABLED.with(|disabled| disabled.borrow_mut().retain(|name| name != function));
+}
+
+/// Turns mocks of the function on in the current thread until the guard is
+/// dropped, the state the name had (see [`disable`]) is restored then, even
+/// when the test panics
+pub fn activate(function: &str) -> MockGuard {
+    MockGuard::new(function, false)
+}
+
+/// Turns mocks of the function off in the current thread until the guard is
+/// dropped, the same as [`activate`] otherwise
+pub fn deactivate(function: &str) -> MockGuard {
+    MockGuard::new(function, true)
+}
+
+/// Restores the state of the name passed to [`activate`] or [`deactivate`]
+/// when dropped
+#[must_use = "the previous state is restored when the guard is dropped"]
+pub struct MockGuard {
+    function: String,
+    was_disabled: bool,
+}
+
+impl MockGuard {
+    fn new(function: &str, is_disabled: bool) -> MockGuard {
+        let was_disabled = DISABLED.with(|disabled| disabled.borrow().iter().any(|name| name == function));
+        if is_disabled {
+            disable(function);
+        } else {
+            enable(function);
+        }
+        MockGuard {
+            function: function.to_string(),
+            was_disabled,
+        }
+    }
+}
+
+impl Drop for MockGuard {
+    fn drop(&mut self) {
+        // the thread-local could be already destroyed at the exit of the thread
+        let _ = DISABLED.try_with(|disabled| {
+            let mut disabled = disabled.borrow_mut();
+            disabled.retain(|name| *name != self.function);
+            if self.was_disabled {
+                disabled.push(self.function.clone());
+            }
+        });
+    }
+}
+
+/// Checks whether mocks of the function are turned off with [`disable`],
+/// methods are given with the path of their type
+pub fn is_disabled(module: &str, self_type: Option<&str>, function: &str) -> bool {
+    DISABLED.with(|disabled| {
+        let disabled = disabled.borrow();
+        if disabled.is_empty() {
+            return false;
+        }
+        // `&mut crate::Struct<u8>` => `crate::Struct`
+        let path = match self_type {
+            Some(self_type) => {
+                let self_type = self_type.trim_start_matches('&').trim_start_matches("mut ");
+                let self_type = self_type.split('<').next().unwrap_or_default();
+                format!("{}::{}", self_type, function)
+            },
+            None => format!("{}::{}", module, function),
+        };
+        disabled
+            .iter()
+            .any(|name| path == *name || path.ends_with(&format!("::{}", name)))
+    })
+}
+
+/// Number of calls expected with [`expect!`](crate::expect) and checked
+/// by [`verify`]
+struct Expected {
+    function: &'static str,
+    tracker: &'static Tracker,
+    baseline: usize,
+    min: usize,
+    max: Option<usize>,
+}
+
+/// Expectation of the number of calls of a function in the current thread,
+/// counted from its creation on. Methods narrow it down, it is registered
+/// until [`verify`] is called even when the handle is dropped.
+pub struct Expectation(Rc<RefCell<Expected>>);
+
+impl Expectation {
+    /// Expects exactly `count` calls
+    pub fn times(self, count: usize) -> Self {
+        self.between(count, Some(count))
+    }
+
+    /// Expects no calls
+    pub fn never(self) -> Self {
+        self.times(0)
+    }
+
+    /// Expects `count` calls or more
+    pub fn at_least(self, count: usize) -> Self {
+        let max = self.0.borrow().max;
+        self.between(count, max)
+    }
+
+    /// Expects `count` calls or less
+    pub fn at_most(self, count: usize) -> Self {
+        let min = self.0.borrow().min;
+        self.between(min, Some(count))
+    }
+
+    fn between(self, min: usize, max: Option<usize>) -> Self {
+        {
+            let mut expected = self.0.borrow_mut();
+            expected.min = min;
+            expected.max = max;
+        }
+        self
+    }
+}
+
+/// Registers an expectation of calls of the function identified by its
+/// tracker, at least one call is expected unless narrowed down
+pub fn expect(function: &'static str, tracker: &'static Tracker) -> Expectation {
+    let expected = Rc::new(RefCell::new(Expected {
+        function,
+        tracker,
+        baseline: tracker.thread_calls(),
+        min: 1,
+        max: None,
+    }));
+    EXPECTATIONS.with(|expectations| expectations.borrow_mut().push(expected.clone()));
+    Expectation(expected)
+}
+
+/// Checks the expectations registered with [`expect!`](crate::expect) in the
+/// current thread and clears them, panics listing all the unmet ones.
+///
+/// Example:
+/// ```rust
+/// covers::expect!(db::load).times(2);
+/// covers::expect!(db::save).never();
+/// service.sync();
+/// covers::verify();
+/// ```
+#[track_caller]
+pub fn verify() {
+    let expectations = EXPECTATIONS.with(|expectations| std::mem::take(&mut *expectations.borrow_mut()));
+    let mut unmet = String::new();
+    for expected in expectations {
+        let expected = expected.borrow();
+        let calls = expected.tracker.thread_calls() - expected.baseline;
+        if calls >= expected.min && expected.max.is_none_or(|max| calls <= max) {
+            continue;
+        }
+        let range = match expected.max {
+            Some(max) if max == expected.min => format!("{}", max),
+            Some(max) if expected.min == 0 => format!("at most {}", max),
+            Some(max) => format!("{} to {}", expected.min, max),
+            None => format!("at least {}", expected.min),
+        };
+        let _ = write!(
+            unmet,
+            "\n  `{}` expected {} call(s), got {}",
+            expected.function, range, calls
+        );
+    }
+    if !unmet.is_empty() {
+        panic!("{}", tagged(&format!("unmet expectations:{}", unmet)));
+    }
+}
+
+/// Formats the value projected by `capture = |args| ..` closure from the
+/// arguments of a call: a single one is passed as is, several ones as a tuple
+/// of references
+pub fn project<A, R: fmt::Debug>(args: A, projection: impl FnOnce(A) -> R) -> String {
+    format!("{:?}", projection(args))
+}
+
+/// Checks the expectations registered in the current thread when dropped,
+/// see [`verifier`]
+#[must_use = "the expectations are checked when the verifier is dropped"]
+pub struct Verifier(());
+
+impl Verifier {
+    /// Checks the expectations now instead of at the end of the scope
+    #[track_caller]
+    pub fn verify(self) {
+        std::mem::forget(self);
+        verify();
+    }
+}
+
+impl Drop for Verifier {
+    fn drop(&mut self) {
+        // the test fails with its own panic, the expectations are not carried
+        // over to the next test running in the thread
+        if thread::panicking() {
+            EXPECTATIONS.with(|expectations| expectations.borrow_mut().clear());
+            return;
+        }
+        verify();
+    }
+}
+
+/// Returns a guard running [`verify`] when dropped, so a test not calling it
+/// explicitly still fails listing the unmet expectations.
+///
+/// Example:
+/// ```rust
+/// let _verifier = covers::verifier();
+/// covers::expect!(db::load).times(2);
+/// service.sync();
+/// ```
+pub fn verifier() -> Verifier {
+    Verifier(())
+}
+
+/// Gets the mock installed for the instance of a `per_instance` method
+/// when `mock` is `None`, otherwise installs (`Some(Some(_))`) or removes
+/// (`Some(None)`) it returning the previous one.
+/// See [`mock_instance!`](crate::mock_instance).
+pub fn instance_mock<F: Copy + 'static>(
+    function: &'static Tracker,
+    instance: usize,
+    mock: Option<Option<F>>,
+) -> Option<F> {
+    let key = (function as *const Tracker as usize, instance);
+    INSTANCE_MOCKS.with(|mocks| {
+        let mut mocks = mocks.borrow_mut();
+        let previous = match mock {
+            None => return mocks.get(&key).and_then(|mock| mock.downcast_ref::<F>()).copied(),
+            Some(Some(mock)) => mocks.insert(key, Box::new(mock)),
+            Some(None) => mocks.remove(&key),
+        };
+        previous.and_then(|mock| mock.downcast_ref::<F>().copied())
+    })
+}
+
+/// Counts a call of a `per_instance` method on the instance when `hit`
+/// is set, returns the number of calls on the instance in this thread.
+/// See [`instance_calls!`](crate::instance_calls).
+pub fn instance_calls(function: &'static Tracker, instance: usize, hit: bool) -> usize {
+    let key = (function as *const Tracker as usize, instance);
+    INSTANCE_CALLS.with(|calls| {
+        let mut calls = calls.borrow_mut();
+        if hit {
+            *calls.entry(key).or_insert(0) += 1;
+        }
+        calls.get(&key).copied().unwrap_or(0)
+    })
+}
+
+/// Function pointer of a mock swapped with [`swap_mock!`](crate::swap_mock),
+/// null when the mock provided to `#[mocked]` is in use
+#[derive(Debug, Default)]
+pub struct HotSwap(AtomicPtr<()>);
+
+impl HotSwap {
+    pub const fn new() -> Self {
+        HotSwap(AtomicPtr::new(std::ptr::null_mut()))
+    }
+
+    /// Gets the swapped mock
+    ///
+    /// # Safety
+    ///
+    /// `F` should be the function pointer type the mock was swapped with.
+    pub unsafe fn get<F: Copy>(&self) -> Option<F> {
+        const { assert!(std::mem::size_of::<F>() == std::mem::size_of::<*mut ()>()) };
+        let pointer = self.0.load(Ordering::Acquire);
+        if pointer.is_null() {
+            None
+        } else {
+            Some(std::mem::transmute_copy(&pointer))
+        }
+    }
+
+    /// Swaps the mock (`None` removes it) returning the previous one
+    ///
+    /// # Safety
+    ///
+    /// `F` should be a function pointer type, the same for all calls.
+    pub unsafe fn swap<F: Copy>(&self, mock: Option<F>) -> Option<F> {
+        const { assert!(std::mem::size_of::<F>() == std::mem::size_of::<*mut ()>()) };
+        let pointer = match mock {
+            Some(mock) => std::mem::transmute_copy(&mock),
+            None => std::ptr::null_mut(),
+        };
+        let previous: *mut () = self.0.swap(pointer, Ordering::AcqRel);
+        if previous.is_null() {
+            None
+        } else {
+            Some(std::mem::transmute_copy(&previous))
+        }
+    }
+}
+
+/// Panics for a method of a mock generated by
+/// [`#[automock]`](crate::automock) called without behavior registered
+#[track_caller]
+pub fn missing_behavior(method: &'static str, setter: &'static str) -> ! {
+    panic!(
+        "{}",
+        tagged(&format!(
+            "`{}` is called without behavior, register it with `{}`",
+            method, setter
+        ))
+    )
+}
+
+/// Runs the future to completion on the current thread.
+///
+/// Backs blocking variants of `async fn` generated with `also_blocking`,
+/// it does not drive any I/O reactor.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unparker(Thread);
+
+    impl Wake for Unparker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(Unparker(thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match Pin::as_mut(&mut future).poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Types of the arguments and the output of a function, implemented for
+/// functions of up to 12 arguments, see
+/// [`assert_same_signature!`](crate::assert_same_signature)
+pub trait Signature<Args> {
+    /// Names of the arguments' types
+    fn arguments(&self) -> Vec<&'static str>;
+    /// Name of the output's type
+    fn output(&self) -> &'static str;
+}
+
+macro_rules! signature {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> Signature<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R,
+        {
+            fn arguments(&self) -> Vec<&'static str> {
+                vec![$(std::any::type_name::<$arg>()),*]
+            }
+
+            fn output(&self) -> &'static str {
+                std::any::type_name::<R>()
+            }
+        }
+    };
+}
+
+signature!();
+signature!(A1);
+signature!(A1, A2);
+signature!(A1, A2, A3);
+signature!(A1, A2, A3, A4);
+signature!(A1, A2, A3, A4, A5);
+signature!(A1, A2, A3, A4, A5, A6);
+signature!(A1, A2, A3, A4, A5, A6, A7);
+signature!(A1, A2, A3, A4, A5, A6, A7, A8);
+signature!(A1, A2, A3, A4, A5, A6, A7, A8, A9);
+signature!(A1, A2, A3, A4, A5, A6, A7, A8, A9, A10);
+signature!(A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11);
+signature!(A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12);
+
+/// Panics with the difference of the types if the signatures of the function
+/// and the mock differ, see
+/// [`assert_same_signature!`](crate::assert_same_signature)
+pub fn assert_same_signature<A, B>(
+    function: impl Signature<A>,
+    mock: impl Signature<B>,
+    function_name: &str,
+    mock_name: &str,
+) {
+    let (expected, actual) = (function.arguments(), mock.arguments());
+    let labels = (1..=expected.len().max(actual.len())).map(|position| format!("argument {}", position));
+    let mut rows: Vec<_> = labels
+        .enumerate()
+        .map(|(index, label)| (label, expected.get(index).copied(), actual.get(index).copied()))
+        .collect();
+    rows.push(("output".to_string(), Some(function.output()), Some(mock.output())));
+    if rows.iter().all(|(_, expected, actual)| expected == actual) {
+        return;
+    }
+
+    let mut diff = format!("signatures differ (- `{}`, + `{}`):\n", function_name, mock_name);
+    for (label, expected, actual) in rows {
+        if expected == actual {
+            let _ = writeln!(diff, "  {}: {}", label, expected.unwrap_or_default());
+        } else {
+            let _ = writeln!(diff, "- {}: {}", label, expected.unwrap_or("(none)"));
+            let _ = writeln!(diff, "+ {}: {}", label, actual.unwrap_or("(none)"));
+        }
+    }
+    panic!("{}", diff.trim_end());
+}
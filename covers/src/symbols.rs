@@ -0,0 +1,72 @@
+//! Mockable `extern` functions collected from a build.
+//!
+//! With `features = ["symbols"]` the wrapper of every `extern` function marked
+//! with `#[mocked]` embeds its name into the build. A post-build step (e.g. an
+//! xtask) collects them from the library and renders cbindgen's config, so
+//! C/C++ harnesses know which entry points have test doubles:
+//! ```rust
+//! let symbols = covers::symbols::collect("target/debug/libapp.a").unwrap();
+//! std::fs::write("cbindgen.mocks.toml", covers::symbols::cbindgen_export(&symbols)).unwrap();
+//! ```
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+
+/// Prefix of the names embedded into the build
+pub const MARKER: &[u8; 23] = b"__covers_extern_symbol:";
+
+const MASK: u8 = 0x5A;
+
+const MASKED: [u8; 23] = mask(MARKER);
+
+const fn mask(bytes: &[u8; 23]) -> [u8; 23] {
+    let mut masked = [0; 23];
+    let mut index = 0;
+    while index < masked.len() {
+        masked[index] = bytes[index] ^ MASK;
+        index += 1;
+    }
+    masked
+}
+
+/// Collects names of the functions embedded into the binary at the path
+pub fn collect(binary: impl AsRef<Path>) -> io::Result<BTreeSet<String>> {
+    Ok(find(&std::fs::read(binary)?))
+}
+
+/// Collects names of the functions embedded into the content of a binary
+pub fn find(content: &[u8]) -> BTreeSet<String> {
+    // unmasked at runtime, otherwise the collector embeds the marker itself
+    let mask = std::hint::black_box(MASK);
+    let marker = MASKED.map(|byte| byte ^ mask);
+
+    let mut symbols = BTreeSet::new();
+    for (index, window) in content.windows(marker.len()).enumerate() {
+        if window != marker {
+            continue;
+        }
+        let rest = &content[index + marker.len()..];
+        let len = rest
+            .iter()
+            .position(|&byte| !(byte.is_ascii_alphanumeric() || byte == b'_'))
+            .unwrap_or(rest.len());
+        if len > 0 && rest.get(len) == Some(&0) {
+            symbols.insert(String::from_utf8_lossy(&rest[..len]).into_owned());
+        }
+    }
+    symbols
+}
+
+/// Renders the names in the format of cbindgen's config section
+pub fn cbindgen_export(symbols: &BTreeSet<String>) -> String {
+    let include = symbols
+        .iter()
+        .map(|symbol| format!("{:?}", symbol))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "# Generated by covers: `extern` functions having test doubles\n[export]\ninclude = [{}]\n",
+        include
+    )
+}
@@ -0,0 +1,6 @@
+// `--features testing` and `--features covers-active` dispatch to mocks of this
+// crate alone, other crates using `covers` are not affected
+fn main() {
+    covers::build::testing();
+    covers::build::active_feature();
+}
@@ -0,0 +1,15 @@
+//! Detection of mocks compiled into a binary
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_canary() {
+        // the test binary has mocks wired, `main` checks builds without them
+        let binary = std::env::current_exe().unwrap();
+        assert!(covers::canary::find(binary).unwrap());
+        assert!(!covers::canary::contains(b"release binary"));
+        assert!(covers::canary::contains(
+            &[b"binary ", &covers::canary::CANARY[..]].concat()
+        ));
+    }
+}
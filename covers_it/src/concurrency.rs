@@ -0,0 +1,70 @@
+//! Checks of locking behavior with `assert_not_concurrent!`
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use covers::mocked;
+
+#[mocked(mock_write)]
+pub fn write_db(_value: u32) {
+    unimplemented!("Database is not available in tests")
+}
+
+#[mocked(mock_write)]
+pub fn write_log(_value: u32) {
+    unimplemented!("Logs are not available in tests")
+}
+
+pub fn mock_write(_value: u32) {
+    thread::sleep(Duration::from_millis(20));
+}
+
+/// Writes the values one by one, the tail recursively
+#[mocked(mock_write_all)]
+pub fn write_all(_values: &[u32]) {
+    unimplemented!("Database is not available in tests")
+}
+
+pub fn mock_write_all(values: &[u32]) {
+    if let [_, tail @ ..] = values {
+        write_all(tail);
+    }
+}
+
+/// Calls `write` from a thread per value
+pub fn save_all(values: &[u32], write: fn(u32), lock: Option<&Mutex<()>>) {
+    thread::scope(|scope| {
+        for &value in values {
+            scope.spawn(move || {
+                let _lock = lock.map(|lock| lock.lock().unwrap());
+                write(value);
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_concurrent() {
+        let _guard = covers::assert_not_concurrent!(write_db);
+        save_all(&[1, 2, 3], write_db, Some(&Mutex::new(())));
+    }
+
+    #[test]
+    #[should_panic(expected = "`write_log` was executing in two or more threads simultaneously")]
+    fn test_concurrent() {
+        let _guard = covers::assert_not_concurrent!(write_log);
+        save_all(&[1, 2, 3], write_log, None);
+    }
+
+    #[test]
+    fn test_not_concurrent_recursive() {
+        // nested calls of the same thread do not overlap with others
+        let _guard = covers::assert_not_concurrent!(write_all);
+        write_all(&[1, 2, 3]);
+    }
+}
@@ -0,0 +1,81 @@
+//! Functions shaped as bindgen output: long attribute lists, raw C types
+//! and `pub unsafe extern "C"`
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+
+use covers::mocked;
+
+/// Sets a hint, returns non-zero when it was applied
+#[allow(non_snake_case, clippy::missing_safety_doc, clippy::not_unsafe_ptr_arg_deref)]
+#[mocked(mock_SDL_SetHint)]
+#[must_use]
+pub unsafe extern "C" fn SDL_SetHint(
+    name: *const ::std::os::raw::c_char,
+    value: *const c_char,
+) -> ::std::os::raw::c_int {
+    (!name.is_null() && !value.is_null()) as c_int
+}
+
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn mock_SDL_SetHint(name: *const c_char, _value: *const c_char) -> c_int {
+    CStr::from_ptr(name).to_bytes().len() as c_int
+}
+
+pub type SDL_Callback = ::std::option::Option<unsafe extern "C" fn(userdata: *mut c_void, code: c_int) -> c_int>;
+
+#[allow(non_snake_case, non_camel_case_types, clippy::too_many_arguments)]
+#[mocked(mock_SDL_AddCallback)]
+pub unsafe extern "C" fn SDL_AddCallback(
+    callback: ::std::option::Option<unsafe extern "C" fn(userdata: *mut c_void, code: c_int) -> c_int>,
+    userdata: *mut ::std::os::raw::c_void,
+    flags: u32,
+) -> c_int {
+    match callback {
+        Some(callback) => callback(userdata, flags as c_int),
+        None => -1,
+    }
+}
+
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn mock_SDL_AddCallback(callback: SDL_Callback, _userdata: *mut c_void, _flags: u32) -> c_int {
+    callback.is_some() as c_int * 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn double(_userdata: *mut c_void, code: c_int) -> c_int {
+        code * 2
+    }
+
+    #[test]
+    fn test_bindgen_output() {
+        let (name, value) = (
+            b"SDL_HINT\0".as_ptr() as *const c_char,
+            b"1\0".as_ptr() as *const c_char,
+        );
+        unsafe {
+            assert_eq!(SDL_SetHint(name, value), 8);
+            assert_eq!(_SDL_SetHint(name, value), 1);
+            assert_eq!(SDL_AddCallback(Some(double), std::ptr::null_mut(), 3), 100);
+            assert_eq!(_SDL_AddCallback(Some(double), std::ptr::null_mut(), 3), 6);
+            assert_eq!(_SDL_AddCallback(None, std::ptr::null_mut(), 3), -1);
+        }
+    }
+
+    #[test]
+    fn test_symbols() {
+        // mocked `extern` functions only, the mocks and plain functions are not listed
+        let symbols = covers::symbols::collect(std::env::current_exe().unwrap()).unwrap();
+        let expected = ["SDL_AddCallback", "SDL_SetHint", "callback"];
+        assert_eq!(symbols.iter().map(String::as_str).collect::<Vec<_>>(), expected);
+        assert_eq!(
+            covers::symbols::cbindgen_export(&symbols),
+            "# Generated by covers: `extern` functions having test doubles\n[export]\ninclude = [\"SDL_AddCallback\", \
+             \"SDL_SetHint\", \"callback\"]\n"
+        );
+        assert!(covers::symbols::find(b"__covers_extern_symbol:broken name\0").is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+//! Mocks coordinating through the state shared with `covers::state`
+
+use covers::mocked;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Users(Vec<String>);
+
+#[mocked(mock_insert)]
+pub fn insert(name: &str) -> bool {
+    unimplemented!("no database for {}", name)
+}
+
+#[mocked(mock_count)]
+pub fn count() -> usize {
+    unimplemented!("no database")
+}
+
+fn mock_insert(name: &str) -> bool {
+    covers::state::<Users>().with(|users| users.0.push(name.to_string()));
+    true
+}
+
+fn mock_count() -> usize {
+    covers::state::<Users>().with(|users| users.0.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state() {
+        assert_eq!(count(), 0);
+        assert!(insert("Agnetha"));
+        assert!(insert("Björn"));
+        assert_eq!(count(), 2);
+        assert_eq!(
+            covers::state::<Users>().get(),
+            Users(vec!["Agnetha".into(), "Björn".into()])
+        );
+        // tests run in own threads, so do threads spawned by them
+        assert_eq!(std::thread::spawn(count).join().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_scoped_state() {
+        assert!(insert("Benny"));
+        {
+            let _users = covers::scoped_state(Users(vec!["Anni-Frid".to_string()]));
+            assert_eq!(count(), 1);
+            assert!(insert("Agnetha"));
+            assert_eq!(count(), 2);
+        }
+        assert_eq!(count(), 1);
+        assert_eq!(covers::state::<Users>().set(Users::default()).0, vec!["Benny"]);
+        assert_eq!(count(), 0);
+    }
+}
@@ -0,0 +1,36 @@
+//! Functions wired to the same named mocks with `mocked_group!`
+
+covers::mocked_group! {
+    mocks => {
+        pub fn dancing_queen(age: u8) -> String {
+            format!("Dancing queen, only {}", age)
+        }
+
+        /// Doc comments are kept
+        pub(crate) fn money_money_money() -> &'static str {
+            "Must be funny"
+        }
+    }
+}
+
+mod mocks {
+    pub fn dancing_queen(age: u8) -> String {
+        format!("Mocking queen, only {}", age)
+    }
+
+    pub fn money_money_money() -> &'static str {
+        "In a mock man's world"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mocked_group() {
+        assert_eq!(dancing_queen(17), "Mocking queen, only 17");
+        assert_eq!(_dancing_queen(17), "Dancing queen, only 17");
+        assert_eq!(money_money_money(), "In a mock man's world");
+    }
+}
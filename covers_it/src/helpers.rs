@@ -0,0 +1,42 @@
+//! Test helpers mocked themselves, e.g. shared builders
+
+pub struct Order {
+    pub items: Vec<&'static str>,
+}
+
+#[cfg(test)]
+#[covers::mocked(mock_order)]
+pub fn order() -> Order {
+    Order {
+        items: vec!["fries", "shake"],
+    }
+}
+
+#[cfg(test)]
+fn mock_order() -> Order {
+    Order { items: vec![] }
+}
+
+#[cfg(test)]
+mod tests {
+    use covers::mocked;
+
+    use super::*;
+
+    #[mocked(mock_receipt)]
+    fn receipt(order: &Order) -> String {
+        order.items.join(", ")
+    }
+
+    fn mock_receipt(order: &Order) -> String {
+        format!("{} item(s)", order.items.len())
+    }
+
+    #[test]
+    fn test_mocked_helpers() {
+        assert_eq!(order().items.len(), 0);
+        assert_eq!(_order().items.len(), 2);
+        assert_eq!(receipt(&_order()), "2 item(s)");
+        assert_eq!(_receipt(&_order()), "fries, shake");
+    }
+}
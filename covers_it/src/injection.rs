@@ -0,0 +1,132 @@
+//! Trait-based dependency injection over mocked functions with `mocked_trait!`
+//! and mocks of traits generated with `#[automock]` or wired with
+//! `#[mock_trait]`
+
+use covers::mocked;
+
+covers::mocked_trait! {
+    pub trait Jukebox for DefaultJukebox {
+        #[mocked(mocks::play)]
+        pub fn play(song: &str, _: u8) -> String {
+            format!("Playing {}", song)
+        }
+
+        #[mocked(mocks::pick)]
+        pub(crate) fn pick<T: Default>() -> T {
+            T::default()
+        }
+    }
+}
+
+mod mocks {
+    pub fn play(song: &str, times: u8) -> String {
+        format!("Mocking {} x{}", song, times)
+    }
+
+    pub fn pick<T: Default>() -> T {
+        T::default()
+    }
+}
+
+/// New code depends on the trait, old code calls the functions
+pub fn party(jukebox: &impl Jukebox) -> String {
+    jukebox.play("Waterloo", jukebox.pick::<u8>() + 2)
+}
+
+#[covers::automock]
+pub trait Playlist {
+    const SIZE: usize = 2;
+
+    fn song(&self, index: usize) -> String;
+
+    fn skip(&mut self, _: usize) -> usize {
+        0
+    }
+
+    fn songs(&self) -> Vec<String> {
+        (0..Self::SIZE).map(|index| self.song(index)).collect()
+    }
+
+    async fn next(&self) -> Option<String>;
+}
+
+pub fn shuffle(playlist: &mut impl Playlist) -> String {
+    let skipped = playlist.skip(1);
+    playlist.songs()[skipped].clone()
+}
+
+pub trait Store {
+    fn get(&self, key: &str) -> Option<String>;
+
+    fn put(&mut self, key: &str, value: String);
+}
+
+pub struct Client {
+    pub puts: Vec<String>,
+}
+
+#[covers::mock_trait(get => store_mocks::get, put => store_mocks::put)]
+impl Store for Client {
+    fn get(&self, key: &str) -> Option<String> {
+        None
+    }
+
+    fn put(&mut self, key: &str, value: String) {
+        self.puts.push(format!("{}={}", key, value));
+    }
+}
+
+mod store_mocks {
+    use super::*;
+
+    pub fn get(this: &Client, key: &str) -> Option<String> {
+        Some(format!("<{}>", key))
+    }
+
+    pub fn put(this: &mut Client, key: &str, value: String) {
+        this.puts.push(format!("mocked {}", key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mocked_trait() {
+        assert_eq!(party(&DefaultJukebox), "Mocking Waterloo x2");
+        assert_eq!(play("Waterloo", 1), "Mocking Waterloo x1");
+        assert_eq!(DefaultJukebox.pick::<String>(), "");
+    }
+
+    #[test]
+    fn test_mock_trait() {
+        let mut client = Client { puts: vec![] };
+        let store: &mut dyn Store = &mut client;
+        assert_eq!(store.get("name"), Some("<name>".to_string()));
+        store.put("name", "Abba".to_string());
+        client._put("name", "Abba".to_string());
+        assert_eq!(client.puts, ["mocked name", "name=Abba"]);
+        assert_eq!(client._get("name"), None);
+    }
+
+    #[test]
+    fn test_automock() {
+        let mut playlist = MockPlaylist::default().on_song(|index| {
+            covers::runtime::state::<Vec<usize>>().with(|played| played.push(index));
+            format!("Song #{}", index)
+        });
+        assert_eq!(shuffle(&mut playlist), "Song #0");
+        assert_eq!(covers::runtime::state::<Vec<usize>>().get(), [0, 1]);
+
+        let mut playlist = playlist.on_skip(|count| count).on_next(|| None);
+        assert_eq!(shuffle(&mut playlist), "Song #1");
+        assert_eq!(covers::runtime::block_on(playlist.next()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "`MockPlaylist::next` is called without behavior, register it with `on_next`")]
+    fn test_automock_missing_behavior() {
+        covers::runtime::block_on(MockPlaylist::default().next());
+    }
+}
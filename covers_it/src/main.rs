@@ -3,9 +3,21 @@
 //! Worth to read it.
 
 #![allow(warnings)]
+#![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
 use covers::{mock, mocked};
 
+mod canary;
+mod concurrency;
+mod ffi;
+mod fixture;
+mod group;
+mod helpers;
+mod injection;
+mod manifest;
+mod options;
+mod signatures;
+
 const ORIGINAL: &str = r#"
 
     I threw a wish in the well
@@ -59,8 +71,23 @@ const COVER: &str = r#"
     "#;
 
 fn main() {
-    let args = &["the well", "never", "tell", "it fell"];
-    assert_trimmed(call_me_maybe(args), ORIGINAL);
+    // `--features covers-active` dispatches to mocks in any build, `--features
+    // testing` too via `covers_testing` cfg of the build script
+    let is_mocked = cfg!(any(feature = "covers-active", covers_testing));
+
+    // dispatch to mocks is compiled only into builds activating them
+    let binary = std::env::current_exe().unwrap();
+    assert_eq!(covers::canary::find(binary).unwrap(), is_mocked);
+
+    if is_mocked {
+        let args = &["the Web", "Dry", "Wet", "crates fail"];
+        assert_trimmed(call_me_maybe(args), COVER);
+        // the functions called above with `report` feature
+        eprint!("{}", covers::runtime::report());
+    } else {
+        let args = &["the well", "never", "tell", "it fell"];
+        assert_trimmed(call_me_maybe(args), ORIGINAL);
+    }
 }
 
 #[cfg(test)]
@@ -72,6 +99,264 @@ mod tests {
         let args = &["the Web", "Dry", "Wet", "crates fail"];
         assert_trimmed(call_me_maybe(args), COVER);
     }
+
+    #[test]
+    fn test_with_original() {
+        let original = covers::with_original!(i_threw_a_wish_in, { i_threw_a_wish_in("the well".to_string()) });
+        assert_eq!(original, "I threw a wish in the well");
+        assert_eq!(
+            i_threw_a_wish_in("the Web".to_string()),
+            "I searched for lib in the Web"
+        );
+
+        let original = covers::with_original!(Chorus::hey_i_just_met_you_and_this_is_crazy, {
+            Chorus::hey_i_just_met_you_and_this_is_crazy()
+        });
+        assert_eq!(original, "Hey, I just met you and this is crazy");
+
+        let original = covers::with_original!(pre_chorus::ripped_jeans_skin_was_showin, {
+            // only the function provided is routed to the original
+            format!("{} {}", pre_chorus::ripped_jeans_skin_was_showin(), applause())
+        });
+        assert_eq!(original, "Ripped jeans, skin was showin' It rocks!");
+    }
+
+    #[test]
+    fn test_disable() {
+        covers::disable("i_threw_a_wish_in");
+        covers::disable("Chorus::hey_i_just_met_you_and_this_is_crazy");
+        assert_eq!(i_threw_a_wish_in("the well".to_string()), "I threw a wish in the well");
+        assert!(covers::explain!(i_threw_a_wish_in).original_override);
+        assert_eq!(
+            Chorus::hey_i_just_met_you_and_this_is_crazy(),
+            "Hey, I just met you and this is crazy"
+        );
+        // the rest stays mocked
+        assert_eq!(applause(), "It rocks!");
+
+        covers::enable("i_threw_a_wish_in");
+        covers::enable("Chorus::hey_i_just_met_you_and_this_is_crazy");
+        assert_eq!(
+            i_threw_a_wish_in("the Web".to_string()),
+            "I searched for lib in the Web"
+        );
+        assert_eq!(
+            Chorus::hey_i_just_met_you_and_this_is_crazy(),
+            "Good, I create this and it is lightweight"
+        );
+
+        // paths are matched by whole segments
+        covers::disable("covers_it::i_threw_a_wish_in");
+        covers::disable("wish_in");
+        assert_eq!(i_threw_a_wish_in("the well".to_string()), "I threw a wish in the well");
+        covers::enable("covers_it::i_threw_a_wish_in");
+        assert_eq!(
+            i_threw_a_wish_in("the Web".to_string()),
+            "I searched for lib in the Web"
+        );
+    }
+
+    #[test]
+    fn test_mock_guard() {
+        covers::disable("i_threw_a_wish_in");
+        {
+            let _guard = covers::activate("i_threw_a_wish_in");
+            assert_eq!(
+                i_threw_a_wish_in("the Web".to_string()),
+                "I searched for lib in the Web"
+            );
+        }
+        assert_eq!(i_threw_a_wish_in("the well".to_string()), "I threw a wish in the well");
+        covers::enable("i_threw_a_wish_in");
+
+        // the state is restored while unwinding too
+        let result = std::panic::catch_unwind(|| {
+            let _guard = covers::deactivate("i_threw_a_wish_in");
+            assert_eq!(i_threw_a_wish_in("the well".to_string()), "I threw a wish in the well");
+            panic!("test failed");
+        });
+        assert!(result.is_err());
+        assert_eq!(
+            i_threw_a_wish_in("the Web".to_string()),
+            "I searched for lib in the Web"
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        assert_eq!(covers::calls!(i_threw_a_wish_in), 0);
+        i_threw_a_wish_in("the Web".to_string());
+        covers::with_original!(i_threw_a_wish_in, { i_threw_a_wish_in("the well".to_string()) });
+        assert_eq!(covers::calls!(i_threw_a_wish_in), 2);
+        assert_eq!(covers::calls!(Chorus::hey_i_just_met_you_and_this_is_crazy), 0);
+
+        // other threads count their own calls
+        std::thread::spawn(|| i_threw_a_wish_in("the Web".to_string()))
+            .join()
+            .unwrap();
+        assert_eq!(covers::calls!(i_threw_a_wish_in), 2);
+    }
+
+    #[test]
+    fn test_calls_many_threads() {
+        // more threads at once than the slots of the tracker, the rest count in a map,
+        // and the slots of exited threads start from zero
+        for _ in 0..2 {
+            let barrier = std::sync::Barrier::new(40);
+            std::thread::scope(|scope| {
+                for calls in 0..40 {
+                    let barrier = &barrier;
+                    scope.spawn(move || {
+                        barrier.wait();
+                        for _ in 0..calls {
+                            i_threw_a_wish_in("the Web".to_string());
+                        }
+                        assert_eq!(covers::calls!(i_threw_a_wish_in), calls);
+                        barrier.wait();
+                    });
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_expect() {
+        covers::expect!(i_threw_a_wish_in).times(2);
+        covers::expect!(Chorus::hey_i_just_met_you_and_this_is_crazy).never();
+        covers::expect!(applause).at_least(1).at_most(2);
+        i_threw_a_wish_in("the Web".to_string());
+        i_threw_a_wish_in("the well".to_string());
+        applause();
+        covers::verify();
+
+        // calls before the expectation do not count, verified ones are cleared
+        covers::expect!(i_threw_a_wish_in).never();
+        covers::verify();
+        covers::verify();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "unmet expectations:\n  `i_threw_a_wish_in` expected 2 call(s), got 1\n  `applause` expected at \
+                    least 1 call(s), got 0"
+    )]
+    fn test_verify_unmet() {
+        covers::expect!(i_threw_a_wish_in).times(2);
+        covers::expect!(applause);
+        covers::expect!(Chorus::hey_i_just_met_you_and_this_is_crazy).at_most(1);
+        i_threw_a_wish_in("the Web".to_string());
+        covers::verify();
+    }
+
+    #[test]
+    fn test_verifier() {
+        let _verifier = covers::verifier();
+        covers::expect!(i_threw_a_wish_in).times(1);
+        i_threw_a_wish_in("the Web".to_string());
+
+        let verifier = covers::verifier();
+        covers::expect!(applause).never();
+        verifier.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "unmet expectations:\n  `applause` expected 2 call(s), got 1")]
+    fn test_verifier_unmet() {
+        let _verifier = covers::verifier();
+        covers::expect!(applause).times(2);
+        applause();
+    }
+
+    #[test]
+    #[should_panic(expected = "failed on its own")]
+    fn test_verifier_panicking() {
+        let _verifier = covers::verifier();
+        covers::expect!(applause).times(2);
+        panic!("failed on its own");
+    }
+
+    covers::expectation_set! {
+        wished_once {
+            i_threw_a_wish_in.times(1);
+            applause.never();
+            Chorus::hey_i_just_met_you_and_this_is_crazy.never();
+        }
+    }
+
+    #[test]
+    fn test_expectation_set() {
+        let _verifier = covers::verifier();
+        wished_once();
+        i_threw_a_wish_in("the Web".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "`i_threw_a_wish_in` expected 1 call(s), got 0")]
+    fn test_expectation_set_unmet() {
+        wished_once();
+        covers::verify();
+    }
+
+    #[test]
+    fn test_explain() {
+        let explanation = covers::explain!(i_threw_a_wish_in);
+        assert_eq!(explanation.function, "i_threw_a_wish_in");
+        assert_eq!(explanation.gate, "test");
+        assert_eq!(explanation.mock, "i_searched_for_lib_in_the_web");
+        assert_eq!(explanation.original, "_i_threw_a_wish_in");
+        assert!(!explanation.original_override);
+        assert!(explanation.to_string().contains("mock: i_searched_for_lib_in_the_web"));
+
+        let explanation = covers::with_original!(i_threw_a_wish_in, { covers::explain!(i_threw_a_wish_in) });
+        assert!(explanation.original_override);
+
+        // other tests call the function concurrently
+        let calls = covers::explain!(i_threw_a_wish_in).calls;
+        i_threw_a_wish_in("well".to_string());
+        assert!(covers::explain!(i_threw_a_wish_in).calls > calls);
+        assert!(explanation.to_string().contains("calls: "));
+    }
+
+    #[test]
+    fn test_assert_same_signature() {
+        covers::assert_same_signature!(i_threw_a_wish_in, i_searched_for_lib_in_the_web);
+        covers::assert_same_signature!(i_looked_at_you_as, theres_no_success_all);
+        covers::assert_same_signature!(
+            Chorus::its_hard_to_look_right_at_you_baby,
+            lyrics_mocks::its_hard_to_test_crate_creating_traits
+        );
+    }
+
+    #[test]
+    fn test_assert_same_signature_drift() {
+        let result = std::panic::catch_unwind(|| {
+            covers::assert_same_signature!(dont_ask_me_i_ll, i_searched_for_lib_in_the_web);
+        });
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        // the path of `String` is up to the compiler
+        let string = std::any::type_name::<String>();
+        assert_eq!(
+            *message,
+            format!(
+                "signatures differ (- `dont_ask_me_i_ll`, + `i_searched_for_lib_in_the_web`):
+- argument 1: &str
++ argument 1: {0}
+- argument 2: &str
++ argument 2: (none)
+  output: {0}",
+                string
+            )
+        );
+    }
+
+    #[test]
+    fn test_tagged() {
+        assert_eq!(covers::runtime::test_name().as_deref(), Some("tests::test_tagged"));
+        assert_eq!(covers::runtime::tagged("call"), "covers [tests::test_tagged]: call");
+        // threads spawned by tests are tagged with their ids
+        let spawned = std::thread::spawn(|| covers::runtime::tagged("call")).join().unwrap();
+        assert!(spawned.starts_with("covers [ThreadId("));
+    }
 }
 
 #[rustfmt::skip]
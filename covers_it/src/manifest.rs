@@ -0,0 +1,52 @@
+//! Functions wired to their mocks in `covers.toml`
+
+use covers::mocked;
+
+#[mocked]
+pub fn gimme_gimme_gimme() -> String {
+    "A man after midnight".to_string()
+}
+
+pub struct Abba {}
+
+// the methods are listed with the name of the type
+#[mocked]
+impl Abba {
+    #[mocked]
+    pub fn take_a_chance_on_me(name: &str) -> String {
+        format!("Take a chance on {}", name)
+    }
+}
+
+#[mocked]
+pub fn take_a_chance_on_me(name: &str) -> String {
+    format!("Take a chance on {} too", name)
+}
+
+pub fn a_mock_after_midnight() -> String {
+    "A mock after midnight".to_string()
+}
+
+pub fn take_a_mock_on_you(name: &str) -> String {
+    format!("Take a mock on {} too", name)
+}
+
+pub struct Mocks {}
+
+impl Mocks {
+    pub fn take_a_mock_on_me(name: &str) -> String {
+        format!("Take a mock on {}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        assert_eq!(gimme_gimme_gimme(), "A mock after midnight");
+        assert_eq!(Abba::take_a_chance_on_me("me"), "Take a mock on me");
+        assert_eq!(take_a_chance_on_me("me"), "Take a mock on me too");
+    }
+}
@@ -0,0 +1,747 @@
+//! Per-invocation options of `#[mocked]`
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use covers::mocked;
+
+#[mocked(mock_always_wrapped, wrap = always)]
+pub fn always_wrapped() -> &'static str {
+    "original"
+}
+
+fn mock_always_wrapped() -> &'static str {
+    "mock"
+}
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Post-processes generated wrappers counting their calls
+macro_rules! count_calls {
+    ($(#[$meta:meta])* $vis:vis fn $name:ident($($args:tt)*) -> $output:ty $body:block) => {
+        $(#[$meta])* $vis fn $name($($args)*) -> $output {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            $body
+        }
+    };
+}
+
+#[mocked(mock_counted, plugin = count_calls)]
+pub fn counted(value: usize) -> usize {
+    value
+}
+
+fn mock_counted(value: usize) -> usize {
+    value * 2
+}
+
+#[mocked(map_original = |greeting: String| greeting.replace(char::is_numeric, "#"))]
+pub fn greeting(name: &str) -> String {
+    format!("Hello, {}! Now is {:?}", name, std::time::SystemTime::now())
+}
+
+#[mocked(mock_stacked_low)]
+#[mocked(mock_stacked_high, priority = 10)]
+pub fn stacked() -> &'static str {
+    "original"
+}
+
+fn mock_stacked_low() -> &'static str {
+    "low"
+}
+
+fn mock_stacked_high() -> &'static str {
+    "high"
+}
+
+#[mocked(mock_connect, args = tuple)]
+pub fn connect(host: &str, port: u16, secure: bool, retries: usize) -> String {
+    format!("{}:{} secure={} retries={}", host, port, secure, retries)
+}
+
+fn mock_connect(args: (&str, u16, bool, usize)) -> String {
+    match args {
+        ("localhost", _, false, _) => "refused".to_string(),
+        (host, port, ..) => format!("mock {}:{}", host, port),
+    }
+}
+
+#[mocked(mock_stripped, unused_original = strip)]
+pub fn stripped(value: u8) -> u8 {
+    value + 1
+}
+
+fn mock_stripped(value: u8) -> u8 {
+    value * 10
+}
+
+#[mocked(mock_fetch, also_blocking = fetch_blocking)]
+pub async fn fetch(id: u32) -> String {
+    format!("record {}", id)
+}
+
+async fn mock_fetch(id: u32) -> String {
+    format!("mock {}", id)
+}
+
+pub struct Replica {
+    pub name: &'static str,
+}
+
+impl Replica {
+    #[mocked(Replica::mock_read, per_instance = true)]
+    pub fn read(&self, key: &str) -> String {
+        format!("{}: {}", self.name, key)
+    }
+
+    fn mock_read(&self, key: &str) -> String {
+        format!("mock {}: {}", self.name, key)
+    }
+}
+
+fn mock_read_stale(replica: &Replica, key: &str) -> String {
+    format!("stale {}: {}", replica.name, key)
+}
+
+#[mocked(mock_buffer_len, const_passthrough = runtime_buffer_len)]
+pub const fn buffer_len() -> usize {
+    4
+}
+
+fn mock_buffer_len() -> usize {
+    1
+}
+
+pub const BUFFER: [u8; buffer_len()] = [0; buffer_len()];
+
+pub static BUFFERS: [usize; 2] = [buffer_len(), buffer_len() * 2];
+
+#[mocked(mock_is_even)]
+pub fn is_even(value: u8) -> bool {
+    value % 2 == 0
+}
+
+fn mock_is_even(value: u8) -> bool {
+    value % 2 == 1
+}
+
+/// Wrapped functions stay plain `fn` items for pattern guards and array
+/// initializers
+pub fn classify(values: &[u8]) -> [bool; 3] {
+    let mut flags = [is_even(0); 3];
+    for (flag, &value) in flags.iter_mut().zip(values) {
+        *flag = match value {
+            value if is_even(value) => true,
+            _ => false,
+        };
+    }
+    flags
+}
+
+#[mocked(mock_status, scoped = true)]
+pub fn status(code: u16) -> String {
+    format!("real {}", code)
+}
+
+fn mock_status(code: u16) -> String {
+    format!("mock {}", code)
+}
+
+fn mock_status_offline(_code: u16) -> String {
+    "offline".to_string()
+}
+
+#[mocked(mock_lookup, scoped = true)]
+pub fn lookup(key: &str) -> Option<String> {
+    Some(key.to_uppercase())
+}
+
+fn mock_lookup(key: &str) -> Option<String> {
+    Some(key.to_string())
+}
+
+#[mocked(mock_download, scoped = true)]
+pub fn download(url: &str) -> Result<Vec<u8>, String> {
+    Ok(url.as_bytes().to_vec())
+}
+
+fn mock_download(_url: &str) -> Result<Vec<u8>, String> {
+    Ok(vec![])
+}
+
+#[mocked(mock_quota, hot_swap = true)]
+pub fn quota(user: &str) -> usize {
+    user.len()
+}
+
+fn mock_quota(_user: &str) -> usize {
+    10
+}
+
+#[mocked(mock_uncovered, coverage = off, scoped = true)]
+pub fn uncovered(value: u8) -> u8 {
+    value
+}
+
+fn mock_uncovered(value: u8) -> u8 {
+    value + 1
+}
+
+#[mocked(mock_countdown, box_return, scoped = true)]
+pub fn countdown(from: u8) -> impl Iterator<Item = u8> + Send {
+    (0..=from).rev()
+}
+
+fn mock_countdown(from: u8) -> impl Iterator<Item = u8> + Send {
+    std::iter::repeat(from).take(2)
+}
+
+#[mocked(mock_welcome, box_return)]
+pub fn welcome(name: &'static str) -> impl std::future::Future<Output = String> {
+    async move { format!("Hello, {}", name) }
+}
+
+fn mock_welcome(name: &'static str) -> impl std::future::Future<Output = String> {
+    std::future::ready(format!("Mocked {}", name))
+}
+
+// e.g. a hardware simulator is swapped in by release-mode system tests
+#[mocked(mock_temperature, keep_in_release)]
+pub fn temperature(sensor: u8) -> i16 {
+    20 + sensor as i16
+}
+
+fn mock_temperature(_sensor: u8) -> i16 {
+    -273
+}
+
+// overrides decorate the mock: a discount applied to whatever the mock charges
+#[mocked(mock_price, scoped = true, hot_swap = true, overrides = wrap)]
+pub fn price(item: &str) -> u32 {
+    item.len() as u32 * 100
+}
+
+fn mock_price(item: &str) -> u32 {
+    item.len() as u32
+}
+
+#[mocked(mock_tax, overrides = forbid)]
+pub fn tax(amount: u32) -> u32 {
+    amount / 5
+}
+
+fn mock_tax(_amount: u32) -> u32 {
+    0
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub age: u8,
+}
+
+#[mocked(auto)]
+pub fn profile(id: u32) -> Profile {
+    Profile {
+        name: format!("user {}", id),
+        age: 42,
+    }
+}
+
+pub struct Mailer;
+
+impl Mailer {
+    #[mocked(mock_send, capture)]
+    pub fn send(&self, to: &str, (subject, attempts): (&str, u8)) -> bool {
+        attempts > 0 && !to.is_empty() && !subject.is_empty()
+    }
+}
+
+fn mock_send(_: &Mailer, _to: &str, _message: (&str, u8)) -> bool {
+    true
+}
+
+#[mocked(spy)]
+pub fn checksum(data: &[u8], seed: u32) -> u32 {
+    data.iter()
+        .fold(seed, |sum, byte| sum.wrapping_mul(31).wrapping_add(*byte as u32))
+}
+
+// the payload is neither cloned nor formatted for the records
+pub struct Upload {
+    pub url: String,
+    pub payload: Vec<u8>,
+}
+
+#[mocked(mock_upload, capture = |upload| upload.url.clone())]
+pub fn upload(upload: &Upload) -> usize {
+    upload.payload.len()
+}
+
+fn mock_upload(_: &Upload) -> usize {
+    0
+}
+
+#[mocked(spy, capture = |(data, _)| data.len())]
+pub fn crc(data: &[u8], seed: u32) -> u32 {
+    data.iter().fold(seed, |sum, byte| sum ^ *byte as u32)
+}
+
+// no mock, tests stub the returned values or get the original
+#[mocked(spy, scoped = true)]
+pub fn exchange_rate(currency: &str) -> Result<f32, String> {
+    Err(format!("{} is not quoted offline", currency))
+}
+
+#[mocked(mock_region, cfg = all(test, not(miri)))]
+pub fn region() -> &'static str {
+    "eu-north-1"
+}
+
+fn mock_region() -> &'static str {
+    "local"
+}
+
+// dispatched under Miri only, which could not reach the metadata service
+#[mocked(mock_zone, cfg = miri)]
+pub fn zone() -> &'static str {
+    "eu-north-1a"
+}
+
+fn mock_zone() -> &'static str {
+    "local-a"
+}
+
+// taken by an unrelated item, so the original of `brightness` is renamed
+// otherwise
+pub fn _brightness() -> u8 {
+    0
+}
+
+#[mocked(mock_brightness, prefix = "orig_")]
+pub fn brightness(raw: u8) -> u8 {
+    raw.min(100)
+}
+
+#[covers::mock]
+fn mock_brightness(raw: u8) -> u8 {
+    orig_brightness(raw) / 2
+}
+
+pub mod vault {
+    use covers::mocked;
+
+    // the original is not exported, the mock is in the same module
+    #[mocked(mock_secret, original_vis = keep)]
+    pub fn secret() -> String {
+        "hunter2".to_string()
+    }
+
+    #[covers::mock(vis = private)]
+    fn mock_secret() -> String {
+        "*".repeat(_secret().len())
+    }
+
+    #[mocked(mock_pin, original_vis = pub(crate))]
+    pub fn pin() -> u16 {
+        1234
+    }
+
+    #[covers::mock(pub(crate))]
+    fn mock_pin() -> u16 {
+        0
+    }
+}
+
+#[mocked(mock_never_called)]
+pub fn never_called() {}
+
+fn mock_never_called() {}
+
+covers::when_mocking! {
+    pub static FIXTURE: &str = "fixture";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_always() {
+        assert_eq!(always_wrapped(), "mock");
+        assert_eq!(_always_wrapped(), "original");
+    }
+
+    #[test]
+    fn test_map_original() {
+        let greeting = greeting("Agnetha");
+        assert!(greeting.starts_with("Hello, Agnetha! Now is SystemTime"));
+        assert!(!greeting.contains(char::is_numeric));
+    }
+
+    #[test]
+    fn test_stacked() {
+        assert_eq!(stacked(), "high");
+        assert_eq!(_stacked(), "original");
+    }
+
+    #[test]
+    fn test_args_tuple() {
+        assert_eq!(connect("localhost", 80, false, 3), "refused");
+        assert_eq!(connect("example.com", 443, true, 0), "mock example.com:443");
+        assert_eq!(
+            _connect("localhost", 80, false, 3),
+            "localhost:80 secure=false retries=3"
+        );
+    }
+
+    #[test]
+    fn test_unused_original_strip() {
+        assert_eq!(stripped(2), 20);
+        assert_eq!(covers::with_original!(stripped, { stripped(2) }), 3);
+    }
+
+    #[test]
+    fn test_also_blocking() {
+        assert_eq!(fetch_blocking(1), "mock 1");
+        assert_eq!(covers::runtime::block_on(fetch(2)), "mock 2");
+        assert_eq!(covers::with_original!(fetch, { fetch_blocking(3) }), "record 3");
+    }
+
+    #[test]
+    fn test_per_instance() {
+        let (primary, secondary) = (Replica { name: "primary" }, Replica { name: "secondary" });
+        covers::mock_instance!(Replica::read, &secondary, mock_read_stale);
+        assert_eq!(primary.read("a"), "mock primary: a");
+        assert_eq!(secondary.read("a"), "stale secondary: a");
+
+        covers::mock_instance!(Replica::read, &primary, |replica, _| replica.name.to_uppercase());
+        assert_eq!(primary.read("a"), "PRIMARY");
+
+        covers::mock_instance!(Replica::read, &secondary, None);
+        assert_eq!(secondary.read("a"), "mock secondary: a");
+    }
+
+    #[test]
+    fn test_instance_calls() {
+        let (primary, secondary) = (Replica { name: "primary" }, Replica { name: "secondary" });
+        primary.read("a");
+        covers::with_original!(Replica::read, { primary.read("b") });
+        assert_eq!(covers::instance_calls!(Replica::read, &primary), 2);
+        assert_eq!(covers::instance_calls!(Replica::read, &secondary), 0);
+    }
+
+    #[test]
+    fn test_scoped_mock() {
+        let outer = covers::scoped_mock!(status, mock_status_offline);
+        assert_eq!(status(1), "offline");
+        {
+            let _inner = covers::scoped_mock!(status, |code| format!("inner {}", code));
+            assert_eq!(status(2), "inner 2");
+            assert_eq!(covers::with_original!(status, { status(3) }), "real 3");
+        }
+        assert_eq!(status(4), "offline");
+
+        // dropping the outer guard first keeps the inner override
+        let inner = covers::scoped_mock!(status, |_| "inner".to_string());
+        drop(outer);
+        assert_eq!(status(5), "inner");
+        drop(inner);
+        assert_eq!(status(6), "mock 6");
+    }
+
+    // a helper of a downstream crate built on the generated items
+    macro_rules! offline {
+        ($function:path) => {
+            covers::mangle!(scoped, $function)(|_| "offline".to_string())
+        };
+    }
+
+    #[test]
+    fn test_mangle() {
+        {
+            let _guard = offline!(status);
+            assert_eq!(status(1), "offline");
+        }
+        assert_eq!(status(1), "mock 1");
+        assert_eq!(covers::mangle!(original, status)(1), "real 1");
+    }
+
+    #[test]
+    fn test_scoped_mock_unwinding() {
+        let _outer = covers::scoped_mock!(status, mock_status_offline);
+        let result = std::panic::catch_unwind(|| {
+            let _inner = covers::scoped_mock!(status, |_| panic!("fixture failed"));
+            status(1)
+        });
+        assert!(result.is_err());
+        assert_eq!(status(2), "offline");
+    }
+
+    #[test]
+    fn test_returns() {
+        let stub = covers::returns!(status, "stub".to_string());
+        assert_eq!(status(1), "stub");
+        drop(stub);
+
+        let none = covers::returns_none!(lookup);
+        assert_eq!(lookup("a"), None);
+        {
+            let _some = covers::returns_some!(lookup, "b".to_string());
+            assert_eq!(lookup("a"), Some("b".to_string()));
+            // the innermost wins whether it is a mock or a stub
+            let _mock = covers::scoped_mock!(lookup, |key| Some(key.repeat(2)));
+            assert_eq!(lookup("a"), Some("aa".to_string()));
+        }
+        assert_eq!(lookup("a"), None);
+        drop(none);
+        assert_eq!(lookup("a"), Some("a".to_string()));
+
+        let _ok = covers::returns_ok!(download, vec![1, 2]);
+        assert_eq!(download("a"), Ok(vec![1, 2]));
+        assert_eq!(download("b"), Ok(vec![1, 2]));
+        let _err = covers::returns_err!(download, "offline".to_string());
+        assert_eq!(download("a"), Err("offline".to_string()));
+    }
+
+    #[test]
+    fn test_swap_mock() {
+        assert_eq!(quota("abba"), 10);
+        assert_eq!(covers::swap_mock!(quota, |user| user.len() * 100), None);
+        // shared by all threads
+        assert_eq!(std::thread::spawn(|| quota("abba")).join().unwrap(), 400);
+        assert!(covers::swap_mock!(quota, None).is_some());
+        assert_eq!(quota("abba"), 10);
+        assert_eq!(covers::with_original!(quota, { quota("abba") }), 4);
+    }
+
+    #[test]
+    fn test_coverage_off() {
+        assert_eq!(uncovered(1), 2);
+        let _guard = covers::returns!(uncovered, 5);
+        assert_eq!(uncovered(1), 5);
+        assert_eq!(covers::with_original!(uncovered, { uncovered(1) }), 1);
+    }
+
+    #[test]
+    fn test_box_return() {
+        assert_eq!(countdown(3).collect::<Vec<_>>(), vec![3, 3]);
+        assert_eq!(_countdown(3).collect::<Vec<_>>(), vec![3, 2, 1, 0]);
+        {
+            let _guard = covers::scoped_mock!(countdown, |from| Box::new(0..from));
+            assert_eq!(countdown(3).collect::<Vec<_>>(), vec![0, 1, 2]);
+        }
+        assert_eq!(covers::runtime::block_on(welcome("Abba")), "Mocked Abba");
+        assert_eq!(covers::runtime::block_on(_welcome("Abba")), "Hello, Abba");
+    }
+
+    #[test]
+    fn test_keep_in_release() {
+        assert_eq!(temperature(1), -273);
+        assert_eq!(covers::swap_mock!(temperature, |sensor| sensor as i16 * 10), None);
+        assert_eq!(std::thread::spawn(|| temperature(2)).join().unwrap(), 20);
+        assert!(covers::swap_mock!(temperature, None).is_some());
+        assert_eq!(temperature(1), -273);
+        assert_eq!(covers::with_original!(temperature, { temperature(1) }), 21);
+    }
+
+    #[test]
+    fn test_overrides() {
+        assert_eq!(price("tea"), 3);
+        {
+            let _guard = covers::scoped_mock!(price, |next, item| next(item) * 2);
+            assert_eq!(price("tea"), 6);
+        }
+        assert_eq!(covers::swap_mock!(price, |next, item| next(item) + 1), None);
+        assert_eq!(price("tea"), 4);
+        assert!(covers::swap_mock!(price, None).is_some());
+        assert_eq!(covers::with_original!(price, { price("tea") }), 300);
+        assert_eq!(tax(100), 0);
+    }
+
+    #[test]
+    fn test_auto() {
+        assert_eq!(profile(1), Profile::default());
+        profile_mock::return_value(Profile {
+            name: "Frida".to_string(),
+            age: 27,
+        });
+        assert_eq!(profile(2).name, "Frida");
+        assert_eq!(covers::with_original!(profile, { profile(3) }).age, 42);
+    }
+
+    #[test]
+    fn test_const_passthrough() {
+        assert_eq!(BUFFER.len(), 4);
+        assert_eq!(buffer_len(), 4);
+        assert_eq!(runtime_buffer_len(), 1);
+        assert_eq!(covers::with_original!(buffer_len, { runtime_buffer_len() }), 4);
+        assert_eq!(BUFFERS, [4, 8]);
+    }
+
+    #[test]
+    fn test_guards_and_initializers() {
+        assert_eq!(classify(&[1, 2]), [true, false, false]);
+        assert_eq!(covers::with_original!(is_even, { classify(&[1, 2]) }), [
+            false, true, true
+        ]);
+    }
+
+    #[test]
+    fn test_original_of() {
+        // resolved from another module without knowing the prefix
+        let original = covers::original_of!(crate::i_threw_a_wish_in)("the well".to_string());
+        assert_eq!(original, "I threw a wish in the well");
+        assert_eq!(
+            covers::original_of!(crate::Chorus::hey_i_just_met_you_and_this_is_crazy)(),
+            "Hey, I just met you and this is crazy"
+        );
+    }
+
+    #[test]
+    fn test_plugin() {
+        assert_eq!(counted(1), 2);
+        assert_eq!(counted(2), 4);
+        assert_eq!(_counted(3), 3);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_report() {
+        counted(5);
+        assert_eq!(covers::calls!(never_called), 0);
+        let report = covers::runtime::report();
+        assert!(report.contains("covers_it::options::counted => mock_counted"));
+        let (_, unused) = report.split_once("never exercised:").unwrap();
+        assert!(unused.contains("covers_it::options::never_called => mock_never_called"));
+        assert!(!unused.contains("covers_it::options::counted "));
+    }
+
+    #[test]
+    fn test_capture() {
+        assert!(covers::captured!(Mailer::send).is_empty());
+        Mailer.send("abba@example.com", ("Waterloo", 1));
+        covers::with_original!(Mailer::send, { Mailer.send("", ("SOS", 0)) });
+        assert_eq!(covers::captured!(Mailer::send), [
+            r#""abba@example.com", ("Waterloo", 1)"#,
+            r#""", ("SOS", 0)"#
+        ]);
+    }
+
+    #[test]
+    fn test_tagged_calls() {
+        checksum(b"a", 0);
+        covers::tag_next_call!(checksum, "retry");
+        checksum(b"b", 1);
+        checksum(b"c", 2);
+        covers::tag_next_call!(checksum, "retry");
+        Mailer.send("abba@example.com", ("Waterloo", checksum(&[], 3) as u8));
+
+        let retry = covers::tagged!(checksum, "retry");
+        assert_eq!(retry.calls(), 2);
+        assert_eq!(retry.captured(), ["[98], 1", "[], 3"]);
+        assert_eq!(retry.returned(), ["129", "3"]);
+        assert_eq!(covers::captured!(checksum).len(), 4);
+        assert!(covers::tagged!(Mailer::send, "retry").captured().is_empty());
+        assert_eq!(covers::tagged!(checksum, "other").calls(), 0);
+    }
+
+    #[test]
+    fn test_capture_projection() {
+        let payload = vec![0; 1 << 20];
+        upload(&Upload {
+            url: "https://abba.com/waterloo".to_string(),
+            payload,
+        });
+        assert_eq!(covers::captured!(upload), [r#""https://abba.com/waterloo""#]);
+
+        assert_eq!(crc(b"ab", 1), 2);
+        assert_eq!(covers::captured!(crc), ["2"]);
+        assert_eq!(covers::returned!(crc), ["2"]);
+    }
+
+    #[test]
+    fn test_spy() {
+        assert_eq!(checksum(b"ab", 0), 3105);
+        assert_eq!(checksum(&[], 7), 7);
+        assert_eq!(covers::calls!(checksum), 2);
+        assert_eq!(covers::captured!(checksum), ["[97, 98], 0", "[], 7"]);
+        assert_eq!(covers::returned!(checksum), ["3105", "7"]);
+        assert_eq!(covers::explain!(checksum).mock, "spy");
+    }
+
+    #[test]
+    fn test_custom_gate() {
+        assert_eq!(region(), "local");
+        assert_eq!(covers::explain!(region).gate, "all(test, not(miri))");
+        assert_eq!(zone(), "eu-north-1a");
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(brightness(250), 50);
+        assert_eq!(orig_brightness(250), 100);
+        assert_eq!(_brightness(), 0);
+        assert_eq!(covers::explain!(brightness).original, "orig_brightness");
+        assert_eq!(covers::explain!(brightness).prefix, "orig_");
+    }
+
+    #[test]
+    fn test_original_vis() {
+        assert_eq!(vault::secret(), "*******");
+        assert_eq!(vault::pin(), 0);
+        assert_eq!(vault::_pin(), 1234);
+        assert_eq!(vault::mock_pin(), 0);
+    }
+
+    #[test]
+    fn test_returns_without_mock() {
+        assert_eq!(exchange_rate("SEK"), Err("SEK is not quoted offline".to_string()));
+        {
+            let _rate = covers::returns_ok!(exchange_rate, 0.1);
+            assert_eq!(exchange_rate("SEK"), Ok(0.1));
+        }
+        assert!(exchange_rate("NOK").is_err());
+        assert_eq!(covers::returned!(exchange_rate).len(), 2);
+    }
+
+    #[test]
+    fn test_thread_local_state() {
+        std::thread::spawn(|| {
+            covers::disable("options::status");
+            let _rate = covers::returns_ok!(exchange_rate, 2.0);
+            let _offline = covers::scoped_mock!(lookup, |_| None);
+            covers::expect!(tax).times(5);
+            assert_eq!(lookup("key"), None);
+            Mailer.send("abba@example.com", ("Waterloo", 1));
+            assert_eq!(status(200), "real 200");
+            assert_eq!(exchange_rate("SEK"), Ok(2.0));
+        })
+        .join()
+        .unwrap();
+
+        // nothing leaks from the other test thread
+        assert_eq!(status(200), "mock 200");
+        assert!(exchange_rate("SEK").is_err());
+        assert!(lookup("key").is_some());
+        assert!(covers::captured!(Mailer::send).is_empty());
+        assert_eq!(covers::calls!(status), 1);
+        covers::verify();
+    }
+
+    #[test]
+    fn test_when_mocking() {
+        assert_eq!(FIXTURE, "fixture");
+        covers::when_mocking! {
+            let fixture = FIXTURE.len();
+            let doubled = fixture * 2;
+        }
+        assert_eq!((fixture, doubled), (7, 14));
+    }
+
+    #[test]
+    fn test_expansion() {
+        let expansion = covers::__expansion!(mocked(mock_foo, wrap = always) fn foo() {});
+        assert!(expansion.contains("mock_foo"));
+        assert!(!expansion.replace(' ', "").contains("cfg(any(debug_assertions,test))"));
+    }
+}
@@ -0,0 +1,858 @@
+//! Functions with less common signatures
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use covers::mocked;
+
+#[mocked(mock_map)]
+pub fn map<F: for<'a> Fn(&'a str) -> String>(f: F) -> String {
+    f("original")
+}
+
+pub fn mock_map<F: for<'a> Fn(&'a str) -> String>(f: F) -> String {
+    f("mock")
+}
+
+#[mocked(mock_apply)]
+pub fn apply<F>(f: F, value: &str) -> usize
+where F: for<'a> Fn(&'a str) -> &'a str {
+    f(value).len()
+}
+
+pub fn mock_apply<F>(f: F, value: &str) -> usize
+where F: for<'a> Fn(&'a str) -> &'a str {
+    f(value).len() * 10
+}
+
+#[mocked(mock_find)]
+pub fn find<T: Ord>(items: &[T]) -> Option<&T> {
+    items.iter().max()
+}
+
+pub fn mock_find<T: Ord>(items: &[T]) -> Option<&T> {
+    items.iter().min()
+}
+
+#[mocked(mock_chunk)]
+pub fn chunk<const N: usize>(buf: &[u8]) -> [u8; N] {
+    let mut chunk = [0; N];
+    chunk.copy_from_slice(&buf[..N]);
+    chunk
+}
+
+pub fn mock_chunk<const N: usize>(buf: &[u8]) -> [u8; N] {
+    let mut chunk = [0; N];
+    chunk.copy_from_slice(&buf[buf.len() - N..]);
+    chunk
+}
+
+#[mocked(mock_longest)]
+pub fn longest<'a>(a: &'a str, b: &'a str) -> &'a str {
+    if a.len() >= b.len() { a } else { b }
+}
+
+pub fn mock_longest<'a>(a: &'a str, _b: &'a str) -> &'a str {
+    a
+}
+
+#[mocked(mock_pick)]
+pub fn pick<'a: 'b, 'b, T: AsRef<str> + 'a>(items: &'a [T], fallback: &'b str) -> &'b str {
+    items.first().map_or(fallback, T::as_ref)
+}
+
+pub fn mock_pick<'a: 'b, 'b, T: AsRef<str> + 'a>(_items: &'a [T], fallback: &'b str) -> &'b str {
+    fallback
+}
+
+#[mocked(mock_render)]
+pub fn render<T, E>(value: T) -> Result<String, E>
+where
+    T: std::fmt::Debug + Send,
+    E: Default, {
+    Ok(format!("{:?}", value))
+}
+
+pub fn mock_render<T, E>(_value: T) -> Result<String, E>
+where
+    T: std::fmt::Debug + Send,
+    E: Default, {
+    Err(E::default())
+}
+
+#[mocked(mock_leading)]
+pub fn leading<I>(items: I) -> Option<<I as IntoIterator>::Item>
+where
+    I: IntoIterator,
+    I::Item: Clone, {
+    items.into_iter().next()
+}
+
+pub fn mock_leading<I>(items: I) -> Option<<I as IntoIterator>::Item>
+where
+    I: IntoIterator,
+    I::Item: Clone, {
+    items.into_iter().last()
+}
+
+#[mocked(mock_read_raw, scoped = true)]
+pub unsafe fn read_raw(data: *const u8, index: usize) -> u8 {
+    *data.add(index)
+}
+
+pub unsafe fn mock_read_raw(data: *const u8, _index: usize) -> u8 {
+    *data
+}
+
+#[mocked(mock_gnarly)]
+pub fn gnarly(value: u8) -> u8 {
+    macro_rules! double {
+        ($value:expr) => {{
+            fn inner(value: u8) -> u8 {
+                value * 2
+            }
+            inner($value)
+        }};
+    }
+    struct Local {
+        value: u8,
+    }
+    impl Local {
+        fn get(&self) -> u8 {
+            self.value
+        }
+    }
+    let future = async move { value };
+    drop(future);
+    let closure = |value: u8| -> u8 { value + 1 };
+    double!(closure(Local { value }.get()))
+}
+
+pub fn mock_gnarly(value: u8) -> u8 {
+    value
+}
+
+#[mocked(mock_ignore)]
+#[allow(unused_variables)]
+pub fn ignore(_: u8, _unused: &str, used: bool) -> bool {
+    used
+}
+
+pub fn mock_ignore(value: u8, text: &str, used: bool) -> bool {
+    value as usize == text.len() && !used
+}
+
+pub struct Size {
+    pub width: u32,
+    pub depth: u32,
+}
+
+#[mocked(mock_area)]
+pub fn area((w, h): (u32, u32), &Size { depth, .. }: &Size) -> u32 {
+    w * h * depth
+}
+
+pub fn mock_area(sides: (u32, u32), size: &Size) -> u32 {
+    sides.0 + sides.1 + size.width
+}
+
+#[mocked(mock_volume, unused_original = strip)]
+pub fn volume([w, h, d]: [u32; 3]) -> u32 {
+    w * h * d
+}
+
+pub fn mock_volume(sides: [u32; 3]) -> u32 {
+    sides.iter().sum()
+}
+
+#[mocked(mock_pad, hot_swap = true)]
+pub fn pad(mut text: String, width: usize) -> String {
+    while text.len() < width {
+        text.push(' ');
+    }
+    text
+}
+
+pub fn mock_pad(text: String, width: usize) -> String {
+    format!("{}/{}", text, width)
+}
+
+#[mocked(mock_trim, unused_original = strip)]
+pub fn trim(mut text: String) -> String {
+    text.truncate(text.trim_end().len());
+    text
+}
+
+pub fn mock_trim(text: String) -> String {
+    text.to_uppercase()
+}
+
+#[mocked(mock_checksum, inline = wrapper)]
+#[cfg_attr(all(), doc = "Sums the bytes")]
+#[cfg_attr(debug_assertions, inline)]
+#[cfg_attr(test, must_use, allow(clippy::cast_lossless))]
+pub fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().map(|byte| *byte as u32).sum()
+}
+
+pub fn mock_checksum(bytes: &[u8]) -> u32 {
+    bytes.len() as u32
+}
+
+#[mocked(mock_handle)]
+pub fn handle(mut reader: impl std::io::Read, limit: impl Into<usize> + Copy) -> usize {
+    let mut buffer = String::new();
+    reader.read_to_string(&mut buffer).unwrap();
+    buffer.len().min(limit.into())
+}
+
+pub fn mock_handle(_reader: impl std::io::Read, limit: impl Into<usize>) -> usize {
+    limit.into() + 1
+}
+
+#[mocked(mock_first, unused_original = strip)]
+pub fn first<T: Clone>(items: &[T], fallback: impl Fn() -> T) -> T {
+    items.first().cloned().unwrap_or_else(fallback)
+}
+
+pub fn mock_first<T: Clone>(_items: &[T], fallback: impl Fn() -> T) -> T {
+    fallback()
+}
+
+#[mocked(mock_render_to)]
+pub fn render_to(out: &mut impl std::fmt::Write, value: u8) -> std::fmt::Result {
+    write!(out, "{}", value)
+}
+
+pub fn mock_render_to(out: &mut impl std::fmt::Write, value: u8) -> std::fmt::Result {
+    write!(out, "<{}>", value)
+}
+
+#[mocked(mock_log, scoped = true)]
+pub fn log(sink: &mut dyn std::io::Write, line: &str) -> usize {
+    sink.write(line.as_bytes()).unwrap()
+}
+
+pub fn mock_log(sink: &mut dyn std::io::Write, _line: &str) -> usize {
+    sink.write(b"mock").unwrap()
+}
+
+fn mock_log_silent(_sink: &mut dyn std::io::Write, _line: &str) -> usize {
+    0
+}
+
+#[mocked(mock_callback, scoped = true)]
+pub extern "C" fn callback(data: *const u8, len: usize) -> i32 {
+    len as i32 - data.is_null() as i32
+}
+
+pub extern "C" fn mock_callback(_data: *const u8, len: usize) -> i32 {
+    -(len as i32)
+}
+
+extern "C" fn mock_callback_null(data: *const u8, _len: usize) -> i32 {
+    data.is_null() as i32
+}
+
+/// Produces a type as typestate or pin-projection macros do
+macro_rules! door {
+    (Open) => { Door<true> };
+    (Closed) => { Door<false> };
+}
+
+pub struct Door<const OPEN: bool>;
+
+#[mocked(mock_open)]
+pub fn open() -> door! { Open } {
+    Door
+}
+
+pub fn mock_open() -> door!(Open) {
+    Door
+}
+
+#[mocked(mock_close)]
+pub fn close(_door: door![Open]) -> Door<{ !true }> {
+    Door
+}
+
+pub fn mock_close(_door: door![Open]) -> door! { Closed } {
+    Door
+}
+
+#[mocked(mock_split)]
+pub fn split(s: &str, limits: HashMap<char, usize>) -> (Vec<String>, HashMap<String, usize>) {
+    let words: Vec<String> = s.split(' ').map(str::to_string).collect();
+    let lengths = words
+        .iter()
+        .map(|word| (word.clone(), word.len().min(limits[&'*'])))
+        .collect();
+    (words, lengths)
+}
+
+pub fn mock_split(s: &str, _limits: HashMap<char, usize>) -> (Vec<String>, HashMap<String, usize>) {
+    (vec![s.to_string()], HashMap::new())
+}
+
+pub struct Meters(pub f64);
+
+pub struct Feet(pub f64);
+
+impl From<Meters> for Feet {
+    fn from(meters: Meters) -> Self {
+        Feet(meters.0 * 3.28)
+    }
+}
+
+impl Meters {
+    #[mocked(mock_convert)]
+    pub fn convert<T: From<Self>>(self) -> T {
+        T::from(self)
+    }
+}
+
+pub fn mock_convert<T: From<Meters>>(meters: Meters) -> T {
+    T::from(Meters(meters.0 * 2.0))
+}
+
+impl Meters {
+    #[mocked(Meters::mock_try_convert)]
+    pub fn try_convert<T>(mut self) -> Option<T>
+    where T: From<Self> {
+        self.0 += 0.0;
+        Some(T::from(self))
+    }
+
+    fn mock_try_convert<T>(self) -> Option<T>
+    where T: From<Self> {
+        None
+    }
+
+    #[mocked(Meters::mock_describe)]
+    pub fn describe<T: std::fmt::Debug + Default>(&self) -> String {
+        format!("{:?} {}", T::default(), self.0)
+    }
+
+    fn mock_describe<T: std::fmt::Debug + Default>(&self) -> String {
+        format!("mock {:?} {}", T::default(), self.0)
+    }
+
+    #[mocked(meters_mocks::mock_scaled)]
+    pub fn scaled(&self, factor: u32) -> f64 {
+        self.0 * factor as f64
+    }
+
+    #[mocked(meters_mocks::mock_stretch)]
+    pub fn stretch(&mut self, by: f64) {
+        self.0 += by;
+    }
+}
+
+impl Meters {
+    #[mocked(meters_mocks::mock_into_feet, scoped = true)]
+    pub fn into_feet(self: Box<Self>) -> Feet {
+        Feet::from(*self)
+    }
+
+    #[mocked(meters_mocks::mock_shared, per_instance = true)]
+    pub fn shared(self: Rc<Self>, by: f64) -> f64 {
+        self.0 + by
+    }
+
+    #[mocked(meters_mocks::mock_grow)]
+    pub fn grow(mut self: Pin<&mut Self>) {
+        self.0 += 1.0;
+    }
+}
+
+// free functions receive `&self` as `&Meters` and `&mut self` as `&mut Meters`,
+// typed receivers are passed as is, e.g. `self: Box<Self>` as `Box<Meters>`
+mod meters_mocks {
+    use std::pin::Pin;
+    use std::rc::Rc;
+
+    use super::{Feet, Meters};
+
+    pub fn mock_scaled(meters: &Meters, factor: u32) -> f64 {
+        meters.0 + factor as f64
+    }
+
+    pub fn mock_stretch(meters: &mut Meters, by: f64) {
+        meters.0 -= by;
+    }
+
+    pub fn mock_into_feet(meters: Box<Meters>) -> Feet {
+        Feet(meters.0)
+    }
+
+    pub fn mock_shared(meters: Rc<Meters>, by: f64) -> f64 {
+        meters.0 - by
+    }
+
+    pub fn mock_grow(mut meters: Pin<&mut Meters>) {
+        meters.0 -= 1.0;
+    }
+}
+
+pub struct Parser<'a> {
+    input: &'a str,
+}
+
+impl Parser<'_> {
+    #[mocked(Parser::mock_token)]
+    pub fn token(&self) -> &str {
+        self.input.split(' ').next().unwrap_or_default()
+    }
+
+    fn mock_token(&self) -> &str {
+        self.input
+    }
+
+    #[mocked(Parser::mock_rest)]
+    pub fn rest(&mut self, parser: &Parser<'_>) -> Option<&'_ str> {
+        self.input.strip_prefix(parser.input)
+    }
+
+    fn mock_rest(&mut self, _parser: &Parser<'_>) -> Option<&'_ str> {
+        None
+    }
+}
+
+impl<'a> Parser<'a> {
+    #[mocked(Parser::mock_parse, scope = impl)]
+    pub fn parse(input: &'a str) -> Parser<'a> {
+        Parser { input }
+    }
+
+    fn mock_parse(input: &'a str) -> Parser<'a> {
+        Parser { input: input.trim() }
+    }
+
+    // `Self` tells it is a method, no `scope = impl` hint is needed
+    #[mocked(Parser::mock_empty)]
+    pub fn empty() -> Self {
+        Parser { input: "" }
+    }
+
+    fn mock_empty() -> Self {
+        Parser { input: "mock" }
+    }
+
+    #[mocked(Parser::mock_is_blank)]
+    pub fn is_blank(input: &'a str) -> bool {
+        Self::parse(input).input.trim().is_empty()
+    }
+
+    fn mock_is_blank(_input: &'a str) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stack<T>(pub Vec<T>);
+
+// `Self` could not be named outside of the impl, so the mocks spell `Stack<T>`
+impl<T: Clone + Default + 'static> Stack<T> {
+    #[mocked(stacks_mocks::new, scoped = true)]
+    pub fn new() -> Self {
+        Stack(Vec::new())
+    }
+
+    #[mocked(stacks_mocks::of)]
+    pub fn of(value: T) -> Self {
+        Stack(vec![value])
+    }
+}
+
+#[covers::mocked_impl(default => stacks_mocks::default)]
+impl<T: Clone + Default + 'static> Default for Stack<T> {
+    fn default() -> Self {
+        Self::of(T::default())
+    }
+}
+
+mod stacks_mocks {
+    use super::*;
+
+    pub fn new<T: Default>() -> Stack<T> {
+        Stack(vec![T::default()])
+    }
+
+    pub fn of<T: Clone>(value: T) -> Stack<T> {
+        Stack(vec![value.clone(), value])
+    }
+
+    pub fn default<T>() -> Stack<T> {
+        Stack(Vec::new())
+    }
+}
+
+pub trait Measure {
+    fn size(&self) -> usize;
+}
+
+impl Measure for &Meters {
+    #[mocked(mock_size_of_ref, scope = trait)]
+    fn size(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+fn mock_size_of_ref(meters: &&Meters) -> usize {
+    meters.0 as usize * 100
+}
+
+impl<T: AsRef<str>> Measure for T {
+    #[mocked(mock_size_of_text, scope = trait)]
+    fn size(&self) -> usize {
+        self.as_ref().len()
+    }
+}
+
+fn mock_size_of_text<T: AsRef<str>>(text: &T) -> usize {
+    text.as_ref().len() + 1
+}
+
+pub trait Greeter {
+    fn greet(&self, name: &str) -> String;
+
+    fn shout(&self, name: &str) -> String;
+}
+
+pub trait Farewell {
+    async fn wave(&self) -> String;
+}
+
+pub struct English;
+
+// the originals and the hidden items move to `impl English`
+#[mocked]
+impl Greeter for English {
+    #[mocked(English::mock_greet, scoped = true)]
+    fn greet(&self, name: &str) -> String {
+        format!("Hello, {}", name)
+    }
+
+    fn shout(&self, name: &str) -> String {
+        self.greet(name).to_uppercase()
+    }
+}
+
+#[mocked]
+impl Farewell for English {
+    #[mocked(English::mock_wave)]
+    async fn wave(&self) -> String {
+        "Bye".to_string()
+    }
+}
+
+impl English {
+    fn mock_greet(&self, name: &str) -> String {
+        format!("Hi, {}", name)
+    }
+
+    async fn mock_wave(&self) -> String {
+        "Mocked bye".to_string()
+    }
+}
+
+pub struct Polish;
+
+// static methods need no `scope = impl` hint, options are still passed to
+// `#[mocked]`
+#[covers::mocked_impl(greet => Polish::mock_greet, polite => greeters_mocks::polite)]
+impl Polish {
+    pub fn greet(name: &str) -> String {
+        format!("Dzień dobry, {}", name)
+    }
+
+    #[mocked(scoped = true)]
+    pub fn polite(&self, name: &str) -> String {
+        format!("Pan {}", name)
+    }
+
+    fn mock_greet(name: &str) -> String {
+        format!("Cześć, {}", name)
+    }
+}
+
+pub struct German;
+
+#[covers::mocked_impl(greet => greeters_mocks::greet_german)]
+impl Greeter for German {
+    fn greet(&self, name: &str) -> String {
+        format!("Hallo, {}", name)
+    }
+
+    fn shout(&self, name: &str) -> String {
+        self.greet(name).to_uppercase()
+    }
+}
+
+mod greeters_mocks {
+    use super::*;
+
+    pub fn polite(this: &Polish, name: &str) -> String {
+        format!("Pani {}", name)
+    }
+
+    pub fn greet_german(this: &German, name: &str) -> String {
+        format!("Servus, {}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hrtb() {
+        assert_eq!(map(|value| value.to_uppercase()), "MOCK");
+        assert_eq!(_map(|value| value.to_uppercase()), "ORIGINAL");
+        assert_eq!(apply(str::trim, " abc "), 30);
+    }
+
+    #[test]
+    fn test_generics() {
+        assert_eq!(find(&[2, 1, 3]), Some(&1));
+        assert_eq!(_find(&[2, 1, 3]), Some(&3));
+        assert_eq!(find::<&str>(&[]), None);
+        assert_eq!(leading(vec![1, 2, 3]), Some(3));
+        assert_eq!(_leading("ab".chars()), Some('a'));
+    }
+
+    #[test]
+    fn test_trait_impl() {
+        let greeter: &dyn Greeter = &English;
+        assert_eq!(greeter.greet("Abba"), "Hi, Abba");
+        assert_eq!(greeter.shout("Abba"), "HI, ABBA");
+        assert_eq!(English._greet("Abba"), "Hello, Abba");
+        assert_eq!(
+            covers::with_original!(English::greet, { English.shout("Abba") }),
+            "HELLO, ABBA"
+        );
+        {
+            let _guard = covers::scoped_mock!(English::greet, |_, name| format!("Yo, {}", name));
+            assert_eq!(greeter.greet("Abba"), "Yo, Abba");
+        }
+        assert_eq!(covers::runtime::block_on(English.wave()), "Mocked bye");
+        assert_eq!(covers::runtime::block_on(English._wave()), "Bye");
+    }
+
+    #[test]
+    fn test_mocked_impl() {
+        assert_eq!(Polish::greet("Ania"), "Cześć, Ania");
+        assert_eq!(Polish::_greet("Ania"), "Dzień dobry, Ania");
+        assert_eq!(Polish.polite("Nowak"), "Pani Nowak");
+        {
+            let _guard = covers::scoped_mock!(Polish::polite, |_, name| format!("Panie {}", name));
+            assert_eq!(Polish.polite("Nowak"), "Panie Nowak");
+        }
+        assert_eq!(Polish._polite("Nowak"), "Pan Nowak");
+        assert_eq!(German.shout("Hans"), "SERVUS, HANS");
+        assert_eq!(German._greet("Hans"), "Hallo, Hans");
+    }
+
+    #[test]
+    fn test_const_generics() {
+        assert_eq!(chunk::<2>(&[1, 2, 3]), [2, 3]);
+        assert_eq!(_chunk::<2>(&[1, 2, 3]), [1, 2]);
+        let chunk: [u8; 1] = chunk(&[1, 2, 3]);
+        assert_eq!(chunk, [3]);
+    }
+
+    #[test]
+    fn test_lifetimes() {
+        assert_eq!(longest("a", "bc"), "a");
+        assert_eq!(_longest("a", "bc"), "bc");
+        assert_eq!(pick(&["call"], "me"), "me");
+        assert_eq!(_pick(&["call"], "me"), "call");
+    }
+
+    #[test]
+    fn test_where_clause() {
+        assert_eq!(render::<_, u8>(42), Err(0));
+        assert_eq!(_render::<_, u8>(42), Ok("42".to_string()));
+    }
+
+    #[test]
+    fn test_receiver_with_generics() {
+        let feet: Feet = Meters(1.0).convert();
+        assert_eq!(feet.0, 6.56);
+        assert_eq!(Meters(1.0)._convert::<Feet>().0, 3.28);
+        assert!(Meters(1.0).try_convert::<Feet>().is_none());
+        assert_eq!(Meters(1.0).describe::<u8>(), "mock 0 1");
+        assert_eq!(Meters(1.0)._describe::<bool>(), "false 1");
+    }
+
+    #[test]
+    fn test_tuples_and_nested_generics() {
+        let limits: HashMap<char, usize> = vec![('*', 3)].into_iter().collect();
+        assert_eq!(
+            split("call me", limits.clone()),
+            (vec!["call me".to_string()], HashMap::new())
+        );
+
+        let (words, lengths) = _split("call me", limits);
+        assert_eq!(words, vec!["call", "me"]);
+        assert_eq!(lengths["call"], 3);
+    }
+
+    #[test]
+    fn test_unsafe() {
+        let data = [1u8, 2, 3];
+        unsafe {
+            assert_eq!(read_raw(data.as_ptr(), 2), 1);
+            assert_eq!(_read_raw(data.as_ptr(), 2), 3);
+            let _guard = covers::scoped_mock!(read_raw, |data, index| *data.add(index - 1));
+            assert_eq!(read_raw(data.as_ptr(), 2), 2);
+        }
+    }
+
+    #[test]
+    fn test_gnarly_body() {
+        assert_eq!(gnarly(1), 1);
+        assert_eq!(_gnarly(1), 4);
+    }
+
+    #[test]
+    fn test_extern() {
+        let data = [1u8, 2, 3];
+        assert_eq!(callback(data.as_ptr(), data.len()), -3);
+        assert_eq!(_callback(data.as_ptr(), data.len()), 3);
+        let _guard = covers::scoped_mock!(callback, mock_callback_null);
+        assert_eq!(callback(std::ptr::null(), 0), 1);
+    }
+
+    #[test]
+    fn test_macro_output() {
+        let _: Door<false> = close(open());
+        let _: Door<false> = _close(_open());
+    }
+
+    #[test]
+    fn test_anonymous_lifetimes() {
+        let mut parser = Parser::parse(" call me ");
+        assert_eq!(parser.token(), "call me");
+        assert_eq!(parser._token(), "call");
+        assert_eq!(parser.rest(&Parser::_parse("call")), None);
+        assert_eq!(parser._rest(&Parser::parse("call")), Some(" me"));
+    }
+
+    #[test]
+    fn test_self_detection() {
+        assert_eq!(Parser::empty().token(), "mock");
+        assert_eq!(Parser::_empty().input, "");
+        assert!(!Parser::is_blank(" "));
+        assert!(Parser::_is_blank(" "));
+    }
+
+    #[test]
+    fn test_self_constructors() {
+        assert_eq!(Stack::<u8>::new(), Stack(vec![0]));
+        assert_eq!(Stack::of(1), Stack(vec![1, 1]));
+        assert_eq!(Stack::<u8>::default(), Stack(vec![]));
+        assert_eq!(Stack::<u8>::_default(), Stack(vec![0, 0]));
+
+        let _stub = covers::returns!(Stack::<u8>::new, Stack(vec![7]));
+        assert_eq!(Stack::<u8>::new(), Stack(vec![7]));
+    }
+
+    #[test]
+    fn test_trait_impls() {
+        assert_eq!((&Meters(2.0)).size(), 200);
+        assert_eq!("abba".size(), 5);
+        assert_eq!(String::from("abba").size(), 5);
+    }
+
+    #[test]
+    fn test_wildcard_args() {
+        assert!(ignore(3, "abc", false));
+        assert!(_ignore(3, "abc", true));
+    }
+
+    #[test]
+    fn test_cfg_attr() {
+        assert_eq!(checksum(&[1, 2, 3]), 3);
+        assert_eq!(_checksum(&[1, 2, 3]), 6);
+    }
+
+    #[test]
+    fn test_receivers_in_free_mocks() {
+        let mut meters = Meters(2.0);
+        assert_eq!(meters.scaled(3), 5.0);
+        assert_eq!(meters._scaled(3), 6.0);
+        meters.stretch(0.5);
+        assert_eq!(meters.0, 1.5);
+        meters._stretch(0.5);
+        assert_eq!(meters.0, 2.0);
+    }
+
+    #[test]
+    fn test_typed_receivers() {
+        assert_eq!(Box::new(Meters(2.0)).into_feet().0, 2.0);
+        {
+            let _guard = covers::scoped_mock!(Meters::into_feet, |meters| Feet(meters.0 * 10.0));
+            assert_eq!(Box::new(Meters(2.0)).into_feet().0, 20.0);
+        }
+        assert_eq!(Box::new(Meters(2.0))._into_feet().0, 6.56);
+
+        let (first, second) = (Rc::new(Meters(2.0)), Rc::new(Meters(2.0)));
+        covers::mock_instance!(Meters::shared, &*second, |meters, by| meters.0 * by);
+        assert_eq!(first.clone().shared(3.0), -1.0);
+        assert_eq!(second.clone().shared(3.0), 6.0);
+        assert_eq!(covers::instance_calls!(Meters::shared, &*second), 1);
+
+        let mut meters = Meters(2.0);
+        Pin::new(&mut meters).grow();
+        assert_eq!(meters.0, 1.0);
+        Pin::new(&mut meters)._grow();
+        assert_eq!(meters.0, 2.0);
+    }
+
+    #[test]
+    fn test_impl_trait_args() {
+        assert_eq!(handle("abc".as_bytes(), 2u8), 3);
+        assert_eq!(_handle("abc".as_bytes(), 2u8), 2);
+        assert_eq!(first(&[1, 2], || 0), 0);
+        assert_eq!(covers::with_original!(first, { first(&[1, 2], || 0) }), 1);
+    }
+
+    #[test]
+    fn test_mut_trait_args() {
+        let mut out = String::new();
+        render_to(&mut out, 1).unwrap();
+        _render_to(&mut out, 2).unwrap();
+        assert_eq!(out, "<1>2");
+
+        let mut sink = vec![];
+        assert_eq!(log(&mut sink, "real"), 4);
+        assert_eq!(_log(&mut sink, "real"), 4);
+        {
+            let _guard = covers::scoped_mock!(log, mock_log_silent);
+            assert_eq!(log(&mut sink, "real"), 0);
+        }
+        assert_eq!(sink, b"mockreal");
+    }
+
+    #[test]
+    fn test_mut_args() {
+        assert_eq!(pad("ab".to_string(), 4), "ab/4");
+        assert_eq!(_pad("ab".to_string(), 4), "ab  ");
+        covers::swap_mock!(pad, |text, _| text);
+        assert_eq!(pad("ab".to_string(), 4), "ab");
+        covers::swap_mock!(pad, None);
+        assert_eq!(trim("ab ".to_string()), "AB ");
+        assert_eq!(covers::with_original!(trim, { trim("ab ".to_string()) }), "ab");
+    }
+
+    #[test]
+    fn test_pattern_args() {
+        let size = Size { width: 4, depth: 5 };
+        assert_eq!(area((2, 3), &size), 9);
+        assert_eq!(_area((2, 3), &size), 30);
+        assert_eq!(volume([2, 3, 4]), 9);
+        assert_eq!(covers::with_original!(volume, { volume([2, 3, 4]) }), 24);
+    }
+}
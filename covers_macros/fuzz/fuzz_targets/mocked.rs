@@ -0,0 +1,21 @@
+//! Expands `#[mocked(args)]` of arbitrary functions: `cargo +nightly fuzz run
+//! mocked` in `covers_macros` directory. Compile errors are fine, panics are
+//! not.
+
+#![no_main]
+// the macros are compiled whole, the harness calls `self_test` alone
+#![allow(dead_code, unused_imports)]
+
+use libfuzzer_sys::fuzz_target;
+
+covers_macros::__fuzzing!();
+
+fuzz_target!(|data: &[u8]| {
+    let source = match std::str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+    // the first line is the arguments of the attribute, the rest is the function
+    let (args, input) = source.split_once('\n').unwrap_or(("mock_foo", source));
+    let _ = self_test::try_mocked(args, input);
+});
@@ -0,0 +1,467 @@
+//! Messages of compile-time errors reported by the macros.
+//!
+//! The text is a part of the public contract: downstream crates wrapping
+//! `covers` may snapshot it with `trybuild`, so it changes only in breaking
+//! releases. Errors are reported at the span of the option or the token they
+//! are about, the macro invocation's one otherwise.
+//!
+//! All messages are constructed here, the rest of the crate only decides
+//! when to report them.
+
+use std::fmt::Display;
+use std::path::Path;
+
+pub fn invalid_priority(priority: &str) -> String {
+    format!("`priority = {}` should be an integer", priority)
+}
+
+pub fn invalid_prefix(prefix: &str) -> String {
+    format!(
+        "`prefix = {}` should be a string of identifier characters, e.g. `prefix = \"orig_\"`",
+        prefix
+    )
+}
+
+pub fn unknown_original_vis(original_vis: &str) -> String {
+    format!(
+        "Unknown `original_vis = {}`, expected `pub` (default), `keep` or a restricted one, e.g. `pub(crate)`",
+        original_vis
+    )
+}
+
+pub fn unknown_mock_vis(vis: &str) -> String {
+    format!(
+        "Unknown visibility `{}` of `#[mock]`, expected `pub`, a restricted one, e.g. `pub(crate)`, or `private`",
+        vis
+    )
+}
+
+pub fn mock_with_map_original() -> String {
+    "Either a reference to mock or `map_original` should be provided, not both".to_string()
+}
+
+pub fn missing_mock(fn_name: &str, manifest_env: &str) -> String {
+    format!(
+        "At least fully-qualified reference to mock have to be provided for `{}`! Pass it to the macro or list in the \
+         file referenced by `{}`",
+        fn_name, manifest_env
+    )
+}
+
+pub fn unknown_wrap(wrap: &str) -> String {
+    format!("Unknown `wrap = {}`, expected `debug` (default) or `always`", wrap)
+}
+
+pub fn unknown_args(args: &str) -> String {
+    format!("Unknown `args = {}`, expected `forward` (default) or `tuple`", args)
+}
+
+pub fn unknown_per_instance(per_instance: &str) -> String {
+    format!("Unknown `per_instance = {}`, expected `true` or `false`", per_instance)
+}
+
+pub fn per_instance_receiver(fn_name: &str) -> String {
+    format!(
+        "`per_instance` mocks require `&self`, `&mut self` or a pointer to `Self` receiver of `{}`",
+        fn_name
+    )
+}
+
+pub fn per_instance_generics(fn_name: &str) -> String {
+    format!(
+        "`per_instance` mocks are not supported for generic method `{}`",
+        fn_name
+    )
+}
+
+pub fn const_without_passthrough(fn_name: &str) -> String {
+    format!(
+        "`const fn {0}` could not dispatch to mocks in const contexts, pass `const_passthrough = {0}_runtime` to keep \
+         it intact and wire the mock to a non-const entry point",
+        fn_name
+    )
+}
+
+pub fn unknown_scoped(scoped: &str) -> String {
+    format!("Unknown `scoped = {}`, expected `true` or `false`", scoped)
+}
+
+pub fn scoped_unsupported(fn_name: &str) -> String {
+    format!(
+        "`scoped` mocks are not supported for generic or `async` function `{}`",
+        fn_name
+    )
+}
+
+pub fn unknown_hot_swap(hot_swap: &str) -> String {
+    format!("Unknown `hot_swap = {}`, expected `true` or `false`", hot_swap)
+}
+
+pub fn hot_swap_unsupported(fn_name: &str) -> String {
+    format!(
+        "`hot_swap` mocks are not supported for generic or `async` function `{}`",
+        fn_name
+    )
+}
+
+pub fn unsupported_impl_form() -> String {
+    "Unsupported impl form: `#[mocked]` is expected on methods or on impl blocks (without arguments), pass `scope = \
+     trait` to the methods of `impl Trait for &Type` and blanket impls"
+        .to_string()
+}
+
+pub fn unknown_impl_method(method: impl Display) -> String {
+    format!(
+        "Method `{}` listed with its mock is not found in the impl block",
+        method
+    )
+}
+
+pub fn automock_form() -> String {
+    "`#[automock]` is expected on trait definitions without generic parameters".to_string()
+}
+
+pub fn automock_item(item: impl Display) -> String {
+    format!(
+        "`#[automock]` could not implement `{}`, only methods and associated consts with defaults are supported",
+        item
+    )
+}
+
+pub fn automock_method(fn_name: &str) -> String {
+    format!(
+        "`#[automock]` could not mock `{}`: generic methods, `impl Trait` types and static methods without default \
+         body are not supported",
+        fn_name
+    )
+}
+
+pub fn unknown_item(item: &str, names: &[&str]) -> String {
+    format!(
+        "Unknown generated item `{}`, expected one of: {}",
+        item,
+        names.join(", ")
+    )
+}
+
+pub fn unsupported_impl_type(self_type: impl Display) -> String {
+    format!(
+        "`#[mocked]` could not move the originals of `impl Trait for {}` to an inherent impl, pass `scope = trait` to \
+         its methods instead",
+        self_type
+    )
+}
+
+pub fn trait_scope_option(fn_name: &str, option: &str) -> String {
+    format!(
+        "`{}` is not supported for `{}` in a trait impl (`scope = trait`), the method could not get sibling items",
+        option, fn_name
+    )
+}
+
+pub fn auto_unsupported(fn_name: &str) -> String {
+    format!(
+        "`#[mocked(auto)]` is not supported for generic functions and methods, got `{}`",
+        fn_name
+    )
+}
+
+pub fn unknown_unused_original(strategy: &str) -> String {
+    format!(
+        "Unknown `unused_original = {}`, expected `warn` (default), `allow` or `strip`",
+        strategy
+    )
+}
+
+pub fn unknown_coverage(coverage: &str) -> String {
+    format!("Unknown `coverage = {}`, expected `on` (default) or `off`", coverage)
+}
+
+pub fn unknown_inline(inline: &str) -> String {
+    format!(
+        "Unknown `inline = {}`, expected `both` (default), `wrapper`, `original` or `drop`",
+        inline
+    )
+}
+
+pub fn unknown_docs(docs: &str) -> String {
+    format!("Unknown `docs = {}`, expected `wrapper` (default) or `both`", docs)
+}
+
+pub fn box_return_without_impl(fn_name: &str) -> String {
+    format!("`box_return` requires `-> impl Trait` output of `{}`", fn_name)
+}
+
+pub fn box_return_inlined(fn_name: &str) -> String {
+    format!(
+        "`box_return` could not be combined with the body of `{}` inlined into the wrapper (`unused_original = strip` \
+         or `scope = trait`)",
+        fn_name
+    )
+}
+
+pub fn unknown_overrides(overrides: &str) -> String {
+    format!(
+        "Unknown `overrides = {}`, expected `replace` (default), `wrap` or `forbid`",
+        overrides
+    )
+}
+
+pub fn wrapping_without_overrides(fn_name: &str) -> String {
+    format!(
+        "`overrides = wrap` requires `per_instance`, `scoped` or `hot_swap` mocks of `{}`",
+        fn_name
+    )
+}
+
+pub fn wrapping_unsupported(fn_name: &str) -> String {
+    format!(
+        "`overrides = wrap` is not supported for `async`, `keep_in_release` or `const_passthrough` function `{}`",
+        fn_name
+    )
+}
+
+pub fn forbidden_override(fn_name: &str, option: &str) -> String {
+    format!(
+        "`{}` could not be combined with `overrides = forbid` of `{}`",
+        option, fn_name
+    )
+}
+
+pub fn stripped_original_mapped(fn_name: &str) -> String {
+    format!(
+        "`unused_original = strip` could not be combined with `map_original` calling the original of `{}`",
+        fn_name
+    )
+}
+
+pub fn mocked_trait_receiver(fn_name: &str) -> String {
+    format!(
+        "Only functions without `self` are expected by `mocked_trait!`, got `{}`",
+        fn_name
+    )
+}
+
+pub fn blocking_not_async(fn_name: &str) -> String {
+    format!("`also_blocking` is expected for `async fn`, got `{}`", fn_name)
+}
+
+pub fn extra_parameter() -> String {
+    "Extra parameters should be provided in `key = value` format!".to_string()
+}
+
+pub fn receiver_not_first(arg: impl Display) -> String {
+    format!("`self` is allowed only as the first argument, got `{}`", arg)
+}
+
+pub fn unsupported_receiver(arg: impl Display) -> String {
+    format!(
+        "Unsupported receiver `{}`, expected one of: `self`, `mut self`, `&self`, `&mut self`, `self: Type`",
+        arg
+    )
+}
+
+pub fn unsupported_parameter(param: impl Display) -> String {
+    format!("Unsupported parameter `{}`, expected `pattern: Type`", param)
+}
+
+pub fn not_function_reference() -> String {
+    "The first argument should be a reference to the function denoted as `#[mocked]`!".to_string()
+}
+
+pub fn not_function_item(item: impl Display) -> String {
+    format!("Only functions are expected, got `{}`", item)
+}
+
+/// Wrong arguments of a function-like macro, e.g.
+/// `usage("explain!(path::to::function)")`
+pub fn usage(example: &str) -> String {
+    format!("Usage: `{}`", example)
+}
+
+/// The macro generated invalid code, the snippet is included as is
+pub fn unparsable_expansion(fn_name: &str, err: impl Display, code: &str) -> String {
+    format!(
+        "Failed to parse the code generated for `{}`: {}. Please report it along with the code:\n{}",
+        fn_name,
+        err,
+        code.trim()
+    )
+}
+
+pub fn manifest_unreadable(path: &Path, err: impl Display) -> String {
+    format!("Failed to read covers manifest '{}': {}", path.display(), err)
+}
+
+pub fn manifest_invalid(path: &Path, err: impl Display) -> String {
+    format!("Failed to parse covers manifest '{}': {}", path.display(), err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        // the text is snapshotted by downstream crates, keep in sync with the changelog
+        assert_eq!(
+            usage("explain!(path::to::function)"),
+            "Usage: `explain!(path::to::function)`"
+        );
+        assert_eq!(
+            missing_mock("foo", "COVERS_MANIFEST"),
+            "At least fully-qualified reference to mock have to be provided for `foo`! Pass it to the macro or list \
+             in the file referenced by `COVERS_MANIFEST`"
+        );
+        assert_eq!(
+            unsupported_receiver("ref self"),
+            "Unsupported receiver `ref self`, expected one of: `self`, `mut self`, `&self`, `&mut self`, `self: Type`"
+        );
+        assert_eq!(
+            unparsable_expansion("foo", "lex error", " fn foo() { "),
+            "Failed to parse the code generated for `foo`: lex error. Please report it along with the code:\nfn foo() \
+             {"
+        );
+    }
+
+    #[test]
+    fn test_option_messages() {
+        // values of the options not matching any of the keywords
+        assert_eq!(
+            unknown_wrap("sometimes"),
+            "Unknown `wrap = sometimes`, expected `debug` (default) or `always`"
+        );
+        assert_eq!(
+            unknown_args("tupled"),
+            "Unknown `args = tupled`, expected `forward` (default) or `tuple`"
+        );
+        assert_eq!(
+            unknown_per_instance("yes"),
+            "Unknown `per_instance = yes`, expected `true` or `false`"
+        );
+        assert_eq!(
+            unknown_scoped("yes"),
+            "Unknown `scoped = yes`, expected `true` or `false`"
+        );
+        assert_eq!(
+            unknown_hot_swap("yes"),
+            "Unknown `hot_swap = yes`, expected `true` or `false`"
+        );
+        assert_eq!(
+            unknown_coverage("none"),
+            "Unknown `coverage = none`, expected `on` (default) or `off`"
+        );
+        assert_eq!(
+            unknown_docs("none"),
+            "Unknown `docs = none`, expected `wrapper` (default) or `both`"
+        );
+        assert_eq!(
+            unknown_inline("always"),
+            "Unknown `inline = always`, expected `both` (default), `wrapper`, `original` or `drop`"
+        );
+        assert_eq!(
+            unknown_overrides("merge"),
+            "Unknown `overrides = merge`, expected `replace` (default), `wrap` or `forbid`"
+        );
+        assert_eq!(
+            unknown_unused_original("drop"),
+            "Unknown `unused_original = drop`, expected `warn` (default), `allow` or `strip`"
+        );
+        assert_eq!(
+            unknown_original_vis("public"),
+            "Unknown `original_vis = public`, expected `pub` (default), `keep` or a restricted one, e.g. `pub(crate)`"
+        );
+        assert_eq!(invalid_priority("high"), "`priority = high` should be an integer");
+        assert_eq!(
+            invalid_prefix("1_"),
+            "`prefix = 1_` should be a string of identifier characters, e.g. `prefix = \"orig_\"`"
+        );
+        assert_eq!(
+            extra_parameter(),
+            "Extra parameters should be provided in `key = value` format!"
+        );
+        assert_eq!(
+            mock_with_map_original(),
+            "Either a reference to mock or `map_original` should be provided, not both"
+        );
+    }
+
+    #[test]
+    fn test_function_messages() {
+        // options not applicable to the function they are passed for
+        assert_eq!(
+            per_instance_receiver("get"),
+            "`per_instance` mocks require `&self`, `&mut self` or a pointer to `Self` receiver of `get`"
+        );
+        assert_eq!(
+            per_instance_generics("get"),
+            "`per_instance` mocks are not supported for generic method `get`"
+        );
+        assert_eq!(
+            const_without_passthrough("len"),
+            "`const fn len` could not dispatch to mocks in const contexts, pass `const_passthrough = len_runtime` to \
+             keep it intact and wire the mock to a non-const entry point"
+        );
+        assert_eq!(
+            scoped_unsupported("foo"),
+            "`scoped` mocks are not supported for generic or `async` function `foo`"
+        );
+        assert_eq!(
+            hot_swap_unsupported("foo"),
+            "`hot_swap` mocks are not supported for generic or `async` function `foo`"
+        );
+        assert_eq!(
+            box_return_without_impl("foo"),
+            "`box_return` requires `-> impl Trait` output of `foo`"
+        );
+        assert_eq!(
+            blocking_not_async("foo"),
+            "`also_blocking` is expected for `async fn`, got `foo`"
+        );
+        assert_eq!(
+            forbidden_override("foo", "scoped"),
+            "`scoped` could not be combined with `overrides = forbid` of `foo`"
+        );
+        assert_eq!(
+            trait_scope_option("foo", "scoped"),
+            "`scoped` is not supported for `foo` in a trait impl (`scope = trait`), the method could not get sibling \
+             items"
+        );
+        assert_eq!(
+            automock_method("read"),
+            "`#[automock]` could not mock `read`: generic methods, `impl Trait` types and static methods without \
+             default body are not supported"
+        );
+    }
+
+    #[test]
+    fn test_input_messages() {
+        // items and parameters the macros could not parse
+        assert_eq!(
+            unsupported_impl_form(),
+            "Unsupported impl form: `#[mocked]` is expected on methods or on impl blocks (without arguments), pass \
+             `scope = trait` to the methods of `impl Trait for &Type` and blanket impls"
+        );
+        assert_eq!(
+            unknown_impl_method("put"),
+            "Method `put` listed with its mock is not found in the impl block"
+        );
+        assert_eq!(
+            receiver_not_first("self"),
+            "`self` is allowed only as the first argument, got `self`"
+        );
+        assert_eq!(
+            unsupported_parameter(": u8"),
+            "Unsupported parameter `: u8`, expected `pattern: Type`"
+        );
+        assert_eq!(
+            not_function_reference(),
+            "The first argument should be a reference to the function denoted as `#[mocked]`!"
+        );
+        assert_eq!(not_function_item("struct"), "Only functions are expected, got `struct`");
+        assert_eq!(
+            unknown_item("Trackr", &["Switch", "Tracker"]),
+            "Unknown generated item `Trackr`, expected one of: Switch, Tracker"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Sources of the crate for the fuzz harness in `fuzz` directory.
+//!
+//! A `proc-macro` crate exports nothing but macros, so `__fuzzing!()` expands
+//! to the crate itself: the modules are inlined, `feature = ".."` predicates
+//! are resolved as the features of `covers_macros` are enabled, and the
+//! `proc_macro` entry points are dropped, leaving `self_test` module as the
+//! entry of the harness.
+
+use proc_macro2::{Delimiter, Group, Ident, TokenStream, TokenTree};
+
+use crate::{is_attribute, is_punct};
+
+/// Sources of the modules declared in `lib.rs`, the others are dropped
+const MODULES: &[(&str, &str)] = &[
+    ("diagnostics", include_str!("diagnostics.rs")),
+    ("manifest", include_str!("manifest.rs")),
+    ("naming", include_str!("naming.rs")),
+    ("self_test", include_str!("self_test.rs")),
+    ("symbols", include_str!("symbols.rs")),
+];
+
+/// Attributes of the items exported by a `proc-macro` crate
+const ENTRY_POINTS: &[&str] = &["proc_macro", "proc_macro_attribute", "proc_macro_derive"];
+
+/// The sources of `lib.rs` with the modules inlined, to be expanded at the
+/// root of the harness
+pub fn sources() -> TokenStream {
+    let tokens: Vec<TokenTree> = parse(include_str!("lib.rs")).into_iter().collect();
+    // inner attributes (`//!`) are not allowed in the expansion of a macro
+    let mut start = 0;
+    while let [hash, bang, TokenTree::Group(_), ..] = &tokens[start..] {
+        if !is_punct(hash, '#') || !is_punct(bang, '!') {
+            break;
+        }
+        start += 3;
+    }
+    inline(tokens[start..].iter().cloned().collect())
+}
+
+/// The sources are compiled as a part of the crate already
+fn parse(source: &str) -> TokenStream {
+    source.parse().unwrap()
+}
+
+/// Splits the module into items at `;` or `{ .. }` on the top level, the
+/// items other than module declarations and entry points are kept as is
+fn inline(module: TokenStream) -> TokenStream {
+    let mut inlined = Vec::new();
+    let mut item = Vec::new();
+    for token in resolve_features(module) {
+        let is_end = match &token {
+            TokenTree::Punct(punct) => punct.as_char() == ';',
+            TokenTree::Group(group) => group.delimiter() == Delimiter::Brace,
+            _ => false,
+        };
+        item.push(token);
+        if is_end {
+            inlined.extend(inline_item(std::mem::take(&mut item)));
+        }
+    }
+    inlined.extend(item);
+    inlined.into_iter().collect()
+}
+
+fn inline_item(mut item: Vec<TokenTree>) -> Vec<TokenTree> {
+    let is_entry_point = item.windows(2).any(|pair| match pair {
+        [hash, TokenTree::Group(group)] => {
+            is_punct(hash, '#') && ENTRY_POINTS.iter().any(|name| is_attribute(group, name))
+        },
+        _ => false,
+    });
+    if is_entry_point {
+        return Vec::new();
+    }
+    let source = match &item[..] {
+        [.., TokenTree::Ident(keyword), TokenTree::Ident(name), semicolon]
+            if keyword == "mod" && is_punct(semicolon, ';') =>
+        {
+            let name = name.to_string();
+            match MODULES.iter().find(|(module, _)| *module == name) {
+                Some((_, source)) => source,
+                None => return Vec::new(),
+            }
+        },
+        _ => return item,
+    };
+    let semicolon = item.pop().unwrap();
+    let mut body = Group::new(Delimiter::Brace, inline(parse(source)));
+    body.set_span(semicolon.span());
+    item.push(TokenTree::from(body));
+    item
+}
+
+/// Replaces `feature = ".."` of the conditional compilation with `all()` for
+/// the enabled features and `any()` for the others
+fn resolve_features(stream: TokenStream) -> TokenStream {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut resolved = Vec::new();
+    let mut rest = &tokens[..];
+    loop {
+        rest = match rest {
+            [TokenTree::Ident(ident), eq, TokenTree::Literal(feature), tail @ ..]
+                if ident == "feature" && is_punct(eq, '=') =>
+            {
+                let predicate = if is_enabled(&feature.to_string()) { "all" } else { "any" };
+                resolved.push(TokenTree::from(Ident::new(predicate, ident.span())));
+                resolved.push(TokenTree::from(Group::new(Delimiter::Parenthesis, TokenStream::new())));
+                tail
+            },
+            [TokenTree::Group(group), tail @ ..] => {
+                let mut inner = Group::new(group.delimiter(), resolve_features(group.stream()));
+                inner.set_span(group.span());
+                resolved.push(TokenTree::from(inner));
+                tail
+            },
+            [token, tail @ ..] => {
+                resolved.push(token.clone());
+                tail
+            },
+            [] => return resolved.into_iter().collect(),
+        };
+    }
+}
+
+/// Checks the feature of `covers_macros` by its literal, e.g. `"report"`
+fn is_enabled(feature: &str) -> bool {
+    match feature.trim_matches('"') {
+        "__" => cfg!(feature = "__"),
+        "_orig_" => cfg!(feature = "_orig_"),
+        "no-pub" => cfg!(feature = "no-pub"),
+        "self-test" => cfg!(feature = "self-test"),
+        "canary" => cfg!(feature = "canary"),
+        "symbols" => cfg!(feature = "symbols"),
+        "report" => cfg!(feature = "report"),
+        "fuzzing" => cfg!(feature = "fuzzing"),
+        _ => false,
+    }
+}
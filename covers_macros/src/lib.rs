@@ -0,0 +1,4471 @@
+//! **The crate stores the implementation of macros**
+//!
+//! Integration tests are outside in [`covers_it`](https://github.com/reanimatorzon/covers/tree/master/covers_it).
+//! The expansion itself is unit tested via string-based entry points of
+//! `self_test` module.
+//!
+//! The functions are parsed on the token level with `proc-macro2` alone, no
+//! `syn`/`quote`: the wrapper reuses the original tokens of the signature
+//! (attributes, generics, `where` clauses, qualified types are forwarded as
+//! is), so only the parameters, the name and the qualifiers are inspected.
+//! `syn` with `full` feature would be the heaviest dependency of users' test
+//! builds, while the self tests pin the token-exact expansion.
+//!
+//! @see [https://github.com/dtolnay/proc-macro-hack](https://github.com/dtolnay/proc-macro-hack)
+//!
+//! With `fuzzing` feature hidden `__fuzzing!()` expands to the sources of the
+//! crate, so the harness in `fuzz` directory calls `self_test` module directly.
+
+// not in the extern prelude of the fuzz harness compiling the sources
+extern crate proc_macro;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use proc_macro2::Delimiter::{Brace, Parenthesis};
+use proc_macro2::*;
+
+use Stage::*;
+use naming::Item;
+
+mod diagnostics;
+#[cfg(feature = "fuzzing")]
+mod fuzzing;
+mod manifest;
+mod naming;
+#[cfg(any(test, feature = "self-test", feature = "fuzzing"))]
+// called by the harness expanding `__fuzzing!()`
+#[cfg_attr(feature = "fuzzing", allow(dead_code))]
+pub(crate) mod self_test;
+mod symbols;
+
+/// Predicate of the target's build when the functions are wrapped and mocks
+/// compiled.
+const ACTIVE_GATE: &str = "any(debug_assertions, test)";
+
+/// Environment variable with a default macro post-processing generated
+/// wrappers.
+const PLUGIN_ENV: &str = "COVERS_PLUGIN";
+
+/// Environment variable with a default strategy for unused originals.
+const UNUSED_ORIGINAL_ENV: &str = "COVERS_UNUSED_ORIGINAL";
+
+/// Environment variable turning the wrapping off, functions are emitted as is.
+const DISABLE_ENV: &str = "COVERS_DISABLE";
+
+/// Predicate of the target's build when the wrapper dispatches to mocks.
+const MOCK_GATE: &str = "test";
+
+/// Cfg of the library under integration tests (`tests/` directory) emitted
+/// by `covers::build::testing()` of its build script.
+const TESTING_CFG: &str = "covers_testing";
+
+/// Environment variable set by `covers::build::testing()` for the crate being
+/// compiled, its mocks are dispatched under [`TESTING_CFG`] too.
+const TESTING_ENV: &str = "COVERS_TESTING";
+
+/// Feature of the crate being compiled turning mocks on outside of its unit
+/// tests: for its integration tests and for tests of dependent crates.
+const ACTIVE_FEATURE: &str = "covers-active";
+
+/// Environment variable set by `covers::build::active_feature()` for the crate
+/// being compiled, its mocks are dispatched under [`ACTIVE_FEATURE`] too.
+const ACTIVE_FEATURE_ENV: &str = "COVERS_ACTIVE_FEATURE";
+
+/// Excludes the generated items from coverage reports with `coverage = off`,
+/// the crate should enable `coverage_attribute` feature under the same cfg.
+const COVERAGE_OFF: &str = "#[cfg_attr(coverage_nightly, coverage(off))]";
+
+/// Options of `#[mocked]` which could be passed without a value.
+const FLAGS: &[&str] = &["keep_in_release", "box_return", "capture"];
+
+/// Options taking a keyword, their values are matched case-insensitively, e.g.
+/// `scope = IMPL`
+const KEYWORD_OPTIONS: &[&str] = &[
+    "args",
+    "box_return",
+    "coverage",
+    "docs",
+    "hot_swap",
+    "inline",
+    "keep_in_release",
+    "original_vis",
+    "overrides",
+    "per_instance",
+    "scope",
+    "scoped",
+    "unused_original",
+    "wrap",
+];
+
+#[derive(Clone, Copy)]
+enum Stage {
+    Start = 0,
+    FnIdentFound = 1,
+    FnNameFound = 2,
+    FnArgsFound = 3,
+    FnBodyFound = 4,
+}
+
+/// Compile error of a macro: the message and the span it is reported at
+type Diagnostic = (Span, String);
+
+/// Returns the compile error unless the condition holds
+macro_rules! ensure {
+    ($condition:expr, $span:expr, $message:expr) => {
+        if !$condition {
+            return Err(($span, $message));
+        }
+    };
+}
+
+#[derive(Default)]
+struct Params {
+    reference: String,
+    options: HashMap<String, String>,
+    /// Spans of the options' names to report errors about them
+    spans: HashMap<String, Span>,
+    reference_span: Option<Span>,
+}
+
+impl Params {
+    /// Span of the option, the reference's or the attribute's one when it is
+    /// not passed
+    fn span(&self, option: &str) -> Span {
+        self.spans
+            .get(option)
+            .or(self.reference_span.as_ref())
+            .copied()
+            .unwrap_or_else(Span::call_site)
+    }
+
+    /// Checks whether the option is passed with any value but `false`, e.g. as
+    /// a flag
+    fn is_enabled(&self, option: &str) -> bool {
+        self.options.get(option).is_some_and(|value| value != "false")
+    }
+}
+
+/// Branch of the dispatch to a mock
+struct Arm {
+    /// Predicate of `#[cfg]` when the arm is active
+    gate: String,
+    priority: i64,
+    call: String,
+    /// Human-readable mock for diagnostics
+    mock: String,
+    /// Coercion of the mock to the pointer type of the function, empty when
+    /// the type could not be named
+    check: String,
+}
+
+/// Options of `#[mocked]` merged over the stacked attributes, their values
+/// are validated before the function is parsed
+struct MockedOptions {
+    /// Arguments of each attribute, the outer one first, every attribute is an
+    /// arm of the dispatch
+    attributes: Vec<Params>,
+    /// Options of all the attributes, the outer ones take precedence
+    merged: Params,
+    prefix: String,
+    coverage: &'static str,
+    is_always_wrapped: bool,
+    inline: Inline,
+    has_original_docs: bool,
+    unused_original: UnusedOriginal,
+    original_vis: OriginalVis,
+    overrides: Overrides,
+    is_per_instance: bool,
+    is_scoped: bool,
+    is_hot_swap: bool,
+    is_kept_in_release: bool,
+    is_boxed: bool,
+    is_impl_scope: bool,
+    is_trait_scope: bool,
+    const_passthrough: Option<String>,
+    also_blocking: Option<String>,
+    block_on: String,
+    /// `true` or a projection of the arguments to record
+    capture: Option<String>,
+    plugin: Option<String>,
+}
+
+/// Code of an option of `#[mocked]` spliced into the expansion, empty when
+/// the option is not passed
+#[derive(Default)]
+struct Fragment {
+    /// Statements of the wrapper ahead of the check of the switch
+    tracking: String,
+    /// Dispatch of the wrapper taking precedence over the attribute mocks
+    dispatch: String,
+    /// Dispatch of the wrapper in the builds without mocks
+    release_dispatch: String,
+    /// Items next to the wrapper
+    items: String,
+}
+
+impl Fragment {
+    fn join(fragments: &[Fragment], part: impl Fn(&Fragment) -> &String) -> String {
+        fragments.iter().map(part).cloned().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Functions the `#[inline]` attribute is kept on
+#[derive(Clone, Copy)]
+enum Inline {
+    Both,
+    Wrapper,
+    Original,
+    Drop,
+}
+
+/// What happens to the original when mocks never delegate to it
+#[derive(Clone, Copy)]
+enum UnusedOriginal {
+    Warn,
+    Allow,
+    Strip,
+}
+
+enum OriginalVis {
+    Pub,
+    Keep,
+    /// E.g. `pub(crate)`
+    Restricted(TokenStream),
+}
+
+/// How runtime overrides treat the attribute mock
+#[derive(Clone, Copy)]
+enum Overrides {
+    Replace,
+    Wrap,
+    Forbid,
+}
+
+/// The function under `#[mocked]` split into the parts of the expansion
+struct MockedFn {
+    name: String,
+    /// Span of the name, errors about the whole function are reported at it
+    name_span: Span,
+    /// Name of the original, the function's one with the prefix
+    orig_name: String,
+    original: Vec<TokenTree>,
+    /// Signature of the wrapper, pattern arguments become plain bindings
+    signature: Vec<TokenTree>,
+    /// Arguments forwarded to the mocks and the original, e.g. `self, a, b`
+    args: String,
+    params: TokenStream,
+    /// Tokens following the parameters: the output and `where` clause
+    tail: Vec<TokenTree>,
+    /// The body, pattern arguments are destructured in front of it
+    body: TokenStream,
+    /// Explicit generic arguments, e.g. `::<T, N>`
+    turbofish: String,
+    has_generics: bool,
+    is_impl_scope: bool,
+    is_extern: bool,
+    is_async: bool,
+    is_const: bool,
+    is_unsafe: bool,
+    /// Qualifiers of the function's pointer type, e.g. `unsafe extern "C" `
+    qualifiers: String,
+}
+
+/// Wraps the function below for calling another mock function
+/// named according to the macro's argument when `#[cfg(debug_assertions)]`
+/// enabled. Call original or mock function according to `#[cfg(test)]` flag.
+///
+/// Function signature should be the same as original: arguments, output.
+/// The mock is coerced to the function's pointer type in the wrapper, so a
+/// mismatch is reported at the attribute with both signatures. It is skipped
+/// for `async`, `extern` and `impl Trait` signatures, and for outputs
+/// borrowing from the receiver.
+///
+/// In most cases you need to pass only the single required argument
+/// fully-qualified reference to a mock function.
+///
+/// There only one exception when you need to hint
+/// macro with `scope = impl` when you try to mock
+/// static struct method (in `impl` block) which does not mention `Self`
+/// in its signature or body; the ones mentioning it are detected.
+/// Alternatively mark the whole `impl` block with `#[mocked]`.
+///
+/// Other options are listed in the README.
+///
+/// Usage
+/// ======
+/// ```
+/// use covers::{mocked, mock};
+///
+/// #[mocked(mock_foo)]
+/// fn foo(name: &str) -> String {
+///     format!("Response: Foo = {}", name)
+/// }
+///
+/// fn mock_foo(another_name: &str) -> String {
+///     format!("Response: Mocked(Foo = {})", another_name)
+/// }
+///
+/// #[mocked(module::mock_bar)]
+/// fn bar(name: &str) -> String {
+///     format!("Response: Bar = {}", name)
+/// }
+///
+/// pub struct Struct {}
+///
+/// mod module {
+///     use super::*;
+///
+///     #[mock]
+///     pub fn mock_bar(name: &str) -> String {
+///         let original_function_result = _bar(name);
+///         format!("Response: Mocked({})", original_function_result)
+///     }
+///
+///     pub fn yyy(this: Struct, name: &str) -> String {
+///         format!("Response: Mocked({})", name)
+///     }
+/// }
+///
+/// impl Struct {
+///     #[mocked(Struct::mock_baz, scope = impl)]
+///     fn baz(name: &str) -> String {
+///         format!("Response: Baz = {}", name)
+///     }
+///
+///     fn mock_baz(name: &str) -> String {
+///         format!("Response: Baz = {}", name)
+///     }
+///
+///     #[mocked(module::yyy)]
+///     fn xxx(self, name: &str) -> String {
+///         format!("Response: Baz = {}", name)
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn mocked(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_attribute(args, input, expand_mocked)
+}
+
+fn expand_mocked(args: TokenStream, input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    if is_expansion_disabled() {
+        return Ok(with_env_tracking(input));
+    }
+
+    if is_impl_block(&input) {
+        return expand_mocked_impl(args, input);
+    }
+    expand_mocked_fn(args, input, None)
+}
+
+/// Expands `#[mocked]` of a function, methods of impl blocks marked with
+/// `#[mocked]` get the name of the type, e.g. to be looked up in the manifest
+fn expand_mocked_fn(args: TokenStream, input: TokenStream, self_type: Option<&str>) -> Result<TokenStream, Diagnostic> {
+    let args = parse_params(args)?;
+    // consts or statics preceding the function (e.g. emitted by other macros) are
+    // passed through as is
+    let (leading_items, input) = split_leading_items(input);
+    // stacked `#[mocked]` attributes are merged into a single wrapper
+    let (stacked, input) = split_stacked_attributes(input)?;
+
+    let mut attributes = vec![args];
+    attributes.extend(stacked);
+    let options = MockedOptions::parse(attributes)?;
+
+    let input_tokens = input.clone();
+    let mut function = MockedFn::parse(input, &options)?;
+
+    let fn_name = function.name.clone();
+    // const contexts (statics, array lengths, promotion) could not dispatch to
+    // mocks
+    ensure!(
+        !function.is_const || options.const_passthrough.is_some(),
+        options.merged.span("const_passthrough"),
+        diagnostics::const_without_passthrough(&fn_name)
+    );
+    let fn_switch_name = Item::Switch.name(&fn_name);
+    let fn_tracker_name = Item::Tracker.name(&fn_name);
+    let fn_explain_name = Item::Explain.name(&fn_name);
+    let fq = function.fq();
+    let unsafe_block = function.unsafe_block();
+    let coverage = options.coverage;
+
+    // names of `extern` functions having test doubles for cross-language harnesses
+    let symbol = if function.is_extern {
+        symbols::embed(&fn_name)
+    } else {
+        String::new()
+    };
+
+    let (arms, manifest_tracking) = mock_arms(&function, &options, self_type)?;
+    let mock_gate = mock_gate(&arms);
+    let signature_checks = signature_checks(&arms);
+
+    let boxing = box_return(&mut function, &options)?;
+    let (override_type, override_arguments, next) = override_wiring(&function, &options, &arms, &mock_gate, boxing)?;
+    let (gate, release) = release_copy(&function, &options, &input_tokens, &mock_gate, &override_arguments);
+    let (wrapper_gate, fallthrough, release) =
+        const_passthrough(&mut function, &options, &gate, release, &input_tokens);
+    let mut fallthrough = fallthrough_statement(&function, boxing, &fallthrough);
+
+    strip_inline(&mut function, options.inline);
+    let fn_original = original_fn(&mut function, &options, &gate)?;
+    if fn_original.is_none() && options.const_passthrough.is_none() {
+        // the body is inlined into the wrapper instead
+        fallthrough = function.body.to_string();
+    }
+
+    let fragments = [
+        instance_override(&function, &options, &mock_gate, &override_type, &override_arguments)?,
+        scoped_override(&function, &options, &mock_gate, &override_type, &override_arguments)?,
+        auto_module(&function, &options, &mock_gate),
+        hot_swap_override(&function, &options, &mock_gate, &override_type, &override_arguments)?,
+    ];
+    let blocking = blocking_fn(&function, &options)?;
+    check_trait_scope(&function, &options)?;
+    let capture = capture_statement(&function, &options, &mock_gate);
+    let is_disabled = is_disabled_call(&function, &options);
+
+    let dispatch = dispatch(&arms, true, |arm| {
+        format!("return {};", boxed(boxing, arm.call.clone()))
+    });
+    let wrapper_attributes = format!("{} {}", wrapper_gate, coverage);
+    let signature = with_wiring_doc(
+        with_attributes(&wrapper_attributes, function.signature.iter().cloned().collect()),
+        &arms,
+    );
+    let wrapper = if options.is_trait_scope {
+        format!(
+            r#"
+            {signature} {{
+                {env_tracking}
+                {manifest_tracking}
+                {symbol}
+                {signature_checks}
+                #[cfg({mock_gate})]
+                {unsafe_block} {{
+                    {canary}
+                    if !{is_disabled} {{
+                        {dispatch}
+                    }}
+                }}
+                {body}
+            }}
+            "#,
+            signature = signature,
+            env_tracking = env_tracking(),
+            manifest_tracking = manifest_tracking,
+            symbol = symbol,
+            signature_checks = signature_checks,
+            mock_gate = mock_gate,
+            canary = canary(),
+            unsafe_block = unsafe_block,
+            is_disabled = is_disabled,
+            dispatch = dispatch,
+            body = function.body
+        )
+    } else {
+        format!(
+            r#"
+            {signature} {{
+                {env_tracking}
+                {manifest_tracking}
+                {symbol}
+                {signature_checks}
+                #[cfg({mock_gate})]
+                let _execution = {fq}{fn_tracker_name}().enter({fq}{fn_explain_name});
+                {capture}
+                #[cfg({mock_gate})]
+                {unsafe_block} {{
+                    {canary}
+                    {tracking}
+                    if !{fq}{fn_switch_name}().with(|original| original.get()) && !{is_disabled} {{
+                        {fq}{fn_tracker_name}().hit();
+                        {overrides}
+                        {dispatch}
+                    }}
+                }}
+                {release_dispatch}
+                {fallthrough}
+            }}
+            "#,
+            signature = signature,
+            env_tracking = env_tracking(),
+            manifest_tracking = manifest_tracking,
+            symbol = symbol,
+            signature_checks = signature_checks,
+            mock_gate = mock_gate,
+            fn_tracker_name = fn_tracker_name,
+            fn_explain_name = fn_explain_name,
+            fn_switch_name = fn_switch_name,
+            is_disabled = is_disabled,
+            capture = capture,
+            canary = canary(),
+            unsafe_block = unsafe_block,
+            tracking = Fragment::join(&fragments, |fragment| &fragment.tracking),
+            overrides = Fragment::join(&fragments, |fragment| &fragment.dispatch),
+            release_dispatch = Fragment::join(&fragments, |fragment| &fragment.release_dispatch),
+            dispatch = dispatch,
+            fallthrough = fallthrough,
+            fq = fq
+        )
+    };
+
+    // post-processing of the wrapper by a custom macro
+    let wrapper = match &options.plugin {
+        Some(plugin) => format!("{}! {{ {} }}", plugin, wrapper),
+        None => wrapper,
+    };
+
+    if options.is_trait_scope {
+        let code = format!("{} {} {}", leading_items, wrapper, release);
+        return parse_generated(&code, &fn_name, function.name_span);
+    }
+
+    let code = format!(
+        r#"
+        {leading_items}
+
+        {fn_original}
+
+        {wrapper}
+
+        #[cfg({mock_gate})]
+        #[doc(hidden)]
+        {coverage}
+        pub fn {fn_switch_name}() -> &'static ::std::thread::LocalKey<::std::cell::Cell<bool>> {{
+            ::std::thread_local!(static ORIGINAL: ::std::cell::Cell<bool> = ::std::cell::Cell::new(false));
+            &ORIGINAL
+        }}
+
+        #[cfg({mock_gate})]
+        #[doc(hidden)]
+        {coverage}
+        pub fn {fn_tracker_name}() -> &'static ::covers::runtime::Tracker {{
+            static TRACKER: ::covers::runtime::Tracker = ::covers::runtime::Tracker::new();
+            {report}
+            &TRACKER
+        }}
+
+        {next}
+
+        {items}
+
+        {explain}
+
+        {blocking}
+
+        {release}
+        "#,
+        leading_items = leading_items,
+        mock_gate = mock_gate,
+        coverage = coverage,
+        blocking = blocking,
+        release = release,
+        fn_original = fn_original.unwrap_or_default(),
+        wrapper = wrapper,
+        fn_switch_name = fn_switch_name,
+        fn_tracker_name = fn_tracker_name,
+        next = next,
+        items = Fragment::join(&fragments, |fragment| &fragment.items),
+        report = report(&fn_name, &arms[0].mock),
+        explain = explain_fn(&function, &options, &arms, &mock_gate, &gate, &is_disabled),
+    );
+
+    parse_generated(&code, &fn_name, function.name_span)
+}
+
+/// Opaque `impl Trait` outputs of the original and mocks differ, so with
+/// `box_return` the wrapper returns them boxed as trait objects. Returns the
+/// boxing function, empty without the option
+fn box_return(function: &mut MockedFn, options: &MockedOptions) -> Result<&'static str, Diagnostic> {
+    if !options.is_boxed {
+        return Ok("");
+    }
+    ensure!(
+        !options.is_trait_scope,
+        options.merged.span("box_return"),
+        diagnostics::box_return_inlined(&function.name)
+    );
+    let (boxed_tail, boxing) = boxed_output(&function.tail).ok_or_else(|| {
+        (
+            options.merged.span("box_return"),
+            diagnostics::box_return_without_impl(&function.name),
+        )
+    })?;
+    function
+        .signature
+        .truncate(function.signature.len() - function.tail.len());
+    function.signature.extend(boxed_tail.iter().cloned());
+    function.tail = boxed_tail;
+    Ok(boxing)
+}
+
+/// The wrapping is decided by the target's profile, not the one the macro is
+/// compiled with. Returns the gate of the wrapper and the original, and the
+/// copy of the function for the builds without them
+fn release_copy(
+    function: &MockedFn,
+    options: &MockedOptions,
+    input: &TokenStream,
+    mock_gate: &str,
+    override_arguments: &str,
+) -> (String, String) {
+    if options.is_always_wrapped {
+        return (String::new(), String::new());
+    }
+    if is_test_only(input) {
+        // test helpers have no other builds to gate or keep a release copy for
+        return ("#[cfg(test)]".to_string(), String::new());
+    }
+    // swapped mocks of functions kept in release are dispatched in all builds
+    let release = if options.is_kept_in_release {
+        let swap_dispatch = swap_dispatch(function, override_arguments);
+        with_body_prefix(
+            input.clone(),
+            &format!("{} {{ {} }}", function.unsafe_block(), swap_dispatch),
+        )
+    } else {
+        input.clone()
+    };
+    let active_gate = active_gate(mock_gate);
+    (
+        format!("#[cfg({})]", active_gate),
+        with_attributes(&format!("#[cfg(not({}))]", active_gate), release).to_string(),
+    )
+}
+
+/// With `const_passthrough` the `const fn` stays intact for const contexts
+/// (array lengths, const asserts), the mock is wired to a non-const runtime
+/// entry point instead. Returns the gate of the wrapper, the function it falls
+/// through to and the copy of the function kept next to it
+fn const_passthrough(
+    function: &mut MockedFn,
+    options: &MockedOptions,
+    gate: &str,
+    release: String,
+    input: &TokenStream,
+) -> (String, String, String) {
+    match &options.const_passthrough {
+        Some(runtime_name) => {
+            function.signature = rename_signature(function.signature.clone(), &function.name, runtime_name, "const");
+            (String::new(), function.name.clone(), input.to_string())
+        },
+        None => (gate.to_string(), function.orig_name.clone(), release),
+    }
+}
+
+/// The statement of the wrapper returning the result of the function it falls
+/// through to when no mock is dispatched
+fn fallthrough_statement(function: &MockedFn, boxing: &str, fallthrough: &str) -> String {
+    let fallthrough = boxed(
+        boxing,
+        format!(
+            "{}{}{}{}{}",
+            function.fq(),
+            fallthrough,
+            function.turbofish,
+            function.arguments(),
+            function.awaited()
+        ),
+    );
+    if function.is_unsafe {
+        format!("return unsafe {{ {} }};", fallthrough)
+    } else {
+        format!("return {};", fallthrough)
+    }
+}
+
+/// `#[inline]` on both the wrapper and the original changes codegen of debug
+/// builds, release builds keep it as is
+fn strip_inline(function: &mut MockedFn, inline: Inline) {
+    match inline {
+        Inline::Both => (),
+        Inline::Wrapper => function.original = strip_attributes(function.original.clone(), "inline"),
+        Inline::Original => function.signature = strip_attributes(function.signature.clone(), "inline"),
+        Inline::Drop => {
+            function.original = strip_attributes(function.original.clone(), "inline");
+            function.signature = strip_attributes(function.signature.clone(), "inline");
+        },
+    }
+}
+
+/// The renamed original with its docs hidden and the visibility of
+/// `original_vis`, `None` when `unused_original = strip` inlines it into the
+/// wrapper
+fn original_fn(
+    function: &mut MockedFn,
+    options: &MockedOptions,
+    gate: &str,
+) -> Result<Option<TokenStream>, Diagnostic> {
+    // docs are rendered once, on the wrapper, the public original is hidden
+    if !options.has_original_docs {
+        let stripped = strip_attributes(function.original.clone(), "doc");
+        if stripped.len() != function.original.len() {
+            let hidden: TokenStream = "#[doc(hidden)]".parse().unwrap();
+            function.original = hidden.into_iter().chain(stripped).collect();
+        }
+    }
+
+    // the original is unused when mocks never delegate to it
+    let attributes = match options.unused_original {
+        UnusedOriginal::Warn => gate.to_string(),
+        UnusedOriginal::Allow => format!("{} #[allow(dead_code)]", gate),
+        UnusedOriginal::Strip => {
+            ensure!(
+                !options.is_boxed,
+                options.merged.span("unused_original"),
+                diagnostics::box_return_inlined(&function.name)
+            );
+            ensure!(
+                options
+                    .attributes
+                    .iter()
+                    .all(|params| !params.options.contains_key("map_original")),
+                options.merged.span("unused_original"),
+                diagnostics::stripped_original_mapped(&function.name)
+            );
+            return Ok(None);
+        },
+    };
+
+    // the original is public to let mocks in other modules call it
+    let fn_original: TokenStream = function.original.iter().cloned().collect();
+    let fn_original = match &options.original_vis {
+        OriginalVis::Pub => make_public(fn_original),
+        OriginalVis::Keep => fn_original,
+        OriginalVis::Restricted(visibility) => with_visibility(fn_original, visibility.clone()),
+    };
+    Ok(Some(with_attributes(&attributes, fn_original)))
+}
+
+/// Methods of trait impls (`impl Ext for &Type`, blanket `impl<T> Ext for T`)
+/// could not get sibling items, so the original body is inlined and the
+/// per-function switches and trackers are omitted, as well as the options
+/// relying on them
+fn check_trait_scope(function: &MockedFn, options: &MockedOptions) -> Result<(), Diagnostic> {
+    if !options.is_trait_scope {
+        return Ok(());
+    }
+    for option in &[
+        "per_instance",
+        "scoped",
+        "hot_swap",
+        "keep_in_release",
+        "also_blocking",
+        "const_passthrough",
+        "map_original",
+        "capture",
+    ] {
+        ensure!(
+            !options.merged.is_enabled(option),
+            options.merged.span(option),
+            diagnostics::trait_scope_option(&function.name, option)
+        );
+    }
+    ensure!(
+        !options.is_spy(),
+        options.merged.span("spy"),
+        diagnostics::trait_scope_option(&function.name, "spy")
+    );
+    Ok(())
+}
+
+/// Checks mocks turned off with `covers::disable` at runtime, methods are
+/// named after their types
+fn is_disabled_call(function: &MockedFn, options: &MockedOptions) -> String {
+    let self_type = if function.is_impl_scope || options.is_trait_scope {
+        "::std::option::Option::Some(::std::any::type_name::<Self>())"
+    } else {
+        "::std::option::Option::None"
+    };
+    format!(
+        "::covers::runtime::is_disabled(module_path!(), {}, {:?})",
+        self_type, function.name
+    )
+}
+
+impl MockedOptions {
+    /// Merges the arguments of the stacked attributes, the outer one first,
+    /// and validates the values of the options
+    fn parse(attributes: Vec<Params>) -> Result<Self, Diagnostic> {
+        // options of the outer attributes take precedence
+        let mut merged = Params::default();
+        for params in attributes.iter().rev() {
+            merged.options.extend(params.options.clone());
+            merged.spans.extend(params.spans.clone());
+        }
+        merged.reference_span = attributes[0].reference_span;
+        let options = &merged.options;
+        let value = |option: &str| options.get(option).map(String::as_str);
+        let switch = |option: &str, unknown: fn(&str) -> String| match value(option) {
+            None | Some("false") => Ok(false),
+            Some("true") => Ok(true),
+            Some(other) => Err((merged.span(option), unknown(other))),
+        };
+
+        // the original could get another prefix, e.g. when `_foo` is already taken
+        let prefix = match options.get("prefix") {
+            Some(prefix) => {
+                let unquoted = prefix.trim_matches('"');
+                ensure!(
+                    prefix.starts_with('"') && is_prefix(unquoted),
+                    merged.span("prefix"),
+                    diagnostics::invalid_prefix(prefix)
+                );
+                unquoted.to_string()
+            },
+            None => Item::Original.prefix().to_string(),
+        };
+
+        // the generated glue could be excluded from coverage reports, e.g. of
+        // `cargo llvm-cov` on nightly
+        let coverage = match value("coverage") {
+            None | Some("on") => "",
+            Some("off") => COVERAGE_OFF,
+            Some(other) => return Err((merged.span("coverage"), diagnostics::unknown_coverage(other))),
+        };
+
+        let is_always_wrapped = match value("wrap") {
+            None | Some("debug") => false,
+            Some("always") => true,
+            Some(other) => return Err((merged.span("wrap"), diagnostics::unknown_wrap(other))),
+        };
+
+        let inline = match value("inline") {
+            None | Some("both") => Inline::Both,
+            Some("wrapper") => Inline::Wrapper,
+            Some("original") => Inline::Original,
+            Some("drop") => Inline::Drop,
+            Some(other) => return Err((merged.span("inline"), diagnostics::unknown_inline(other))),
+        };
+
+        let has_original_docs = match value("docs") {
+            None | Some("wrapper") => false,
+            Some("both") => true,
+            Some(other) => return Err((merged.span("docs"), diagnostics::unknown_docs(other))),
+        };
+
+        let unused_original = options
+            .get("unused_original")
+            .cloned()
+            .or_else(|| std::env::var(UNUSED_ORIGINAL_ENV).ok())
+            .filter(|strategy| !strategy.trim().is_empty());
+        let unused_original = match unused_original.as_deref() {
+            None | Some("warn") => UnusedOriginal::Warn,
+            Some("allow") => UnusedOriginal::Allow,
+            Some("strip") => UnusedOriginal::Strip,
+            Some(other) => {
+                return Err((
+                    merged.span("unused_original"),
+                    diagnostics::unknown_unused_original(other),
+                ));
+            },
+        };
+
+        let original_vis = match value("original_vis") {
+            None | Some("pub") => OriginalVis::Pub,
+            Some("keep") => OriginalVis::Keep,
+            Some(visibility) if visibility.replace(' ', "").starts_with("pub(") => {
+                let visibility = visibility.parse().map_err(|_| {
+                    (
+                        merged.span("original_vis"),
+                        diagnostics::unknown_original_vis(visibility),
+                    )
+                })?;
+                OriginalVis::Restricted(visibility)
+            },
+            Some(other) => return Err((merged.span("original_vis"), diagnostics::unknown_original_vis(other))),
+        };
+
+        let overrides = match value("overrides") {
+            None | Some("replace") => Overrides::Replace,
+            Some("wrap") => Overrides::Wrap,
+            Some("forbid") => Overrides::Forbid,
+            Some(other) => return Err((merged.span("overrides"), diagnostics::unknown_overrides(other))),
+        };
+
+        let is_per_instance = switch("per_instance", diagnostics::unknown_per_instance)?;
+        let is_scoped = switch("scoped", diagnostics::unknown_scoped)?;
+        let is_kept_in_release = merged.is_enabled("keep_in_release");
+        // functions kept in release are swapped in all builds
+        let is_hot_swap = is_kept_in_release || switch("hot_swap", diagnostics::unknown_hot_swap)?;
+
+        // post-processing of the wrapper by a custom macro
+        let plugin = options
+            .get("plugin")
+            .cloned()
+            .or_else(|| std::env::var(PLUGIN_ENV).ok())
+            .filter(|plugin| !plugin.trim().is_empty());
+
+        Ok(MockedOptions {
+            prefix,
+            coverage,
+            is_always_wrapped,
+            inline,
+            has_original_docs,
+            unused_original,
+            original_vis,
+            overrides,
+            is_per_instance,
+            is_scoped,
+            is_hot_swap,
+            is_kept_in_release,
+            is_boxed: merged.is_enabled("box_return"),
+            is_impl_scope: value("scope") == Some("impl"),
+            is_trait_scope: value("scope") == Some("trait"),
+            const_passthrough: options.get("const_passthrough").cloned(),
+            also_blocking: options.get("also_blocking").cloned(),
+            block_on: options
+                .get("block_on")
+                .cloned()
+                .unwrap_or_else(|| "::covers::runtime::block_on".to_string()),
+            capture: options.get("capture").filter(|capture| *capture != "false").cloned(),
+            plugin,
+            attributes,
+            merged,
+        })
+    }
+
+    /// Checks whether any of the attributes records the calls with `spy`
+    fn is_spy(&self) -> bool {
+        self.attributes.iter().any(|params| params.reference == "spy")
+    }
+}
+
+impl MockedFn {
+    /// Splits the function into the original renamed with the prefix and the
+    /// wrapper's signature, collecting the parts of the latter
+    fn parse(input: TokenStream, options: &MockedOptions) -> Result<Self, Diagnostic> {
+        let mut stage = Start;
+
+        let mut original = vec![];
+        let mut signature = vec![];
+
+        let mut name = String::new();
+        let mut name_span = Span::call_site();
+        let mut orig_name = String::new();
+        let mut args = String::new();
+        let mut params = TokenStream::new();
+        let mut tail = vec![];
+        let mut body = TokenStream::new();
+        // pattern arguments are fresh bindings in the wrapper, destructured for inlined
+        // bodies
+        let mut destructuring = String::new();
+        let mut has_generics = false;
+        // generic parameters are forwarded explicitly, they could be not inferable
+        let mut generics = vec![];
+
+        // FIXME: dirty hack for 'Self::' prefix to functions inside 'impl' block.
+        let mut is_impl_scope = false;
+        let mut is_extern = false;
+
+        // generic parameters may contain groups too: `<F: for<'a> Fn(&'a str) ->
+        // String>`, as well as the output: `-> Array<{ N }>`
+        let mut generics_depth = 0;
+        let mut is_arrow = false;
+        // the output could be produced by another macro: `-> typestate! { Open }`
+        let mut is_bang = false;
+
+        for token in input {
+            if cmp(&stage, FnNameFound) == 0 || cmp(&stage, FnArgsFound) == 0 {
+                if let TokenTree::Punct(punct) = &token {
+                    match punct.as_char() {
+                        '<' => {
+                            generics_depth += 1;
+                            has_generics = has_generics || cmp(&stage, FnNameFound) == 0;
+                        },
+                        '>' if !is_arrow => generics_depth -= 1,
+                        _ => (),
+                    }
+                    is_arrow = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+                }
+                if cmp(&stage, FnNameFound) == 0
+                    && (generics_depth > 1 || generics_depth == 1 && !is_punct(&token, '<'))
+                {
+                    generics.push(token.clone());
+                }
+            }
+            let is_macro_group = std::mem::replace(&mut is_bang, is_punct(&token, '!'));
+
+            match &token {
+                TokenTree::Ident(ident) if cmp(&stage, FnIdentFound) < 0 && ident == "extern" => {
+                    is_extern = true;
+                    signature.push(token.clone());
+                    original.push(token);
+                },
+                TokenTree::Ident(ident) if cmp(&stage, FnIdentFound) < 0 && ident == "fn" => {
+                    stage = FnIdentFound;
+                    signature.push(token.clone());
+                    original.push(token);
+                },
+                TokenTree::Ident(ident) if cmp(&stage, FnIdentFound) == 0 => {
+                    stage = FnNameFound;
+
+                    name = ident.to_string();
+                    name_span = ident.span();
+                    signature.push(token.clone());
+
+                    let new_token = TokenTree::from(Ident::new(&format!("{}{}", options.prefix, ident), ident.span()));
+                    orig_name = new_token.to_string();
+                    original.push(new_token);
+                },
+                TokenTree::Group(group)
+                    if cmp(&stage, FnNameFound) == 0 && generics_depth == 0 && group.delimiter() == Parenthesis =>
+                {
+                    stage = FnArgsFound;
+                    let (forwarded, wrapper_params, pattern_destructuring) = parse_args(group)?;
+                    // `impl Trait` arguments make the function generic, e.g. for function pointers
+                    has_generics = has_generics || has_impl_trait(group.stream());
+                    args = forwarded;
+                    destructuring = pattern_destructuring;
+                    params = group.stream();
+                    is_impl_scope = args.starts_with("self,") || args == "self";
+                    signature.push(TokenTree::from(wrapper_params));
+                    original.push(token);
+                },
+                TokenTree::Group(group)
+                    if cmp(&stage, FnArgsFound) == 0
+                        && generics_depth == 0
+                        && !is_macro_group
+                        && group.delimiter() == Brace =>
+                {
+                    stage = FnBodyFound;
+                    body = with_body_prefix(TokenStream::from(token.clone()), &destructuring);
+                    original.push(token);
+                },
+                _ => {
+                    if cmp(&stage, FnArgsFound) == 0 {
+                        tail.push(token.clone());
+                    }
+                    if cmp(&stage, FnBodyFound) < 0 {
+                        signature.push(token.clone());
+                    }
+                    original.push(token);
+                },
+            };
+        }
+
+        // FIXME: dirty hack for 'Self::' prefix to functions inside 'impl' block.
+        // Free functions could not mention `Self`, so static methods doing it need
+        // no hint, e.g. constructors
+        is_impl_scope = is_impl_scope
+            || options.is_impl_scope
+            || mentions_self(params.clone())
+            || mentions_self(tail.iter().cloned().collect())
+            || mentions_self(generics.iter().cloned().collect())
+            || body_mentions_self(body.clone());
+
+        let qualifiers = signature
+            .iter()
+            .take_while(|token| !matches!(token, TokenTree::Ident(ident) if ident == "fn"));
+        // calls of `async fn` mocks and the original are awaited by the wrapper
+        let is_async = qualifiers
+            .clone()
+            .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "async"));
+        let is_const = qualifiers
+            .clone()
+            .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "const"));
+        // calls of `unsafe fn` mocks and the original are wrapped in `unsafe {}`
+        let is_unsafe = qualifiers
+            .clone()
+            .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "unsafe"));
+        // function pointers of runtime mocks keep the ABI: `unsafe extern "C" fn(..)`
+        let mut pointer_qualifiers = String::new();
+        let mut is_abi = false;
+        for token in qualifiers {
+            match token {
+                TokenTree::Ident(ident) if ident == "unsafe" || ident == "extern" => {
+                    is_abi = ident == "extern";
+                    pointer_qualifiers.push_str(&format!("{} ", ident));
+                },
+                TokenTree::Literal(abi) if is_abi => pointer_qualifiers.push_str(&format!("{} ", abi)),
+                _ => is_abi = false,
+            }
+        }
+
+        Ok(MockedFn {
+            name,
+            name_span,
+            orig_name,
+            original,
+            signature,
+            args,
+            params,
+            tail,
+            body,
+            turbofish: turbofish(generics),
+            has_generics,
+            is_impl_scope,
+            is_extern,
+            is_async,
+            is_const,
+            is_unsafe,
+            qualifiers: pointer_qualifiers,
+        })
+    }
+
+    /// Qualifies calls of the generated items inside impl blocks
+    fn fq(&self) -> &'static str {
+        if self.is_impl_scope { "Self::" } else { "" }
+    }
+
+    /// Arguments of the calls of mocks and the original, e.g. `(self, a, b)`
+    fn arguments(&self) -> String {
+        format!("({})", self.args)
+    }
+
+    fn awaited(&self) -> &'static str {
+        if self.is_async { ".await" } else { "" }
+    }
+
+    fn unsafe_block(&self) -> &'static str {
+        if self.is_unsafe { "unsafe" } else { "" }
+    }
+
+    /// Type of the function's pointer keeping its ABI, e.g.
+    /// `unsafe extern "C" fn(u8) -> u8`
+    fn pointer_type(&self) -> String {
+        format!(
+            "{}{}",
+            self.qualifiers,
+            fn_pointer_type(self.params.clone(), &self.tail)
+        )
+    }
+}
+
+/// Builds the arms of the dispatch, one per `#[mocked]` attribute, along with
+/// the statement letting compiler track the manifest the mocks are listed in
+fn mock_arms(
+    function: &MockedFn,
+    options: &MockedOptions,
+    self_type: Option<&str>,
+) -> Result<(Vec<Arm>, String), Diagnostic> {
+    let fn_name = &function.name;
+    let fq = function.fq();
+    let arguments = function.arguments();
+    let awaited = function.awaited();
+    let turbofish = &function.turbofish;
+
+    // mismatched mocks are reported comparing the whole signatures. Opaque
+    // types of `impl Trait` and futures could not be named, mocks of `extern`
+    // functions could have any ABI, and outputs borrowing from the receiver
+    // lose their lifetimes without `self`
+    let fn_tail_tokens: TokenStream = function.tail.iter().cloned().collect();
+    let has_receiver = split_top_level(function.params.clone())
+        .first()
+        .is_some_and(|receiver| is_receiver(receiver));
+    let check_type = format!(
+        "{}{}",
+        if function.is_unsafe { "unsafe " } else { "" },
+        fn_pointer_type(function.params.clone(), &function.tail)
+    );
+    let is_checked = !function.is_async
+        && !function.qualifiers.contains("extern")
+        && !has_impl_trait(function.params.clone())
+        && !has_impl_trait(fn_tail_tokens.clone())
+        && (!has_receiver || !has_elided_lifetime(fn_tail_tokens));
+
+    let default_gate = default_mock_gate();
+    let mut arms = vec![];
+    let mut manifest_tracking = String::new();
+    for params in &options.attributes {
+        let priority = match params.options.get("priority") {
+            Some(priority) => priority
+                .replace(' ', "")
+                .parse()
+                .map_err(|_| (params.span("priority"), diagnostics::invalid_priority(priority)))?,
+            None => 0,
+        };
+
+        // the mock could receive all the arguments packed into a single tuple
+        let mock_arguments = match params.options.get("args").map(String::as_str) {
+            None | Some("forward") => arguments.clone(),
+            Some("tuple") if function.args.is_empty() => "(())".to_string(),
+            Some("tuple") => format!("(({},))", function.args),
+            Some(other) => return Err((params.span("args"), diagnostics::unknown_args(other))),
+        };
+
+        let gate = params
+            .options
+            .get("cfg")
+            .cloned()
+            .unwrap_or_else(|| default_gate.clone());
+        let mut check = String::new();
+        // calls the original and post-processes its result instead of a mock
+        let (call, mock) = if let Some(map) = params.options.get("map_original") {
+            ensure!(
+                params.reference.is_empty(),
+                params.span("map_original"),
+                diagnostics::mock_with_map_original()
+            );
+            let call = format!(
+                "({map})({fq}{fn_orig_name}{turbofish}{arguments}{awaited})",
+                map = map,
+                fq = fq,
+                fn_orig_name = function.orig_name,
+                turbofish = turbofish,
+                arguments = arguments,
+                awaited = awaited
+            );
+            (call, format!("map_original = {}", map))
+        } else if params.reference == "spy" {
+            let call = format!(
+                "{fq}{fn_tracker_name}().spy({fq}{fn_orig_name}{turbofish}{arguments}{awaited})",
+                fq = fq,
+                fn_tracker_name = Item::Tracker.name(fn_name),
+                fn_orig_name = function.orig_name,
+                turbofish = turbofish,
+                arguments = arguments,
+                awaited = awaited
+            );
+            (call, "spy".to_string())
+        } else if params.reference == "auto" {
+            ensure!(
+                !function.has_generics && !function.is_impl_scope,
+                params.span("auto"),
+                diagnostics::auto_unsupported(fn_name)
+            );
+            (
+                format!("{}::{}()", naming::auto_module(fn_name), naming::AUTO_RETURN),
+                format!("{}::return_value", naming::auto_module(fn_name)),
+            )
+        } else {
+            let reference = if params.reference.is_empty() {
+                let key = manifest::key(&call_site_module(), self_type, fn_name);
+                let entry = manifest::lookup(&key)
+                    .map_err(|message| (Span::call_site(), message))?
+                    .ok_or_else(|| {
+                        (
+                            Span::call_site(),
+                            diagnostics::missing_mock(&key, manifest::MANIFEST_ENV),
+                        )
+                    })?;
+                // the manifest is included as bytes to let compiler track its changes
+                manifest_tracking = format!("const _: &[u8] = include_bytes!({:?});", entry.path);
+                entry.reference
+            } else {
+                params.reference.clone()
+            };
+            // the mock is coerced to the function's pointer type, so compiler
+            // shows both signatures when they differ
+            if is_checked && params.options.get("args").is_none_or(|args| args == "forward") {
+                check = format!("let _: {} = {}{};", check_type, reference, turbofish);
+            }
+            (
+                format!("{}{}{}{}", reference, turbofish, mock_arguments, awaited),
+                reference.replace(" :: ", "::"),
+            )
+        };
+
+        arms.push(Arm {
+            gate,
+            priority,
+            call,
+            mock,
+            check,
+        });
+    }
+    arms.sort_by_key(|arm| -arm.priority);
+    Ok((arms, manifest_tracking))
+}
+
+/// Wraps the value returned by the wrapper into the box of `box_return`,
+/// `boxing` is empty without it
+fn boxed(boxing: &str, value: String) -> String {
+    if boxing.is_empty() {
+        value
+    } else {
+        format!("{}({})", boxing, value)
+    }
+}
+
+/// Runtime overrides (`per_instance`, `scoped` and `hot_swap`) replace the
+/// attribute mock, wrap it getting it as `next` or are forbidden. Returns the
+/// type of the overrides, the arguments they are called with and the `next`
+/// function
+fn override_wiring(
+    function: &MockedFn,
+    options: &MockedOptions,
+    arms: &[Arm],
+    mock_gate: &str,
+    boxing: &str,
+) -> Result<(String, String, String), Diagnostic> {
+    let fn_name = &function.name;
+    let fn_next_name = Item::Next.name(fn_name);
+    let fn_type = function.pointer_type();
+    let overridable = ["per_instance", "scoped", "hot_swap", "keep_in_release"];
+    match options.overrides {
+        Overrides::Replace => Ok((fn_type, function.arguments(), String::new())),
+        Overrides::Wrap => {
+            ensure!(
+                overridable.iter().any(|option| options.merged.is_enabled(option)),
+                options.merged.span("overrides"),
+                diagnostics::wrapping_without_overrides(fn_name)
+            );
+            ensure!(
+                !function.is_async && !options.is_kept_in_release && options.const_passthrough.is_none(),
+                options.merged.span("overrides"),
+                diagnostics::wrapping_unsupported(fn_name)
+            );
+
+            let next_param = if function.params.is_empty() {
+                format!("next: {}", fn_type)
+            } else {
+                format!("next: {}, {}", fn_type, function.params)
+            };
+            let override_type = format!(
+                "{}{}",
+                function.qualifiers,
+                fn_pointer_type(
+                    parse_generated(&next_param, fn_name, function.name_span)?,
+                    &function.tail
+                )
+            );
+            let override_arguments = if function.args.is_empty() {
+                format!("({}{})", function.fq(), fn_next_name)
+            } else {
+                format!("({}{}, {})", function.fq(), fn_next_name, function.args)
+            };
+            // the same signature without attributes of the function
+            let mut rest = &function.signature[..];
+            while let [hash, TokenTree::Group(group), tail @ ..] = rest {
+                if !is_punct(hash, '#') || group.delimiter() != Delimiter::Bracket {
+                    break;
+                }
+                rest = tail;
+            }
+            let next = format!(
+                r#"
+                #[cfg({mock_gate})]
+                #[doc(hidden)]
+                #[allow(unused_variables)]
+                {coverage}
+                {signature} {{
+                    {unsafe_block} {{ {dispatch} }}
+                }}
+                "#,
+                mock_gate = mock_gate,
+                coverage = options.coverage,
+                signature = rename_signature(rest.to_vec(), fn_name, &fn_next_name, "")
+                    .into_iter()
+                    .collect::<TokenStream>(),
+                unsafe_block = function.unsafe_block(),
+                dispatch = dispatch(arms, true, |arm| format!("return {};", boxed(boxing, arm.call.clone())))
+            );
+            Ok((override_type, override_arguments, next))
+        },
+        Overrides::Forbid => {
+            for option in &overridable {
+                ensure!(
+                    !options.merged.is_enabled(option),
+                    options.merged.span(option),
+                    diagnostics::forbidden_override(fn_name, option)
+                );
+            }
+            Ok((fn_type, function.arguments(), String::new()))
+        },
+    }
+}
+
+/// The module of `#[mocked(auto)]` with the mock returning a value configured
+/// in tests, `Default` until then
+fn auto_module(function: &MockedFn, options: &MockedOptions, mock_gate: &str) -> Fragment {
+    if !options.attributes.iter().any(|params| params.reference == "auto") {
+        return Fragment::default();
+    }
+    let items = format!(
+        r#"
+        #[cfg({mock_gate})]
+        {coverage}
+        {visibility} mod {auto_module} {{
+            #[allow(unused_imports)]
+            use super::*;
+
+            ::std::thread_local!(
+                static RETURN_VALUE: ::std::cell::RefCell<Option<{output}>> = ::std::cell::RefCell::new(None)
+            );
+
+            /// Sets the value returned by the mock of `{fn_name}` in the current thread
+            pub fn return_value(value: {output}) {{
+                RETURN_VALUE.with(|return_value| *return_value.borrow_mut() = Some(value));
+            }}
+
+            #[doc(hidden)]
+            pub fn {auto_return}() -> {output} {{
+                RETURN_VALUE.with(|return_value| return_value.borrow().clone()).unwrap_or_default()
+            }}
+        }}
+        "#,
+        mock_gate = mock_gate,
+        coverage = options.coverage,
+        visibility = visibility(&function.signature),
+        auto_module = naming::auto_module(&function.name),
+        fn_name = function.name,
+        output = output_type(&function.tail),
+        auto_return = naming::AUTO_RETURN
+    );
+    Fragment {
+        items,
+        ..Fragment::default()
+    }
+}
+
+/// Mocks installed for particular instances with `per_instance` take
+/// precedence over the attributes. Counts calls of the instance, dispatches to
+/// its mock and adds the registry functions
+fn instance_override(
+    function: &MockedFn,
+    options: &MockedOptions,
+    mock_gate: &str,
+    override_type: &str,
+    override_arguments: &str,
+) -> Result<Fragment, Diagnostic> {
+    if !options.is_per_instance {
+        return Ok(Fragment::default());
+    }
+    let fn_name = &function.name;
+    ensure!(
+        !function.has_generics,
+        options.merged.span("per_instance"),
+        diagnostics::per_instance_generics(fn_name)
+    );
+    // typed receivers (`self: Box<Self>`, `self: Pin<&mut Self>`) are keyed by the
+    // instance they point to
+    let instance = match split_top_level(function.params.clone()).into_iter().next() {
+        Some(receiver) if is_receiver(&receiver) && is_punct(&receiver[0], '&') => "self",
+        Some(receiver)
+            if is_receiver(&receiver)
+                && type_colon(&receiver).is_some_and(
+                    |colon| !matches!(&receiver[colon + 1..], [TokenTree::Ident(ident)] if ident == "Self"),
+                ) =>
+        {
+            "&*self"
+        },
+        _ => {
+            return Err((
+                options.merged.span("per_instance"),
+                diagnostics::per_instance_receiver(fn_name),
+            ));
+        },
+    };
+
+    let fn_instance_name = Item::Instance.name(fn_name);
+    let fn_instance_calls_name = Item::InstanceCalls.name(fn_name);
+    let tracking = format!("Self::{}({}, true);", fn_instance_calls_name, instance);
+    let dispatch = format!(
+        "if let Some(mock) = Self::{fn_instance_name}({instance}, None) {{ return mock{arguments}; }}",
+        fn_instance_name = fn_instance_name,
+        instance = instance,
+        arguments = override_arguments
+    );
+    let registry = format!(
+        r#"
+        #[cfg({mock_gate})]
+        #[doc(hidden)]
+        {coverage}
+        pub fn {fn_instance_name}(instance: &Self, mock: Option<Option<{fn_type}>>) -> Option<{fn_type}> {{
+            ::covers::runtime::instance_mock(Self::{fn_tracker_name}(), instance as *const Self as usize, mock)
+        }}
+
+        #[cfg({mock_gate})]
+        #[doc(hidden)]
+        {coverage}
+        pub fn {fn_instance_calls_name}(instance: &Self, hit: bool) -> usize {{
+            ::covers::runtime::instance_calls(Self::{fn_tracker_name}(), instance as *const Self as usize, hit)
+        }}
+        "#,
+        mock_gate = mock_gate,
+        coverage = options.coverage,
+        fn_instance_name = fn_instance_name,
+        fn_instance_calls_name = fn_instance_calls_name,
+        fn_tracker_name = Item::Tracker.name(fn_name),
+        fn_type = override_type
+    );
+    Ok(Fragment {
+        tracking,
+        dispatch,
+        items: registry,
+        ..Fragment::default()
+    })
+}
+
+/// Mocks installed for a scope with `scoped` take precedence over the
+/// attributes too. Dispatches to them and adds the functions installing them
+fn scoped_override(
+    function: &MockedFn,
+    options: &MockedOptions,
+    mock_gate: &str,
+    override_type: &str,
+    override_arguments: &str,
+) -> Result<Fragment, Diagnostic> {
+    if !options.is_scoped {
+        return Ok(Fragment::default());
+    }
+    ensure!(
+        !function.has_generics && !function.is_async,
+        options.merged.span("scoped"),
+        diagnostics::scoped_unsupported(&function.name)
+    );
+
+    let fq = function.fq();
+    let fn_tracker_name = Item::Tracker.name(&function.name);
+    let output = output_type(&function.tail);
+    let dispatch = format!(
+        "if let Some(mock) = ::covers::runtime::scoped_mock::<{fn_type}>({fq}{fn_tracker_name}()) {{ return \
+         mock{arguments}; }} if let Some(value) = \
+         ::covers::runtime::scoped_return::<{output}>({fq}{fn_tracker_name}()) {{ return value; }}",
+        fn_type = override_type,
+        output = output,
+        fq = fq,
+        fn_tracker_name = fn_tracker_name,
+        arguments = override_arguments
+    );
+    let registry = format!(
+        r#"
+        #[cfg({mock_gate})]
+        #[doc(hidden)]
+        {coverage}
+        pub fn {fn_scoped_name}(mock: {fn_type}) -> ::covers::runtime::ScopedMock {{
+            ::covers::runtime::ScopedMock::push({fq}{fn_tracker_name}(), mock)
+        }}
+
+        #[cfg({mock_gate})]
+        #[doc(hidden)]
+        {coverage}
+        pub fn {fn_scoped_return_name}(
+            stub: ::std::boxed::Box<dyn Fn() -> {output}>,
+        ) -> ::covers::runtime::ScopedMock {{
+            ::covers::runtime::ScopedMock::push_return({fq}{fn_tracker_name}(), stub)
+        }}
+        "#,
+        mock_gate = mock_gate,
+        coverage = options.coverage,
+        fn_scoped_name = Item::Scoped.name(&function.name),
+        fn_scoped_return_name = Item::ScopedReturn.name(&function.name),
+        fn_type = override_type,
+        output = output,
+        fq = fq,
+        fn_tracker_name = fn_tracker_name
+    );
+    Ok(Fragment {
+        dispatch,
+        items: registry,
+        ..Fragment::default()
+    })
+}
+
+/// Dispatch of the wrapper to the mock swapped with `swap_mock!`
+fn swap_dispatch(function: &MockedFn, override_arguments: &str) -> String {
+    format!(
+        "if let Some(mock) = {fq}{fn_swap_name}(None) {{ return mock{arguments}; }}",
+        fq = function.fq(),
+        fn_swap_name = Item::Swap.name(&function.name),
+        arguments = override_arguments
+    )
+}
+
+/// Mocks swapped at runtime with `hot_swap` are shared by all threads.
+/// Dispatches to them in the builds with mocks, and the ones without with
+/// `keep_in_release`, and adds the function swapping them
+fn hot_swap_override(
+    function: &MockedFn,
+    options: &MockedOptions,
+    mock_gate: &str,
+    override_type: &str,
+    override_arguments: &str,
+) -> Result<Fragment, Diagnostic> {
+    if !options.is_hot_swap {
+        return Ok(Fragment::default());
+    }
+    ensure!(
+        !function.has_generics && !function.is_async,
+        options.merged.span(if options.is_kept_in_release {
+            "keep_in_release"
+        } else {
+            "hot_swap"
+        }),
+        diagnostics::hot_swap_unsupported(&function.name)
+    );
+
+    let swap_dispatch = swap_dispatch(function, override_arguments);
+    let (registry_gate, release_dispatch) = if options.is_kept_in_release {
+        let dispatch = format!(
+            "#[cfg(not({}))] {} {{ {} }}",
+            mock_gate,
+            function.unsafe_block(),
+            swap_dispatch
+        );
+        (String::new(), dispatch)
+    } else {
+        (format!("#[cfg({})]", mock_gate), String::new())
+    };
+    let registry = format!(
+        r#"
+        {registry_gate}
+        #[doc(hidden)]
+        {coverage}
+        pub fn {fn_swap_name}(mock: Option<Option<{fn_type}>>) -> Option<{fn_type}> {{
+            static POINTER: ::covers::runtime::HotSwap = ::covers::runtime::HotSwap::new();
+            // SAFETY: the pointer is accessed here only, always as `{fn_type}`
+            unsafe {{
+                match mock {{
+                    None => POINTER.get(),
+                    Some(mock) => POINTER.swap(mock),
+                }}
+            }}
+        }}
+        "#,
+        registry_gate = registry_gate,
+        coverage = options.coverage,
+        fn_swap_name = Item::Swap.name(&function.name),
+        fn_type = override_type
+    );
+    Ok(Fragment {
+        dispatch: swap_dispatch,
+        release_dispatch,
+        items: registry,
+        ..Fragment::default()
+    })
+}
+
+/// The blocking variant of `async fn` with `also_blocking`, it shares the
+/// dispatch with the original
+fn blocking_fn(function: &MockedFn, options: &MockedOptions) -> Result<String, Diagnostic> {
+    let blocking_name = match &options.also_blocking {
+        Some(blocking_name) => blocking_name,
+        None => return Ok(String::new()),
+    };
+    ensure!(
+        function.is_async,
+        options.merged.span("also_blocking"),
+        diagnostics::blocking_not_async(&function.name)
+    );
+
+    Ok(format!(
+        "{coverage} {signature} {{ {block_on}({fq}{fn_name}{turbofish}{arguments}) }}",
+        coverage = options.coverage,
+        signature = rename_signature(function.signature.clone(), &function.name, blocking_name, "async")
+            .into_iter()
+            .collect::<TokenStream>(),
+        block_on = options.block_on,
+        fq = function.fq(),
+        fn_name = function.name,
+        turbofish = function.turbofish,
+        arguments = function.arguments()
+    ))
+}
+
+/// Records arguments of the calls for `captured!` (spies too), the receiver
+/// is skipped. A projection closure records only a part of them, e.g. to not
+/// format huge payloads
+fn capture_statement(function: &MockedFn, options: &MockedOptions, mock_gate: &str) -> String {
+    if !options.is_spy() && options.capture.is_none() {
+        return String::new();
+    }
+    let args = function
+        .args
+        .split(", ")
+        .filter(|arg| !arg.is_empty() && *arg != "self")
+        .collect::<Vec<_>>();
+    let captured = match options.capture.as_deref() {
+        Some(projection) if projection != "true" => {
+            let args = match args.as_slice() {
+                [] => "()".to_string(),
+                [arg] => format!("&{}", arg),
+                _ => format!(
+                    "({},)",
+                    args.iter()
+                        .map(|arg| format!("&{}", arg))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            format!("::covers::runtime::project({}, {})", args, projection)
+        },
+        _ => {
+            let args = args
+                .iter()
+                .map(|arg| format!("::std::format!(\"{{:?}}\", {})", arg))
+                .collect::<Vec<_>>();
+            format!("<[::std::string::String]>::join(&[{}], \", \")", args.join(", "))
+        },
+    };
+    format!(
+        "#[cfg({})] {}{}().capture({});",
+        mock_gate,
+        function.fq(),
+        Item::Tracker.name(&function.name),
+        captured
+    )
+}
+
+/// The function describing the wiring for `explain!`, it is available
+/// wherever the function is wrapped
+fn explain_fn(
+    function: &MockedFn,
+    options: &MockedOptions,
+    arms: &[Arm],
+    mock_gate: &str,
+    gate: &str,
+    is_disabled: &str,
+) -> String {
+    format!(
+        r#"
+        {gate}
+        #[doc(hidden)]
+        #[allow(unreachable_code)]
+        {coverage}
+        pub fn {fn_explain_name}() -> ::covers::runtime::Explanation {{
+            let mock = (|| {{
+                {explain_dispatch}
+                ""
+            }})();
+            let original_override = false;
+            #[cfg({mock_gate})]
+            let original_override = {fq}{fn_switch_name}().with(|original| original.get()) || {is_disabled};
+            let calls = 0;
+            #[cfg({mock_gate})]
+            let calls = {fq}{fn_tracker_name}().calls();
+            ::covers::runtime::Explanation {{
+                function: {fn_name:?},
+                gate: if cfg!({mock_gate}) {{
+                    {mock_gate_name:?}
+                }} else if cfg!(debug_assertions) {{
+                    "debug_assertions"
+                }} else {{
+                    "none"
+                }},
+                mock,
+                original: {fn_orig_name:?},
+                prefix: {prefix:?},
+                original_override,
+                calls,
+            }}
+        }}
+        "#,
+        gate = gate,
+        coverage = options.coverage,
+        fn_explain_name = Item::Explain.name(&function.name),
+        explain_dispatch = dispatch(arms, false, |arm| format!("return {:?};", arm.mock)),
+        mock_gate = mock_gate,
+        fq = function.fq(),
+        fn_switch_name = Item::Switch.name(&function.name),
+        is_disabled = is_disabled,
+        fn_tracker_name = Item::Tracker.name(&function.name),
+        fn_name = function.name,
+        mock_gate_name = if mock_gate == default_mock_gate() {
+            "test"
+        } else {
+            mock_gate
+        },
+        fn_orig_name = function.orig_name,
+        prefix = options.prefix,
+    )
+}
+
+/// Expands `#[mocked]` applied to impl blocks: the marked methods are
+/// expanded in place, while for `impl Trait for Type` their originals and the
+/// hidden items move to an inherent `impl Type` block
+fn expand_mocked_impl(args: TokenStream, input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let mut header: Vec<TokenTree> = input.into_iter().collect();
+    let block = match header.pop() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Brace && args.is_empty() => group,
+        _ => return Err((input_span(&args), diagnostics::unsupported_impl_form())),
+    };
+
+    // `impl<..> Trait for Type where ..`, generic parameters could contain
+    // `for<'a>` and `->`
+    let impl_index = header
+        .iter()
+        .position(|token| matches!(token, TokenTree::Ident(ident) if ident == "impl"))
+        .ok_or_else(|| (input_span(&args), diagnostics::unsupported_impl_form()))?;
+    let mut generics_end = impl_index + 1;
+    let mut for_index = None;
+    let mut depth = 0;
+    let mut is_arrow = false;
+    for (index, token) in header.iter().enumerate().skip(impl_index + 1) {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '<' => depth += 1,
+            TokenTree::Punct(punct) if punct.as_char() == '>' && !is_arrow => {
+                depth -= 1;
+                if depth == 0 && generics_end == impl_index + 1 && is_punct(&header[impl_index + 1], '<') {
+                    generics_end = index + 1;
+                }
+            },
+            TokenTree::Ident(ident) if depth == 0 && ident == "for" => {
+                for_index = Some(index);
+                break;
+            },
+            _ => (),
+        }
+        is_arrow = matches!(token, TokenTree::Punct(punct)
+            if punct.as_char() == '-' && punct.spacing() == Spacing::Joint);
+    }
+    // inherent impl blocks keep all the expanded items
+    let is_trait_impl = for_index.is_some();
+    let for_index = for_index.unwrap_or(generics_end - 1);
+    let where_index = header[for_index..]
+        .iter()
+        .position(|token| matches!(token, TokenTree::Ident(ident) if ident == "where"))
+        .map_or(header.len(), |index| for_index + index);
+    let self_type = &header[for_index + 1..where_index];
+    // the name of the type without its path and generic arguments, e.g. `Parser` of
+    // `parser::Parser<'a>`
+    let type_name = self_type
+        .iter()
+        .take_while(|token| !is_punct(token, '<'))
+        .filter_map(|token| match token {
+            TokenTree::Ident(ident) => Some(ident.to_string()),
+            _ => None,
+        })
+        .last();
+
+    // the originals could not be moved to `&Type` or type parameters of blanket
+    // impls
+    let generics = header[impl_index + 1..generics_end]
+        .iter()
+        .cloned()
+        .collect::<TokenStream>();
+    let parameters = if generics_end > impl_index + 1 {
+        header[impl_index + 2..generics_end - 1].iter().cloned().collect()
+    } else {
+        TokenStream::new()
+    };
+    let is_type_parameter = |name: &Ident| {
+        split_top_level(parameters.clone())
+            .iter()
+            .any(|param| match param.as_slice() {
+                [TokenTree::Ident(keyword), TokenTree::Ident(parameter), ..] if keyword == "const" => parameter == name,
+                [TokenTree::Ident(parameter), ..] => parameter == name,
+                _ => false,
+            })
+    };
+    ensure!(
+        !is_trait_impl
+            || matches!(self_type.first(), Some(TokenTree::Ident(name)) if name != "dyn" && !is_type_parameter(name)),
+        self_type.first().map_or_else(Span::call_site, TokenTree::span),
+        diagnostics::unsupported_impl_type(self_type.iter().cloned().collect::<TokenStream>())
+    );
+
+    let mut trait_items = TokenStream::new();
+    let mut inherent_items = TokenStream::new();
+    for (fn_name, item) in split_impl_items(block.stream()) {
+        let (fn_name, (attribute, mut args)) = match (fn_name, find_mocked_attribute(&item)) {
+            (Some(fn_name), Some(attribute)) => (fn_name, attribute),
+            _ => {
+                trait_items.extend(item);
+                continue;
+            },
+        };
+
+        // the originals are siblings of the methods in the inherent impl
+        if !parse_params(args.clone())?.options.contains_key("scope") {
+            if !args.is_empty() {
+                args.extend(std::iter::once(TokenTree::from(Punct::new(',', Spacing::Alone))));
+            }
+            args.extend(vec![
+                TokenTree::from(Ident::new("scope", Span::call_site())),
+                TokenTree::from(Punct::new('=', Spacing::Alone)),
+                TokenTree::from(Ident::new("impl", Span::call_site())),
+            ]);
+        }
+        let method = item[..attribute]
+            .iter()
+            .chain(&item[attribute + 2..])
+            .cloned()
+            .collect();
+        for (name, expanded) in split_impl_items(expand_mocked_fn(args, method, type_name.as_deref())?) {
+            match name {
+                Some(name) if is_trait_impl && name != fn_name => inherent_items.extend(expanded),
+                _ => trait_items.extend(expanded),
+            }
+        }
+    }
+
+    let mut result: TokenStream = header.iter().cloned().collect();
+    result.extend(std::iter::once(TokenTree::from(Group::new(Brace, trait_items))));
+    if !inherent_items.is_empty() {
+        // the inherent impl shares conditional compilation of the trait impl only
+        let mut attributes = TokenStream::new();
+        for pair in header[..impl_index].windows(2) {
+            if let [hash, TokenTree::Group(group)] = pair {
+                if is_punct(hash, '#') && is_attribute(group, "cfg") {
+                    attributes.extend(pair.iter().cloned());
+                }
+            }
+        }
+        let code = format!(
+            "{attributes} impl {generics} {self_type} {where_clause} {{ {items} }}",
+            attributes = attributes,
+            generics = generics,
+            self_type = self_type.iter().cloned().collect::<TokenStream>(),
+            where_clause = header[where_index..].iter().cloned().collect::<TokenStream>(),
+            items = inherent_items
+        );
+        let type_name = type_name.unwrap_or_default();
+        result.extend(parse_generated(&code, &type_name, block.span())?);
+    }
+    Ok(result)
+}
+
+/// Usage of `#[mocked_impl]`, shown when its arguments could not be parsed
+pub(crate) const MOCKED_IMPL_USAGE: &str = "#[mocked_impl(method => path::to::mock, ..)] impl Type { .. }";
+
+/// Wires the listed methods of an impl block to their mocks at once
+///
+/// Accepts `method => path::to::mock` pairs, each of them is the same as
+/// `#[mocked(path::to::mock)]` on the method, with `scope = impl` implied, so
+/// static methods need no hints. Options of the method are still passed with
+/// `#[mocked(..)]` on it, the listed mock is prepended to them. Works for
+/// inherent impls and `impl Trait for Type` blocks like `#[mocked]` on them.
+///
+/// Example:
+/// ```rust
+/// pub struct Struct {}
+///
+/// mod module {
+///     use super::*;
+///
+///     pub fn yyy(this: Struct, name: &str) -> String {
+///         format!("Response: Mocked({})", name)
+///     }
+/// }
+///
+/// #[covers::mocked_impl(baz => Struct::mock_baz, xxx => module::yyy)]
+/// impl Struct {
+///     fn baz(name: &str) -> String {
+///         format!("Response: Baz = {}", name)
+///     }
+///
+///     fn mock_baz(name: &str) -> String {
+///         format!("Response: Mocked(Baz = {})", name)
+///     }
+///
+///     fn xxx(self, name: &str) -> String {
+///         format!("Response: Xxx = {}", name)
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn mocked_impl(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_attribute(args, input, |args, input| {
+        expand_mocked_impl_list(args, input, MOCKED_IMPL_USAGE)
+    })
+}
+
+/// Wires the methods listed as `method => path::to::mock` pairs with
+/// `#[mocked]` and expands the impl block
+fn expand_mocked_impl_list(args: TokenStream, input: TokenStream, example: &str) -> Result<TokenStream, Diagnostic> {
+    if is_expansion_disabled() {
+        return Ok(with_env_tracking(input));
+    }
+
+    let mut mocks = split_params(args)
+        .into_iter()
+        .map(|param| match param.as_slice() {
+            [TokenTree::Ident(method), eq, gt, mock @ ..]
+                if is_punct(eq, '=') && is_punct(gt, '>') && !mock.is_empty() =>
+            {
+                Ok((method.clone(), mock.iter().cloned().collect::<TokenStream>()))
+            },
+            _ => Err((param[0].span(), diagnostics::usage(example))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut header: Vec<TokenTree> = input.into_iter().collect();
+    let block = match header.pop() {
+        Some(TokenTree::Group(group))
+            if group.delimiter() == Brace
+                && header
+                    .iter()
+                    .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "impl")) =>
+        {
+            group
+        },
+        _ => return Err((Span::call_site(), diagnostics::usage(example))),
+    };
+
+    let mut items = TokenStream::new();
+    for (fn_name, mut item) in split_impl_items(block.stream()) {
+        let index = fn_name.and_then(|fn_name| mocks.iter().position(|(method, _)| *method == fn_name));
+        if let Some(index) = index {
+            let (method, mock) = mocks.remove(index);
+            // the mock goes first among the options of `#[mocked(..)]` already marking the
+            // method
+            let args = match find_mocked_attribute(&item) {
+                Some((attribute, args)) => {
+                    item.drain(attribute..attribute + 2);
+                    args
+                },
+                None => TokenStream::new(),
+            };
+            let code = if args.is_empty() {
+                format!("#[mocked({})]", mock)
+            } else {
+                format!("#[mocked({}, {})]", mock, args)
+            };
+            items.extend(parse_generated(&code, &method.to_string(), method.span())?);
+        }
+        items.extend(item);
+    }
+    if let Some((method, _)) = mocks.first() {
+        return Err((method.span(), diagnostics::unknown_impl_method(method)));
+    }
+
+    header.push(TokenTree::from(Group::new(Brace, items)));
+    expand_mocked_impl(TokenStream::new(), header.into_iter().collect())
+}
+
+/// Rewires methods of `impl Trait for Type` block to the listed mocks in
+/// tests, keeping the originals for release
+///
+/// The same as [`macro@mocked_impl`] accepting trait impls only, the mocks
+/// take the receiver as the first argument.
+///
+/// Example:
+/// ```rust
+/// #[covers::mock_trait(get => mocks::get, put => mocks::put)]
+/// impl Store for Client {
+///     fn get(&self, key: &str) -> Option<String> {
+///         self.http.get(key)
+///     }
+///
+///     fn put(&mut self, key: &str, value: String) {
+///         self.http.put(key, value)
+///     }
+/// }
+///
+/// mod mocks {
+///     pub fn get(this: &Client, key: &str) -> Option<String> {
+///         Some(format!("<{}>", key))
+///     }
+///
+///     pub fn put(this: &mut Client, key: &str, value: String) {}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn mock_trait(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_attribute(args, input, expand_mock_trait)
+}
+
+/// Usage of `#[mock_trait]`, shown when its arguments could not be parsed
+pub(crate) const MOCK_TRAIT_USAGE: &str = "#[mock_trait(method => path::to::mock, ..)] impl Trait for Type { .. }";
+
+fn expand_mock_trait(args: TokenStream, input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let is_trait_impl = input
+        .clone()
+        .into_iter()
+        .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "for"));
+    ensure!(is_trait_impl, Span::call_site(), diagnostics::usage(MOCK_TRAIT_USAGE));
+    expand_mocked_impl_list(args, input, MOCK_TRAIT_USAGE)
+}
+
+/// Marks the following function to be built only for testing purposes
+///
+/// In other words it is prepended with `#[cfg(any(debug_assertions, test))]`.
+///
+/// * It is very useful to not compile mock functions for release.
+/// * It makes function public - Can be disabled with `features = ["no-pub"]` or
+///   per function passing the visibility: `#[mock(pub(crate))]`, or `#[mock(vis
+///   = private)]` to keep the mock private to its module
+/// * It is **strictly** needed when we use reference to original logic of the
+///   mocked function.
+///
+/// Example:
+/// ```rust
+/// #[mocked(mock_bar)]
+/// fn bar(name: &str) -> String {
+///     format!("Response: Bar = {}", name)
+/// }
+///
+/// #[mock]
+/// pub fn mock_bar(name: &str) -> String {
+///     let original_function_result = _bar(name);
+///     format!("Response: Mocked({})", original_function_result)
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn mock(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_attribute(args, input, expand_mock)
+}
+
+fn expand_mock(args: TokenStream, input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    // mocks could call the originals, which are not emitted
+    if is_expansion_disabled() {
+        return Ok(TokenStream::new());
+    }
+
+    let span = input_span(&args);
+    let mut args: Vec<TokenTree> = args.into_iter().collect();
+    if matches!(&args[..], [TokenTree::Ident(vis), eq, ..] if vis == "vis" && is_punct(eq, '=')) {
+        args.drain(..2);
+    }
+    let visibility = args.into_iter().collect::<TokenStream>();
+    let input = match visibility.to_string().replace(' ', "").as_str() {
+        "" if cfg!(feature = "no-pub") => input,
+        "" => make_public(input),
+        "private" => with_visibility(input, TokenStream::new()),
+        vis if vis == "pub" || vis.starts_with("pub(") => with_visibility(input, visibility),
+        _ => return Err((span, diagnostics::unknown_mock_vis(&visibility.to_string()))),
+    };
+    let gate = parse_macro_expansion(format!("#[cfg({})]", active_gate(&default_mock_gate())), "#[mock]")?;
+    Ok(gate.into_iter().chain(input).collect())
+}
+
+/// Routes the mocked function to its original implementation
+/// within the block, even when `#[cfg(test)]` is enabled.
+///
+/// Accepts a reference to the function wrapped with `#[mocked]`
+/// (the same path used to call it) and a block. The block's value is returned.
+///
+/// * Only the given function is affected - all the others stay mocked.
+/// * The switch is thread-local, so parallel tests do not interfere.
+/// * Previous state is restored on leaving the block, including unwinding.
+/// * Outside of `#[cfg(test)]` the block is evaluated as is.
+///
+/// Example:
+/// ```rust
+/// let response = covers::with_original!(foo, { foo("Real") });
+/// assert_eq!(response, "Response: Foo = Real");
+///
+/// let response = covers::with_original!(Struct::baz, { Struct::baz("Real") });
+/// assert_eq!(response, "Response: Baz = Real");
+/// ```
+#[proc_macro]
+pub fn with_original(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_with_original)
+}
+
+fn expand_with_original(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut path = vec![];
+    let mut block = vec![];
+
+    let mut iter = input.into_iter();
+    for token in &mut iter {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => break,
+            _ => path.push(token),
+        }
+    }
+    block.extend(iter);
+
+    ensure!(
+        !path.is_empty() && !block.is_empty(),
+        span,
+        diagnostics::usage("with_original!(path::to::function, { ... })")
+    );
+
+    let switch = Item::Switch.path(path)?;
+
+    let code = format!(
+        r#"
+        {{
+            #[cfg({mock_gate})]
+            struct CoversRestore(&'static ::std::thread::LocalKey<::std::cell::Cell<bool>>, bool);
+            #[cfg({mock_gate})]
+            impl ::std::ops::Drop for CoversRestore {{
+                fn drop(&mut self) {{
+                    let previous = self.1;
+                    self.0.with(|original| original.set(previous));
+                }}
+            }}
+            #[cfg({mock_gate})]
+            let _restore = {{
+                let switch = {switch}();
+                CoversRestore(switch, switch.with(|original| original.replace(true)))
+            }};
+            {block}
+        }}
+        "#,
+        mock_gate = default_mock_gate(),
+        switch = switch,
+        block = block.into_iter().collect::<TokenStream>(),
+    );
+
+    parse_macro_expansion(code, "with_original!")
+}
+
+/// Applies `#[mocked]` to each function of the block wiring it
+/// to the same named mock in the module (or struct) provided.
+///
+/// Cuts attribute noise when all the mocks are stored together.
+///
+/// Example:
+/// ```rust
+/// covers::mocked_group! {
+///     mocks => {
+///         fn foo(name: &str) -> String {
+///             format!("Response: Foo = {}", name)
+///         }
+///
+///         pub fn bar(name: &str) -> String {
+///             format!("Response: Bar = {}", name)
+///         }
+///     }
+/// }
+///
+/// mod mocks {
+///     pub fn foo(name: &str) -> String {
+///         format!("Response: Mocked(Foo = {})", name)
+///     }
+///
+///     pub fn bar(name: &str) -> String {
+///         format!("Response: Mocked(Bar = {})", name)
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn mocked_group(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_mocked_group)
+}
+
+fn expand_mocked_group(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut tokens: Vec<TokenTree> = input.into_iter().collect();
+    let usage = diagnostics::usage("mocked_group! { path::to::mocks => { fn a() {} fn b() {} } }");
+
+    let block = match tokens.pop() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Brace => group,
+        _ => return Err((span, usage)),
+    };
+    let arrow = tokens
+        .len()
+        .checked_sub(2)
+        .filter(|&index| is_punct(&tokens[index], '=') && is_punct(&tokens[index + 1], '>'));
+    let mocks = match arrow {
+        Some(index) if index > 0 => tokens[..index].iter().cloned().collect::<TokenStream>(),
+        _ => return Err((span, usage)),
+    };
+
+    let mut result = TokenStream::new();
+    for (fn_name, item) in split_fn_items(block.stream())? {
+        let reference = parse_macro_expansion(format!("{}::{}", mocks, fn_name), "mocked_group!")?;
+        result.extend(expand_mocked(reference, item)?);
+    }
+    Ok(result)
+}
+
+/// Generates a trait with a method per function of the block and a unit
+/// struct implementing it by delegating to the functions. The functions are
+/// passed through as is, so their `#[mocked]` wiring keeps working.
+///
+/// Eases gradual migration from free-function seams to trait-based
+/// dependency injection: new code accepts `impl Storage`, old code calls
+/// the functions, both end up in the same mocks in tests.
+///
+/// Example:
+/// ```rust
+/// covers::mocked_trait! {
+///     pub trait Storage for DefaultStorage {
+///         #[mocked(mocks::read)]
+///         pub fn read(key: &str) -> String {
+///             db::read(key)
+///         }
+///     }
+/// }
+///
+/// fn greet(storage: &impl Storage) -> String {
+///     format!("Hello, {}!", storage.read("name"))
+/// }
+///
+/// greet(&DefaultStorage);
+/// ```
+#[proc_macro]
+pub fn mocked_trait(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_mocked_trait)
+}
+
+fn expand_mocked_trait(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut tokens: Vec<TokenTree> = input.into_iter().collect();
+    let usage = diagnostics::usage("mocked_trait! { pub trait Storage for DefaultStorage { fn a() {} fn b() {} } }");
+
+    let block = match tokens.pop() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Brace => group,
+        _ => return Err((span, usage)),
+    };
+    let keyword = tokens.len().checked_sub(4).filter(|&index| {
+        matches!(&tokens[index], TokenTree::Ident(ident) if ident == "trait")
+            && matches!(&tokens[index + 2], TokenTree::Ident(ident) if ident == "for")
+    });
+    let (vis, trait_name, struct_name) = match keyword {
+        Some(index) => (
+            tokens[..index].iter().cloned().collect::<TokenStream>(),
+            tokens[index + 1].clone(),
+            tokens[index + 3].clone(),
+        ),
+        None => return Err((span, usage)),
+    };
+
+    let mut functions = TokenStream::new();
+    let mut declarations = String::new();
+    let mut implementations = String::new();
+    for (fn_name, item) in split_fn_items(block.stream())? {
+        let (signature, call) = trait_method(&fn_name, item.clone())?;
+        declarations.push_str(&format!("{};", signature));
+        implementations.push_str(&format!("{} {{ {} }}", signature, call));
+        functions.extend(item);
+    }
+
+    let code = format!(
+        r#"
+        {functions}
+
+        {vis} trait {trait_name} {{
+            {declarations}
+        }}
+
+        /// Implementation delegating to the functions
+        {vis} struct {struct_name};
+
+        impl {trait_name} for {struct_name} {{
+            {implementations}
+        }}
+        "#,
+        functions = functions,
+        vis = vis,
+        trait_name = trait_name,
+        struct_name = struct_name,
+        declarations = declarations,
+        implementations = implementations,
+    );
+    parse_generated(&code, &trait_name.to_string(), trait_name.span())
+}
+
+/// Returns the signature of the trait's method taking `&self` and the call
+/// of the function it delegates to
+fn trait_method(fn_name: &str, item: TokenStream) -> Result<(String, String), Diagnostic> {
+    let span = input_span(&item);
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    // the body
+    tokens.pop();
+
+    let mut head = vec![];
+    let mut generics = vec![];
+    let mut tail = vec![];
+    let mut args = None;
+    let mut depth = 0;
+    let mut is_arrow = false;
+    let mut is_async = false;
+
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        match &token {
+            // attributes and visibility belong to the function only
+            TokenTree::Punct(punct) if head.is_empty() && punct.as_char() == '#' => {
+                iter.next();
+                continue;
+            },
+            TokenTree::Ident(ident) if head.is_empty() && ident == "pub" => {
+                iter.next_if(|token| matches!(token, TokenTree::Group(group) if group.delimiter() == Parenthesis));
+                continue;
+            },
+            TokenTree::Ident(ident) if args.is_none() && ident == "async" => is_async = true,
+            TokenTree::Group(group) if args.is_none() && depth == 0 && group.delimiter() == Parenthesis => {
+                let (forwarded, params, _) = parse_args(group)?;
+                ensure!(
+                    !forwarded.starts_with("self"),
+                    group.span(),
+                    diagnostics::mocked_trait_receiver(fn_name)
+                );
+                args = Some((forwarded, params));
+                continue;
+            },
+            TokenTree::Punct(punct) if args.is_none() => {
+                match punct.as_char() {
+                    '<' => depth += 1,
+                    '>' if !is_arrow => depth -= 1,
+                    _ => (),
+                }
+                is_arrow = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+            },
+            _ => (),
+        }
+        if args.is_some() {
+            tail.push(token);
+            continue;
+        }
+        if depth > 1 || depth == 1 && !is_punct(&token, '<') {
+            generics.push(token.clone());
+        }
+        head.push(token);
+    }
+
+    let (forwarded, params) = args.ok_or_else(|| (span, diagnostics::not_function_item(fn_name)))?;
+    let params = params.stream();
+    let receiver = if params.is_empty() {
+        "&self".to_string()
+    } else {
+        format!("&self, {}", params)
+    };
+    let signature = format!(
+        "{}({}) {}",
+        head.into_iter().collect::<TokenStream>(),
+        receiver,
+        tail.into_iter().collect::<TokenStream>()
+    );
+    let call = format!(
+        "{}{}({}){}",
+        fn_name,
+        turbofish(generics),
+        forwarded,
+        if is_async { ".await" } else { "" }
+    );
+    Ok((signature, call))
+}
+
+/// Generates `MockTrait` struct implementing the trait for tests, methods of
+/// which run the behavior registered with `on_method`
+///
+/// * Behavior is a closure taking the arguments of the method without its
+///   receiver, state shared with the test could be kept in
+///   `covers::runtime::state`.
+/// * Methods without behavior run their default body, if any, or panic.
+/// * Associated consts with defaults are kept as is, supertraits should be
+///   implemented for the mock separately.
+/// * The mock is generated for `#[cfg(test)]` only (integration tests too with
+///   `covers::build::testing()`).
+///
+/// Example:
+/// ```rust
+/// #[covers::automock]
+/// pub trait Storage {
+///     fn read(&self, key: &str) -> String;
+///
+///     fn greet(&self) -> String {
+///         format!("Hello, {}!", self.read("name"))
+///     }
+/// }
+///
+/// let storage = MockStorage::default().on_read(|key| format!("<{}>", key));
+/// assert_eq!(storage.greet(), "Hello, <name>!");
+/// ```
+#[proc_macro_attribute]
+pub fn automock(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_attribute(args, input, expand_automock)
+}
+
+fn expand_automock(args: TokenStream, input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let mut header: Vec<TokenTree> = input.into_iter().collect();
+    let block = match header.pop() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Brace && args.is_empty() => group,
+        _ => return Err((input_span(&args), diagnostics::automock_form())),
+    };
+    let (trait_name, trait_span) = match header
+        .iter()
+        .position(|token| matches!(token, TokenTree::Ident(ident) if ident == "trait"))
+        .map(|index| (&header[index + 1], header.get(index + 2)))
+    {
+        Some((TokenTree::Ident(name), next)) if !next.is_some_and(|token| is_punct(token, '<')) => {
+            (name.to_string(), name.span())
+        },
+        _ => return Err((Span::call_site(), diagnostics::automock_form())),
+    };
+    let mock_name = naming::automock(&trait_name);
+
+    let mut fields = String::new();
+    let mut setters = String::new();
+    let mut methods = String::new();
+    for (fn_name, item) in split_impl_items(block.stream()) {
+        let fn_name = match fn_name {
+            Some(fn_name) => fn_name,
+            // associated consts with defaults need no implementation
+            None if item
+                .first()
+                .is_some_and(|token| matches!(token, TokenTree::Ident(ident) if ident == "const"))
+                && item.iter().any(|token| is_punct(token, '=')) =>
+            {
+                continue;
+            },
+            None => {
+                return Err((
+                    item.first().map_or_else(Span::call_site, TokenTree::span),
+                    diagnostics::automock_item(item.into_iter().collect::<TokenStream>()),
+                ));
+            },
+        };
+        let (field, setter, method) = automock_method(&mock_name, &fn_name, item)?;
+        fields.push_str(&field);
+        setters.push_str(&setter);
+        methods.push_str(&method);
+    }
+
+    let code = format!(
+        r#"
+        {header} {block}
+
+        /// Mock of [`{trait_name}`] running the behavior registered with `on_*` methods
+        #[cfg({mock_gate})]
+        #[derive(Default)]
+        #[allow(clippy::type_complexity)]
+        {vis} struct {mock_name} {{
+            {fields}
+        }}
+
+        #[cfg({mock_gate})]
+        impl {mock_name} {{
+            {setters}
+        }}
+
+        #[cfg({mock_gate})]
+        impl {trait_name} for {mock_name} {{
+            {methods}
+        }}
+        "#,
+        header = header.iter().cloned().collect::<TokenStream>(),
+        block = block,
+        trait_name = trait_name,
+        mock_gate = default_mock_gate(),
+        vis = visibility(&header),
+        mock_name = mock_name,
+        fields = fields,
+        setters = setters,
+        methods = methods,
+    );
+    parse_generated(&code, &trait_name, trait_span)
+}
+
+/// Returns the field keeping behavior of the trait's method, its setter and
+/// the implementation running it, static methods are passed through
+fn automock_method(
+    mock_name: &str,
+    fn_name: &str,
+    item: Vec<TokenTree>,
+) -> Result<(String, String, String), Diagnostic> {
+    let span = item.first().map_or_else(Span::call_site, TokenTree::span);
+    let fn_index = item
+        .iter()
+        .position(|token| matches!(token, TokenTree::Ident(ident) if ident == "fn"))
+        .ok_or_else(|| (span, diagnostics::automock_method(fn_name)))?;
+    let span = item.get(fn_index + 1).map_or(span, TokenTree::span);
+    let unsupported = || (span, diagnostics::automock_method(fn_name));
+    let args = match item.get(fn_index + 2) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Parenthesis => group,
+        _ => return Err(unsupported()),
+    };
+    let body = match item.last() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Brace => Some(group.clone()),
+        _ => None,
+    };
+    let head = item[..fn_index + 2].iter().cloned().collect::<TokenStream>();
+    let tail = &item[fn_index + 3..item.len() - 1];
+    if has_impl_trait(args.stream()) || has_impl_trait(tail.iter().cloned().collect()) {
+        return Err(unsupported());
+    }
+
+    let is_method = split_top_level(args.stream())
+        .first()
+        .is_some_and(|param| is_receiver(param));
+    if !is_method {
+        if body.is_none() {
+            return Err(unsupported());
+        }
+        return Ok((
+            String::new(),
+            String::new(),
+            item.into_iter().collect::<TokenStream>().to_string(),
+        ));
+    }
+
+    let (forwarded, params, destructuring) = parse_args(args)?;
+    let forwarded = forwarded.split_once(',').map_or("", |(_, args)| args);
+    let types = split_top_level(params.stream())
+        .into_iter()
+        .skip(1)
+        .map(|param| match type_colon(&param) {
+            // anonymous parameters of 2015 edition, e.g. `fn f(&self, u8)`
+            None => Err(unsupported()),
+            Some(colon) => Ok(param[colon + 1..].iter().cloned().collect::<TokenStream>().to_string()),
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", ");
+    let behavior = format!("dyn Fn({}) -> {}", types, output_type(tail));
+    let fallback = match body {
+        Some(body) => format!("{} {}", destructuring, body),
+        None => format!(
+            "::covers::runtime::missing_behavior(\"{}::{}\", \"{}\")",
+            mock_name,
+            fn_name,
+            naming::behavior_setter(fn_name)
+        ),
+    };
+
+    let field = format!("{}: ::std::option::Option<::std::boxed::Box<{}>>,", fn_name, behavior);
+    let setter = format!(
+        r#"
+        /// Registers behavior of `{fn_name}`
+        pub fn {setter}(mut self, behavior: impl {behavior} + 'static) -> Self {{
+            self.{fn_name} = ::std::option::Option::Some(::std::boxed::Box::new(behavior));
+            self
+        }}
+        "#,
+        fn_name = fn_name,
+        setter = naming::behavior_setter(fn_name),
+        behavior = &behavior["dyn ".len()..],
+    );
+    let method = format!(
+        r#"
+        {head}{params} {tail} {{
+            if let ::std::option::Option::Some(behavior) = self.{fn_name}.as_ref() {{
+                return behavior({forwarded});
+            }}
+            {fallback}
+        }}
+        "#,
+        head = head,
+        params = params,
+        tail = tail.iter().cloned().collect::<TokenStream>(),
+        fn_name = fn_name,
+        forwarded = forwarded,
+        fallback = fallback,
+    );
+    Ok((field, setter, method))
+}
+
+/// Expands to a string literal with the expansion of the macro provided,
+/// e.g. `__expansion!(mocked(mock_foo) fn foo() {})`. For self-testing only.
+#[cfg(feature = "self-test")]
+#[doc(hidden)]
+#[proc_macro]
+pub fn __expansion(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    self_test::expansion(input.into()).into()
+}
+
+/// Expands to the sources of the crate for the fuzz harness, e.g.
+/// `covers_macros::__fuzzing!();` at its root. For fuzzing only.
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+#[proc_macro]
+pub fn __fuzzing(_: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    fuzzing::sources().into()
+}
+
+/// Fails the test if the mocked function is executing in two or more threads
+/// simultaneously while the returned guard is alive.
+///
+/// Accepts a reference to the function wrapped with `#[mocked]`
+/// (the same path used to call it). Overlapping executions are recorded
+/// and reported with a panic when the guard is dropped, so the check
+/// does not interfere with the code under test. Useful to verify locking.
+/// Recursive calls in the same thread do not overlap, and the executions
+/// started before the guard are not seen.
+///
+/// Example:
+/// ```rust
+/// let _guard = covers::assert_not_concurrent!(db::write);
+/// run_workers(4);
+/// ```
+#[proc_macro]
+pub fn assert_not_concurrent(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_assert_not_concurrent)
+}
+
+fn expand_assert_not_concurrent(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let path: Vec<TokenTree> = input.into_iter().collect();
+    ensure!(
+        !path.is_empty(),
+        Span::call_site(),
+        diagnostics::usage("assert_not_concurrent!(path::to::function)")
+    );
+
+    let name = path.iter().cloned().collect::<TokenStream>().to_string();
+    let tracker = Item::Tracker.path(path)?;
+
+    parse_macro_expansion(
+        format!("{}().assert_not_concurrent({:?})", tracker, name),
+        "assert_not_concurrent!",
+    )
+}
+
+/// Installs a mock for a single instance of a struct, other instances keep
+/// using the mock provided to `#[mocked]`. The method should be marked with
+/// `#[mocked(mock_foo, per_instance = true)]` and take `&self`, `&mut self`
+/// or a smart pointer to `Self`, e.g. `self: Box<Self>`.
+///
+/// Accepts a reference to the method, the instance and a function or
+/// a non-capturing closure with the same signature as the method
+/// (`self` becomes `&Self`). Passing `None` removes the mock. Mocks are
+/// kept per thread and are keyed by the instance's address.
+///
+/// Example:
+/// ```rust
+/// covers::mock_instance!(Client::fetch, &offline, |_, _| Err(Error::Offline));
+/// assert!(service.sync(&online, &offline).is_partial());
+/// ```
+#[proc_macro]
+pub fn mock_instance(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_mock_instance)
+}
+
+fn expand_mock_instance(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (path, instance, mock) = match (params.next(), params.next(), params.next(), params.next()) {
+        (Some(path), Some(instance), Some(mock), None) => (path, instance, mock),
+        _ => {
+            return Err((
+                span,
+                diagnostics::usage("mock_instance!(path::to::method, &instance, mock_or_none)"),
+            ));
+        },
+    };
+
+    let registry = Item::Instance.path(path)?;
+    let instance = instance.into_iter().collect::<TokenStream>();
+    let mock = mock.into_iter().collect::<TokenStream>();
+    let mock = if mock.to_string() == "None" {
+        "Some(None)".to_string()
+    } else {
+        format!("Some(Some({}))", mock)
+    };
+
+    parse_macro_expansion(format!("{{ {}({}, {}); }}", registry, instance, mock), "mock_instance!")
+}
+
+/// Returns the number of calls of the method on a single instance, so tests
+/// could check which instances were used, e.g. "replica A was read twice,
+/// replica B never". The method should be marked with
+/// `#[mocked(mock_foo, per_instance = true)]`.
+///
+/// Accepts a reference to the method and the instance. Calls are counted per
+/// thread whether the call is dispatched to a mock or to the original, and
+/// are keyed by the instance's address, so an instance created at the address
+/// of a dropped one inherits its counter.
+///
+/// Example:
+/// ```rust
+/// service.sync(&primary, &secondary);
+/// assert_eq!(covers::instance_calls!(Replica::read, &primary), 2);
+/// assert_eq!(covers::instance_calls!(Replica::read, &secondary), 0);
+/// ```
+#[proc_macro]
+pub fn instance_calls(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_instance_calls)
+}
+
+fn expand_instance_calls(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (path, instance) = match (params.next(), params.next(), params.next()) {
+        (Some(path), Some(instance), None) => (path, instance),
+        _ => return Err((span, diagnostics::usage("instance_calls!(path::to::method, &instance)"))),
+    };
+
+    let counter = Item::InstanceCalls.path(path)?;
+    let instance = instance.into_iter().collect::<TokenStream>();
+
+    parse_macro_expansion(format!("{}({}, false)", counter, instance), "instance_calls!")
+}
+
+/// Returns the number of calls of the mocked function in the current thread,
+/// so tests could check how many times it was hit.
+///
+/// Accepts a reference to the function wrapped with `#[mocked]`. Calls are
+/// counted whether they are dispatched to a mock or to the original, threads
+/// spawned by the test count their own calls. The total number of calls
+/// across threads is reported by [`explain!`].
+///
+/// Example:
+/// ```rust
+/// service.sync();
+/// assert_eq!(covers::calls!(db::load), 2);
+/// assert_eq!(covers::calls!(Cache::evict), 0);
+/// ```
+#[proc_macro]
+pub fn calls(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_calls)
+}
+
+fn expand_calls(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let path: Vec<TokenTree> = input.into_iter().collect();
+    ensure!(
+        !path.is_empty(),
+        Span::call_site(),
+        diagnostics::usage("calls!(path::to::function)")
+    );
+
+    let tracker = Item::Tracker.path(path)?;
+    parse_macro_expansion(format!("{}().thread_calls()", tracker), "calls!")
+}
+
+/// Returns `Debug` representation of the values returned by the calls of the
+/// function in the current thread. The function should be marked with
+/// `#[mocked(spy)]`.
+///
+/// Example:
+/// ```rust
+/// service.register("Abba", 4);
+/// assert_eq!(covers::returned!(db::insert), ["Ok(1)"]);
+/// ```
+#[proc_macro]
+pub fn returned(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_returned)
+}
+
+fn expand_returned(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let path: Vec<TokenTree> = input.into_iter().collect();
+    ensure!(
+        !path.is_empty(),
+        Span::call_site(),
+        diagnostics::usage("returned!(path::to::function)")
+    );
+
+    let tracker = Item::Tracker.path(path)?;
+    parse_macro_expansion(format!("{}().returned()", tracker), "returned!")
+}
+
+/// Expects the mocked function to be called in the current thread from now
+/// on, the calls are checked by `covers::verify()` at the end of the test.
+///
+/// Accepts a reference to the function wrapped with `#[mocked]`. At least one
+/// call is expected unless narrowed down with `times`, `never`, `at_least` or
+/// `at_most` of the returned `covers::runtime::Expectation`. Calls are counted
+/// whether they are dispatched to a mock or to the original.
+///
+/// Example:
+/// ```rust
+/// covers::expect!(db::load).times(2);
+/// covers::expect!(Cache::evict).never();
+/// service.sync();
+/// covers::verify();
+/// ```
+#[proc_macro]
+pub fn expect(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_expect)
+}
+
+fn expand_expect(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let path: Vec<TokenTree> = input.into_iter().collect();
+    ensure!(
+        !path.is_empty(),
+        Span::call_site(),
+        diagnostics::usage("expect!(path::to::function)")
+    );
+
+    let name = path.iter().map(ToString::to_string).collect::<String>();
+    let tracker = Item::Tracker.path(path)?;
+    parse_macro_expansion(
+        format!("::covers::runtime::expect({:?}, {}())", name, tracker),
+        "expect!",
+    )
+}
+
+/// Defines a function installing a named set of expectations with one call,
+/// e.g. for tests of the same scenario.
+///
+/// Each statement is the argument of [`expect!`] followed by the methods
+/// narrowing it down, if any. The function is generated for `#[cfg(test)]`
+/// only (integration tests too with `covers::build::testing()`).
+///
+/// Example:
+/// ```rust
+/// covers::expectation_set! {
+///     pub happy_path {
+///         net::fetch.times(1);
+///         db::save;
+///         db::delete.never();
+///     }
+/// }
+///
+/// let _verifier = covers::verifier();
+/// happy_path();
+/// service.sync();
+/// ```
+#[proc_macro]
+pub fn expectation_set(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_expectation_set)
+}
+
+fn expand_expectation_set(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let usage = diagnostics::usage("expectation_set! { name { path::to::function.times(1); .. } }");
+    let mut tokens: Vec<TokenTree> = input.into_iter().collect();
+
+    let block = match tokens.pop() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Brace => group,
+        _ => return Err((span, usage)),
+    };
+    let name = match tokens.pop() {
+        Some(TokenTree::Ident(name)) => name,
+        _ => return Err((span, usage)),
+    };
+    let vis = tokens.into_iter().collect::<TokenStream>();
+
+    let mut expectations = String::new();
+    let mut statement = vec![];
+    for token in block.stream() {
+        if !is_punct(&token, ';') {
+            statement.push(token);
+            continue;
+        }
+        // the path ends with the first method: `db::save.times(1)`
+        let methods = statement
+            .iter()
+            .position(|token| is_punct(token, '.'))
+            .unwrap_or(statement.len());
+        let narrowing = statement.split_off(methods);
+        let expect = expand_expect(std::mem::take(&mut statement).into_iter().collect())?;
+        expectations.push_str(&format!(
+            "{}{}; ",
+            expect,
+            narrowing.into_iter().collect::<TokenStream>()
+        ));
+    }
+    ensure!(statement.is_empty(), statement[0].span(), usage);
+
+    let code = format!(
+        "#[cfg({mock_gate})] {vis} fn {name}() {{ {expectations} }}",
+        mock_gate = default_mock_gate(),
+        vis = vis,
+        name = name,
+        expectations = expectations,
+    );
+    parse_generated(&code, &name.to_string(), name.span())
+}
+
+/// Returns `Debug` representation of the arguments of the calls of the
+/// function in the current thread, joined with `, ` per call. The function
+/// should be marked with `#[mocked(mock_foo, capture)]`.
+///
+/// Example:
+/// ```rust
+/// service.register("Abba", 4);
+/// assert_eq!(covers::captured!(db::insert), [r#""Abba", 4"#]);
+/// ```
+#[proc_macro]
+pub fn captured(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_captured)
+}
+
+fn expand_captured(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let path: Vec<TokenTree> = input.into_iter().collect();
+    ensure!(
+        !path.is_empty(),
+        Span::call_site(),
+        diagnostics::usage("captured!(path::to::function)")
+    );
+
+    let tracker = Item::Tracker.path(path)?;
+    parse_macro_expansion(format!("{}().captured()", tracker), "captured!")
+}
+
+/// Tags the next call of the function in the current thread, its records are
+/// checked apart from the others with [`tagged!`].
+///
+/// Accepts a reference to the function wrapped with `#[mocked]` and the tag.
+/// The tag applies to the first call made after it, whether it is dispatched
+/// to a mock or to the original.
+///
+/// Example:
+/// ```rust
+/// covers::tag_next_call!(db::save, "after-retry");
+/// service.sync();
+/// assert_eq!(covers::tagged!(db::save, "after-retry").captured(), [r#""Abba""#]);
+/// ```
+#[proc_macro]
+pub fn tag_next_call(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, |input| expand_tag(input, "tag_next_call"))
+}
+
+/// Returns the records of the calls of the function tagged with
+/// [`tag_next_call!`] in the current thread: `calls()`, `captured()` and
+/// `returned()` of `covers::runtime::Tagged`.
+///
+/// Example:
+/// ```rust
+/// covers::tag_next_call!(db::save, "after-retry");
+/// service.sync();
+/// assert_eq!(covers::tagged!(db::save, "after-retry").calls(), 1);
+/// ```
+#[proc_macro]
+pub fn tagged(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, |input| expand_tag(input, "tagged"))
+}
+
+/// Expands `tag_next_call!` or `tagged!` to the method of the tracker
+fn expand_tag(input: TokenStream, method: &str) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (path, tag) = match (params.next(), params.next(), params.next()) {
+        (Some(path), Some(tag), None) => (path, tag),
+        _ => {
+            return Err((
+                span,
+                diagnostics::usage(&format!("{}!(path::to::function, tag)", method)),
+            ));
+        },
+    };
+
+    let tracker = Item::Tracker.path(path)?;
+    let tag = tag.into_iter().collect::<TokenStream>();
+    parse_macro_expansion(format!("{}().{}({})", tracker, method, tag), &format!("{}!", method))
+}
+
+/// Overrides the mock of the function in the current thread until the
+/// returned guard is dropped. The function should be marked with
+/// `#[mocked(mock_foo, scoped = true)]`.
+///
+/// Accepts a reference to the function and a function or a non-capturing
+/// closure with the same signature (`self` becomes `Self`, `&Self` or
+/// `&mut Self`).
+///
+/// * Overrides are stacked: the innermost one wins, dropping a guard restores
+///   the one installed before it, even if guards are dropped out of order.
+/// * Guards are dropped while unwinding, so a panic caught with `catch_unwind`
+///   does not leak the override into the rest of the test.
+/// * [`mock_instance!`] takes precedence over scoped mocks.
+///
+/// Example:
+/// ```rust
+/// let _offline = covers::scoped_mock!(net::fetch, |_| Err(Error::Offline));
+/// {
+///     let _slow = covers::scoped_mock!(net::fetch, fetch_slowly);
+///     assert!(sync().is_slow());
+/// }
+/// assert!(sync().is_offline());
+/// ```
+#[proc_macro]
+pub fn scoped_mock(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_scoped_mock)
+}
+
+fn expand_scoped_mock(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (path, mock) = match (params.next(), params.next(), params.next()) {
+        (Some(path), Some(mock), None) => (path, mock),
+        _ => return Err((span, diagnostics::usage("scoped_mock!(path::to::function, mock)"))),
+    };
+
+    let scoped = Item::Scoped.path(path)?;
+    let mock = mock.into_iter().collect::<TokenStream>();
+
+    parse_macro_expansion(format!("{}({})", scoped, mock), "scoped_mock!")
+}
+
+/// Usages of `returns!` and the macros wrapping its value
+pub(crate) const RETURNS_USAGE: &str = "returns!(path::to::function, value)";
+pub(crate) const RETURNS_OK_USAGE: &str = "returns_ok!(path::to::function, value)";
+pub(crate) const RETURNS_ERR_USAGE: &str = "returns_err!(path::to::function, value)";
+pub(crate) const RETURNS_SOME_USAGE: &str = "returns_some!(path::to::function, value)";
+
+/// Stubs the function in the current thread with a value returned by each
+/// call until the returned guard is dropped, covering the common case of
+/// dependencies without writing a mock. The function should be marked with
+/// `#[mocked(mock_foo, scoped = true)]`, or `#[mocked(spy, scoped = true)]`
+/// to call the original when no stub is installed.
+///
+/// Accepts a reference to the function and the value, which is cloned for
+/// every call. Stubs are stacked with [`scoped_mock!`] ones: the innermost
+/// wins. [`returns_ok!`], [`returns_err!`], [`returns_some!`] and
+/// [`returns_none!`] wrap the value for `Result` and `Option` returns.
+///
+/// Example:
+/// ```rust
+/// let _user = covers::returns!(db::find_user, User::default());
+/// let _offline = covers::returns_err!(net::fetch, Error::Offline);
+/// let _missing = covers::returns_none!(cache::get);
+/// ```
+#[proc_macro]
+pub fn returns(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, |input| expand_returns(input, RETURNS_USAGE, ""))
+}
+
+/// Stubs the function returning `Ok(value)`, see [`returns!`].
+#[proc_macro]
+pub fn returns_ok(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, |input| expand_returns(input, RETURNS_OK_USAGE, "Ok"))
+}
+
+/// Stubs the function returning `Err(value)`, see [`returns!`].
+#[proc_macro]
+pub fn returns_err(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, |input| expand_returns(input, RETURNS_ERR_USAGE, "Err"))
+}
+
+/// Stubs the function returning `Some(value)`, see [`returns!`].
+#[proc_macro]
+pub fn returns_some(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, |input| expand_returns(input, RETURNS_SOME_USAGE, "Some"))
+}
+
+/// Stubs the function returning `None`, see [`returns!`].
+#[proc_macro]
+pub fn returns_none(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_returns_none)
+}
+
+fn expand_returns_none(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let path = match (params.next(), params.next()) {
+        (Some(path), None) => path,
+        _ => return Err((span, diagnostics::usage("returns_none!(path::to::function)"))),
+    };
+
+    let stub = Item::ScopedReturn.path(path)?;
+
+    parse_macro_expansion(format!("{}(::std::boxed::Box::new(|| None))", stub), "returns_none!")
+}
+
+/// Wraps the cloned value with the variant, e.g. `Ok`, if any
+fn expand_returns(input: TokenStream, usage: &str, variant: &str) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (path, value) = match (params.next(), params.next(), params.next()) {
+        (Some(path), Some(value), None) => (path, value),
+        _ => return Err((span, diagnostics::usage(usage))),
+    };
+
+    let stub = Item::ScopedReturn.path(path)?;
+    let value = value.into_iter().collect::<TokenStream>();
+
+    let code = format!(
+        "{}(::std::boxed::Box::new({{ let value = {}; move || {}(::std::clone::Clone::clone(&value)) }}))",
+        stub, value, variant
+    );
+    parse_macro_expansion(code, usage.split('(').next().unwrap_or_default())
+}
+
+/// Swaps the mock of the function for all threads at runtime, returning the
+/// previously swapped one. The function should be marked with
+/// `#[mocked(mock_foo, hot_swap = true)]`.
+///
+/// Accepts a reference to the function and a function or a non-capturing
+/// closure with the same signature (`self` becomes `Self`, `&Self` or
+/// `&mut Self`). Passing `None` restores the mock provided to `#[mocked]`.
+///
+/// The wrapper reads a single atomic pointer, so the overhead is predictable,
+/// e.g. for benchmarks or long-running test servers. [`mock_instance!`] and
+/// [`scoped_mock!`] take precedence over swapped mocks.
+///
+/// Example:
+/// ```rust
+/// covers::swap_mock!(net::fetch, |_| Err(Error::Offline));
+/// assert!(server.health().is_degraded());
+/// covers::swap_mock!(net::fetch, None);
+/// ```
+#[proc_macro]
+pub fn swap_mock(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_swap_mock)
+}
+
+fn expand_swap_mock(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (path, mock) = match (params.next(), params.next(), params.next()) {
+        (Some(path), Some(mock), None) => (path, mock),
+        _ => return Err((span, diagnostics::usage("swap_mock!(path::to::function, mock_or_none)"))),
+    };
+
+    let swap = Item::Swap.path(path)?;
+    let mock = mock.into_iter().collect::<TokenStream>();
+    let mock = if mock.to_string() == "None" {
+        "Some(None)".to_string()
+    } else {
+        format!("Some(Some({}))", mock)
+    };
+
+    parse_macro_expansion(format!("{}({})", swap, mock), "swap_mock!")
+}
+
+/// Expands the body only in builds where `#[mocked]` wrappers dispatch to
+/// mocks, i.e. under the same gate including `covers::build::testing()`.
+/// Useful for fixtures or registrations which should not outlive the mocks.
+///
+/// Accepts items or statements, the body is not wrapped into a block, so
+/// bindings stay visible after the macro.
+///
+/// Example:
+/// ```rust
+/// covers::when_mocking! {
+///     static FIXTURES: &str = include_str!("fixtures.json");
+/// }
+/// ```
+#[proc_macro]
+pub fn when_mocking(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_when_mocking)
+}
+
+fn expand_when_mocking(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    if is_expansion_disabled() {
+        return Ok(TokenStream::new());
+    }
+    parse_macro_expansion(
+        format!(
+            "#[cfg({})] ::covers::__when_mocking! {{ {} }}",
+            default_mock_gate(),
+            input
+        ),
+        "when_mocking!",
+    )
+}
+
+/// Refers to the original implementation of the mocked function without
+/// knowing the prefix in use (see `features = ["__"]` or `["_orig_"]`), the
+/// same as `mangle!(original, path)`.
+///
+/// Accepts a reference to the function wrapped with `#[mocked]`
+/// (the same path used to call it), so shared test utilities in other modules
+/// or crates could call the real implementation. The original is public,
+/// so only the modules on the path should be visible. Functions with their
+/// own `prefix` are not supported.
+///
+/// Example:
+/// ```rust
+/// let real = covers::original_of!(db::Client::write)(&client, "data");
+/// ```
+#[proc_macro]
+pub fn original_of(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_original_of)
+}
+
+fn expand_original_of(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let path: Vec<TokenTree> = input.into_iter().collect();
+    ensure!(
+        !path.is_empty(),
+        Span::call_site(),
+        diagnostics::usage("original_of!(path::to::function)")
+    );
+
+    Item::Original.path(path)
+}
+
+/// Refers to an item generated for the mocked function, e.g. for declarative
+/// macros of downstream crates building their own helpers on top of `covers`.
+///
+/// Accepts the name of the item (`original`, `switch`, `tracker`,
+/// `instance`, `instance_calls`, `scoped`, `scoped_return`, `swap`, `next`
+/// or `explain`) and a reference to the function wrapped with `#[mocked]`.
+/// The names follow the policy selected by features, see
+/// [`original_of!`].
+///
+/// Example:
+/// ```rust
+/// macro_rules! reset {
+///     ($function:path) => {
+///         covers::mangle!(swap, $function)(Some(None))
+///     };
+/// }
+/// ```
+#[proc_macro]
+pub fn mangle(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_mangle)
+}
+
+fn expand_mangle(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (item, path) = match (params.next(), params.next(), params.next()) {
+        (Some(item), Some(path), None) => (item, path),
+        _ => return Err((span, diagnostics::usage("mangle!(item, path::to::function)"))),
+    };
+
+    let name = item.iter().map(ToString::to_string).collect::<String>();
+    let item = Item::from_name(&name).ok_or_else(|| (span, diagnostics::unknown_item(&name, &Item::names())))?;
+    // `$function:path` fragments of declarative macros come as a single group
+    let path = match path.as_slice() {
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::None => group.stream().into_iter().collect(),
+        _ => path,
+    };
+    item.path(path)
+}
+
+/// Describes how the mocked function is wired: the active gate,
+/// the selected mock, the original's name and prefix in use, and whether
+/// the original is forced with [`with_original!`]. Useful to debug wiring
+/// in failing tests, the result implements `Display`.
+///
+/// Accepts a reference to the function wrapped with `#[mocked]`
+/// (the same path used to call it).
+///
+/// Example:
+/// ```rust
+/// println!("{}", covers::explain!(module::foo));
+/// ```
+#[proc_macro]
+pub fn explain(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_explain)
+}
+
+fn expand_explain(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let path: Vec<TokenTree> = input.into_iter().collect();
+    ensure!(
+        !path.is_empty(),
+        Span::call_site(),
+        diagnostics::usage("explain!(path::to::function)")
+    );
+
+    let explain = Item::Explain.path(path)?;
+    parse_macro_expansion(format!("{}()", explain), "explain!")
+}
+
+/// Fails the test if the mock's signature differs from the function's one,
+/// showing the types of the arguments and the output side by side. It guards
+/// against drift of mocks living far from the mocked functions.
+///
+/// Accepts paths to the function and the mock, e.g. `Struct::method` and a
+/// free function taking `&Struct`. Functions of up to 12 arguments are
+/// supported, `async` and `unsafe` ones are not.
+///
+/// Example:
+/// ```rust
+/// covers::assert_same_signature!(db::load, mocks::load);
+/// ```
+#[proc_macro]
+pub fn assert_same_signature(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_function(input, expand_assert_same_signature)
+}
+
+fn expand_assert_same_signature(input: TokenStream) -> Result<TokenStream, Diagnostic> {
+    let span = input_span(&input);
+    let mut params = split_params(input).into_iter();
+    let (function, mock) = match (params.next(), params.next(), params.next()) {
+        (Some(function), Some(mock), None) => (function, mock),
+        _ => {
+            return Err((
+                span,
+                diagnostics::usage("assert_same_signature!(path::to::function, path::to::mock)"),
+            ));
+        },
+    };
+
+    let function = function.into_iter().collect::<TokenStream>();
+    let mock = mock.into_iter().collect::<TokenStream>();
+    parse_macro_expansion(
+        format!(
+            "::covers::runtime::assert_same_signature({}, {}, {:?}, {:?})",
+            function,
+            mock,
+            function.to_string().replace(" :: ", "::"),
+            mock.to_string().replace(" :: ", "::")
+        ),
+        "assert_same_signature!",
+    )
+}
+
+/// Checks whether the prefix makes identifiers of any names, e.g. `orig_`
+fn is_prefix(prefix: &str) -> bool {
+    prefix.chars().all(|char| char.is_ascii_alphanumeric() || char == '_')
+        && prefix.chars().next().is_some_and(|char| !char.is_ascii_digit())
+}
+
+/// Checks whether the wrapping is turned off for the build with
+/// `COVERS_DISABLE` environment variable set to anything but `0` or `false`
+fn is_expansion_disabled() -> bool {
+    std::env::var(DISABLE_ENV).is_ok_and(|value| is_enabling(&value))
+}
+
+/// Items letting cargo track the environment variables read by the
+/// expansion, so the crate is rebuilt when they change. Reads of the macro
+/// itself are not recorded in the dep-info of the crate being compiled,
+/// `option_env!` expanded in the crate is
+fn env_tracking() -> String {
+    [DISABLE_ENV, PLUGIN_ENV, UNUSED_ORIGINAL_ENV, manifest::MANIFEST_ENV]
+        .iter()
+        .map(|name| {
+            format!(
+                "const _: ::core::option::Option<&str> = ::core::option_env!({:?});",
+                name
+            )
+        })
+        .collect()
+}
+
+/// Adds [`env_tracking`] to the function's body, or in front of the impl
+/// block, the input of the disabled expansion
+fn with_env_tracking(input: TokenStream) -> TokenStream {
+    if is_impl_block(&input) {
+        let mut tracking: TokenStream = env_tracking().parse().unwrap();
+        tracking.extend(input);
+        tracking
+    } else {
+        with_body_prefix(input, &env_tracking())
+    }
+}
+
+/// Checks whether the attribute is applied to an impl block, not a function
+fn is_impl_block(input: &TokenStream) -> bool {
+    input
+        .clone()
+        .into_iter()
+        .take_while(|token| !matches!(token, TokenTree::Ident(ident) if ident == "fn"))
+        .any(|token| matches!(&token, TokenTree::Ident(ident) if ident == "impl"))
+}
+
+fn is_enabling(value: &str) -> bool {
+    !matches!(value.trim(), "" | "0" | "false")
+}
+
+/// Predicate of the target's build when the wrapper dispatches to mocks unless
+/// `cfg` is passed, `covers::build::active_feature()` adds the crate's
+/// `covers-active` feature and `covers::build::testing()` adds [`TESTING_CFG`]
+fn default_mock_gate() -> String {
+    env_mock_gate(|name| std::env::var_os(name).is_some())
+}
+
+/// [`default_mock_gate`] by the environment variables set for the crate being
+/// compiled by its build script
+fn env_mock_gate(is_set: impl Fn(&str) -> bool) -> String {
+    let mut gates = vec![MOCK_GATE.to_string()];
+    if is_set(TESTING_ENV) {
+        gates.push(TESTING_CFG.to_string());
+    }
+    if is_set(ACTIVE_FEATURE_ENV) {
+        gates.push(format!("feature = {:?}", ACTIVE_FEATURE));
+    }
+    match gates.len() {
+        1 => MOCK_GATE.to_string(),
+        _ => format!("any({})", gates.join(", ")),
+    }
+}
+
+/// Predicate of the target's build when the function is wrapped, the mocks
+/// could be dispatched outside of debug builds under a custom gate
+fn active_gate(mock_gate: &str) -> String {
+    if mock_gate == MOCK_GATE {
+        ACTIVE_GATE.to_string()
+    } else {
+        format!("any({}, {})", ACTIVE_GATE, mock_gate)
+    }
+}
+
+/// Path of the module invoking the macro relative to the crate root, derived
+/// from the source file, e.g. `net::http` for `src/net/http.rs`. Empty for the
+/// crate root and when the file is unknown
+fn call_site_module() -> String {
+    let module = || {
+        let file = std::fs::canonicalize(Span::call_site().local_file()?).ok()?;
+        let root = std::fs::canonicalize(PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").ok()?).join("src")).ok()?;
+        Some(manifest::module_of(file.strip_prefix(root).ok()?))
+    };
+    module().unwrap_or_default()
+}
+
+/// Reads a path from the environment variable, relative ones are resolved
+/// against `CARGO_MANIFEST_DIR` of the crate being compiled
+fn env_path(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(std::env::var(name).ok().filter(|path| !path.trim().is_empty())?);
+    if path.is_absolute() {
+        return Some(path);
+    }
+    match std::env::var("CARGO_MANIFEST_DIR") {
+        Ok(dir) => Some(PathBuf::from(dir).join(path)),
+        Err(_) => Some(path),
+    }
+}
+
+/// Prepends attributes to the item deduplicating the outer ones, `#[cfg]`s go
+/// first in a stable order, so the expansion is the same across builds
+fn with_attributes(attributes: &str, item: TokenStream) -> TokenStream {
+    let attributes: TokenStream = attributes.parse().unwrap();
+    let tokens: Vec<TokenTree> = attributes.into_iter().chain(item).collect();
+
+    let mut cfgs: Vec<TokenStream> = vec![];
+    let mut others: Vec<TokenStream> = vec![];
+    let mut rest = &tokens[..];
+    while let [hash, TokenTree::Group(group), tail @ ..] = rest {
+        if !is_punct(hash, '#') || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        rest = tail;
+
+        let is_cfg = matches!(group.stream().into_iter().next(), Some(TokenTree::Ident(ident)) if ident == "cfg");
+        let list = if is_cfg { &mut cfgs } else { &mut others };
+        let attribute: TokenStream = vec![hash.clone(), TokenTree::from(group.clone())].into_iter().collect();
+        if !list
+            .iter()
+            .any(|existing| existing.to_string() == attribute.to_string())
+        {
+            list.push(attribute);
+        }
+    }
+    cfgs.sort_by_key(ToString::to_string);
+
+    cfgs.into_iter()
+        .chain(others)
+        .flatten()
+        .chain(rest.iter().cloned())
+        .collect()
+}
+
+fn make_public(input: TokenStream) -> TokenStream {
+    let mut result = vec![];
+    let mut is_public = false;
+
+    let mut iter = input.into_iter();
+    while let Some(token) = iter.next() {
+        match &token {
+            TokenTree::Ident(ident) if ident == "pub" => {
+                is_public = true;
+            },
+            // `pub async fn`, `pub unsafe extern "C" fn`
+            TokenTree::Ident(ident)
+                if ident == "fn" || ident == "async" || ident == "unsafe" || ident == "extern" || ident == "const" =>
+            {
+                if !&is_public {
+                    result.push(TokenTree::from(Ident::new("pub", ident.span())));
+                }
+                // push remaining
+                result.push(token.to_owned());
+                for token in iter {
+                    result.push(token.to_owned());
+                }
+                break;
+            },
+            _ => (),
+        }
+        result.push(token.to_owned());
+    }
+
+    result.into_iter().collect()
+}
+
+/// Replaces the visibility of the function, e.g. with `pub(crate)`
+fn with_visibility(input: TokenStream, visibility: TokenStream) -> TokenStream {
+    let mut result: Vec<TokenTree> = vec![];
+
+    let mut iter = input.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        match &token {
+            TokenTree::Ident(ident) if ident == "pub" => {
+                if matches!(iter.peek(), Some(TokenTree::Group(group)) if group.delimiter() == Parenthesis) {
+                    iter.next();
+                }
+                continue;
+            },
+            TokenTree::Ident(ident)
+                if ident == "fn" || ident == "async" || ident == "unsafe" || ident == "extern" || ident == "const" =>
+            {
+                result.extend(visibility);
+                result.push(token);
+                result.extend(iter);
+                break;
+            },
+            _ => (),
+        }
+        result.push(token);
+    }
+
+    result.into_iter().collect()
+}
+
+/// Builds the type of a function pointer accepted as a `per_instance` or
+/// `scoped` mock, e.g. `fn(&Self, &str) -> String` for
+/// `fn foo(&self, key: &str) -> String`
+fn fn_pointer_type(params: TokenStream, tail: &[TokenTree]) -> String {
+    let mut types = vec![];
+    for param in split_top_level(params) {
+        match type_colon(&param) {
+            Some(colon) => types.push(param[colon + 1..].iter().cloned().collect()),
+            // `self` becomes `Self`, `mut` of the binding is dropped
+            None => {
+                let is_binding_mut = matches!(param.first(), Some(TokenTree::Ident(ident)) if ident == "mut");
+                let receiver = param
+                    .into_iter()
+                    .skip(is_binding_mut as usize)
+                    .map(|token| match &token {
+                        TokenTree::Ident(ident) if ident == "self" => TokenTree::from(Ident::new("Self", ident.span())),
+                        _ => token,
+                    });
+                types.push(receiver.collect::<TokenStream>());
+            },
+        }
+    }
+
+    format!(
+        "fn({}) -> {}",
+        types.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+        output_type(tail)
+    )
+}
+
+/// Extracts the output type from the tokens following the parameters,
+/// `()` when omitted
+fn output_type(tail: &[TokenTree]) -> String {
+    let output = tail
+        .iter()
+        .skip_while(|token| is_punct(token, '-') || is_punct(token, '>'))
+        .take_while(|token| !matches!(token, TokenTree::Ident(ident) if ident == "where"))
+        .cloned()
+        .collect::<TokenStream>();
+    if output.is_empty() {
+        "()".to_string()
+    } else {
+        output.to_string()
+    }
+}
+
+/// Visibility of the function, e.g. `pub(crate)`, empty for private ones
+fn visibility(signature: &[TokenTree]) -> String {
+    let mut iter = signature
+        .iter()
+        .skip_while(|token| !matches!(token, TokenTree::Ident(ident) if ident == "pub"));
+    match (iter.next(), iter.next()) {
+        (Some(vis), Some(TokenTree::Group(group))) if group.delimiter() == Parenthesis => format!("{} {}", vis, group),
+        (Some(vis), _) => vis.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Runs the expansion of an attribute, its error is reported as a compile
+/// error pointing to the option it is about, the item is kept as is to avoid
+/// follow-up errors
+fn expand_attribute(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+    expand: impl FnOnce(TokenStream, TokenStream) -> Result<TokenStream, Diagnostic>,
+) -> proc_macro::TokenStream {
+    let input = TokenStream::from(input);
+    match expand(args.into(), input.clone()) {
+        Ok(expansion) => expansion.into(),
+        Err((span, message)) => {
+            let mut error = compile_error(&message, span);
+            error.extend(input);
+            error.into()
+        },
+    }
+}
+
+/// Runs the expansion of a function-like macro, its error is reported as a
+/// compile error pointing to the input
+fn expand_function(
+    input: proc_macro::TokenStream,
+    expand: impl FnOnce(TokenStream) -> Result<TokenStream, Diagnostic>,
+) -> proc_macro::TokenStream {
+    match expand(input.into()) {
+        Ok(expansion) => expansion.into(),
+        Err((span, message)) => compile_error(&message, span).into(),
+    }
+}
+
+/// Span of the first token of a macro's input, the invocation's one when
+/// it is empty
+fn input_span(input: &TokenStream) -> Span {
+    input
+        .clone()
+        .into_iter()
+        .next()
+        .map_or_else(Span::call_site, |token| token.span())
+}
+
+/// `compile_error!` with the message located at the span, it is valid in both
+/// item and expression positions
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    let mut tokens = vec![
+        TokenTree::from(Punct::new(':', Spacing::Joint)),
+        TokenTree::from(Punct::new(':', Spacing::Alone)),
+        TokenTree::from(Ident::new("core", span)),
+        TokenTree::from(Punct::new(':', Spacing::Joint)),
+        TokenTree::from(Punct::new(':', Spacing::Alone)),
+        TokenTree::from(Ident::new("compile_error", span)),
+        TokenTree::from(Punct::new('!', Spacing::Alone)),
+    ];
+    for token in &mut tokens {
+        token.set_span(span);
+    }
+    let mut arguments = Group::new(Brace, TokenStream::from(TokenTree::from(message)));
+    arguments.set_span(span);
+    tokens.push(TokenTree::from(arguments));
+    tokens.into_iter().collect()
+}
+
+/// Parses the code generated for the function, a failure is a bug of the
+/// macro, so the error at the function's name carries everything needed to
+/// report it
+fn parse_generated(code: &str, fn_name: &str, span: Span) -> Result<TokenStream, Diagnostic> {
+    code.parse()
+        .map_err(|err| (span, diagnostics::unparsable_expansion(fn_name, err, code)))
+}
+
+/// Parses the code generated by a function-like macro, e.g. when a path or an
+/// expression of its input does not survive the round trip through a string
+fn parse_macro_expansion(code: String, macro_name: &str) -> Result<TokenStream, Diagnostic> {
+    parse_generated(&code, macro_name, Span::call_site())
+}
+
+/// Builds explicit generic arguments `::<T, N>` from the parameters
+/// `<'a, T: Into<&'a str>, const N: usize>`, lifetimes are inferred
+fn turbofish(generics: Vec<TokenTree>) -> String {
+    let arguments = split_top_level(generics.into_iter().collect())
+        .into_iter()
+        .filter_map(|param| match param.as_slice() {
+            [TokenTree::Punct(punct), ..] if punct.as_char() == '\'' => None,
+            [TokenTree::Ident(keyword), TokenTree::Ident(name), ..] if keyword == "const" => Some(name.to_string()),
+            [TokenTree::Ident(name), ..] => Some(name.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if arguments.is_empty() {
+        String::new()
+    } else {
+        format!("::<{}>", arguments.join(", "))
+    }
+}
+
+/// Splits by commas outside of generic arguments `<K, V>`
+fn split_top_level(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut items = vec![];
+    let mut item = vec![];
+    let mut depth = 0;
+    let mut is_arrow = false;
+
+    for token in stream {
+        if let TokenTree::Punct(punct) = &token {
+            match punct.as_char() {
+                ',' if depth == 0 => {
+                    items.push(std::mem::take(&mut item));
+                    continue;
+                },
+                '<' => depth += 1,
+                '>' if !is_arrow => depth -= 1,
+                _ => (),
+            }
+            is_arrow = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+        }
+        item.push(token);
+    }
+    items.push(item);
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Renames the function in the signature dropping the qualifier, e.g. for
+/// the non-`const` runtime entry point or the blocking variant of `async fn`
+fn rename_signature(signature: Vec<TokenTree>, fn_name: &str, new_name: &str, qualifier: &str) -> Vec<TokenTree> {
+    let new_name = Ident::new(new_name.trim(), Span::call_site());
+    let mut is_fn_found = false;
+    let mut is_renamed = false;
+    let mut result = vec![];
+    for token in signature {
+        match &token {
+            TokenTree::Ident(ident) if !is_fn_found && ident == qualifier => continue,
+            TokenTree::Ident(ident) if !is_fn_found && ident == "fn" => is_fn_found = true,
+            TokenTree::Ident(ident) if is_fn_found && !is_renamed && ident == fn_name => {
+                is_renamed = true;
+                result.push(TokenTree::from(new_name.clone()));
+                continue;
+            },
+            _ => (),
+        }
+        result.push(token);
+    }
+    result
+}
+
+/// Checks whether the function is marked with `#[cfg(test)]`
+fn is_test_only(input: &TokenStream) -> bool {
+    let test_gate = "cfg(test)".parse::<TokenStream>().unwrap().to_string();
+    input
+        .clone()
+        .into_iter()
+        .take_while(|token| !matches!(token, TokenTree::Ident(ident) if ident == "fn"))
+        .any(|token| {
+            matches!(&token, TokenTree::Group(group)
+                if group.delimiter() == Delimiter::Bracket && group.stream().to_string() == test_gate)
+        })
+}
+
+/// Inserts the statements at the beginning of the function's body
+fn with_body_prefix(item: TokenStream, statements: &str) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    match tokens.pop() {
+        Some(TokenTree::Group(body)) if body.delimiter() == Delimiter::Brace => {
+            let statements: TokenStream = statements.parse().unwrap();
+            let body = Group::new(Delimiter::Brace, statements.into_iter().chain(body.stream()).collect());
+            tokens.push(TokenTree::from(body));
+        },
+        Some(token) => tokens.push(token),
+        None => (),
+    }
+    tokens.into_iter().collect()
+}
+
+/// Removes attributes with the name (with arguments too) preceding `fn`,
+/// e.g. `#[inline]` or `#[doc = ".."]`, including conditional ones
+/// `#[cfg_attr(feature = "x", inline)]`
+fn strip_attributes(tokens: Vec<TokenTree>, name: &str) -> Vec<TokenTree> {
+    let mut is_fn_found = false;
+    let mut result: Vec<TokenTree> = vec![];
+    for token in tokens {
+        let token = match token {
+            TokenTree::Ident(ident) => {
+                is_fn_found = is_fn_found || ident == "fn";
+                TokenTree::from(ident)
+            },
+            TokenTree::Group(group)
+                if !is_fn_found
+                    && group.delimiter() == Delimiter::Bracket
+                    && result.last().is_some_and(|last| is_punct(last, '#')) =>
+            {
+                match strip_attribute(group.stream(), name) {
+                    Some(stream) => {
+                        let mut stripped = Group::new(Delimiter::Bracket, stream);
+                        stripped.set_span(group.span());
+                        TokenTree::from(stripped)
+                    },
+                    None => {
+                        result.pop();
+                        continue;
+                    },
+                }
+            },
+            token => token,
+        };
+        result.push(token);
+    }
+    result
+}
+
+/// Returns the content of the attribute without the one with the name, `None`
+/// when nothing is left. Conditional attributes keep the other ones, e.g.
+/// `cfg_attr(test, inline, must_use)` => `cfg_attr(test, must_use)`
+fn strip_attribute(content: TokenStream, name: &str) -> Option<TokenStream> {
+    let tokens: Vec<TokenTree> = content.clone().into_iter().collect();
+    match &tokens[..] {
+        [TokenTree::Ident(ident), ..] if ident == name => None,
+        [TokenTree::Ident(ident), TokenTree::Group(args)] if ident == "cfg_attr" && args.delimiter() == Parenthesis => {
+            let mut items = split_top_level(args.stream()).into_iter();
+            let predicate = items.next().unwrap_or_default();
+            let attributes: Vec<String> = items
+                .map(|item| item.into_iter().collect::<TokenStream>().to_string())
+                .collect();
+            let kept: Vec<String> = attributes
+                .iter()
+                .filter_map(|attribute| strip_attribute(attribute.parse().unwrap(), name))
+                .map(|attribute| attribute.to_string())
+                .collect();
+            if kept.is_empty() {
+                return None;
+            }
+            if kept == attributes {
+                return Some(content);
+            }
+            let args = format!(
+                "({}, {})",
+                predicate.into_iter().collect::<TokenStream>(),
+                kept.join(", ")
+            );
+            Some(format!("{} {}", ident, args).parse().unwrap())
+        },
+        _ => Some(content),
+    }
+}
+
+/// Checks for the attribute with the name, including conditional ones
+fn has_attribute(group: &Group, name: &str) -> bool {
+    group.delimiter() == Delimiter::Bracket
+        && strip_attribute(group.stream(), name)
+            .is_none_or(|stripped| stripped.to_string() != group.stream().to_string())
+}
+
+/// Checks whether the group is the body of the attribute, e.g.
+/// `[inline(always)]`
+fn is_attribute(group: &Group, name: &str) -> bool {
+    group.delimiter() == Delimiter::Bracket
+        && matches!(group.stream().into_iter().next(), Some(TokenTree::Ident(ident)) if ident == name)
+}
+
+/// Embeds `covers::canary::CANARY` next to the dispatch to mocks
+fn canary() -> &'static str {
+    if cfg!(feature = "canary") {
+        "#[used] static CANARY: [u8; ::covers::canary::CANARY.len()] = *::covers::canary::CANARY;"
+    } else {
+        ""
+    }
+}
+
+/// Registers the function in `covers::runtime::report` the first time its
+/// `TRACKER` static is used with `features = ["report"]`, placed next to it
+fn report(fn_name: &str, mock: &str) -> String {
+    if !cfg!(feature = "report") {
+        return String::new();
+    }
+    format!(
+        r#"
+        static REPORT: ::std::sync::Once = ::std::sync::Once::new();
+        REPORT.call_once(|| {{
+            ::covers::runtime::register(concat!(module_path!(), "::", {fn_name:?}), {mock:?}, &TRACKER);
+        }});
+        "#,
+        fn_name = fn_name,
+        mock = mock
+    )
+}
+
+/// Appends a section describing the mocks to the docs of the wrapper, so
+/// rustdoc and IDE hovers of debug builds show the test seam
+fn with_wiring_doc(item: TokenStream, arms: &[Arm]) -> TokenStream {
+    let doc = format!(
+        "#[cfg_attr({gate}, doc = \"\")] #[cfg_attr({gate}, doc = {doc:?})]",
+        gate = ACTIVE_GATE,
+        doc = format!(" Mocked by `{}` under `cfg({})`.", arms[0].mock, arms[0].gate)
+    );
+
+    // after `#[cfg]`s and the docs of the function, before other attributes
+    let tokens: Vec<TokenTree> = item.into_iter().collect();
+    let (mut index, mut position) = (0, 0);
+    while let [hash, TokenTree::Group(group), ..] = &tokens[index..] {
+        if !is_punct(hash, '#') || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        index += 2;
+        if is_attribute(group, "cfg") || has_attribute(group, "doc") {
+            position = index;
+        }
+    }
+
+    let doc: TokenStream = doc.parse().unwrap();
+    tokens[..position]
+        .iter()
+        .cloned()
+        .chain(doc)
+        .chain(tokens[position..].iter().cloned())
+        .collect()
+}
+
+/// Predicate of `#[cfg]` when any of the arms is active, i.e. the mocks are
+/// dispatched at all
+fn mock_gate(arms: &[Arm]) -> String {
+    let mut gates: Vec<&str> = vec![];
+    for arm in arms {
+        if !gates.contains(&arm.gate.as_str()) {
+            gates.push(&arm.gate);
+        }
+    }
+    match gates.as_slice() {
+        [gate] => gate.to_string(),
+        _ => format!("any({})", gates.join(", ")),
+    }
+}
+
+/// Coercions of the mocks dispatched under their gates, the shadowed ones are
+/// never called
+fn signature_checks(arms: &[Arm]) -> String {
+    let mut gates: Vec<&str> = vec![];
+    let mut checks = String::new();
+    for arm in arms {
+        if !gates.contains(&arm.gate.as_str()) && !arm.check.is_empty() {
+            checks.push_str(&format!("#[cfg({})] {}", arm.gate, arm.check));
+        }
+        gates.push(&arm.gate);
+    }
+    checks
+}
+
+/// Builds the cascade of statements where the first active arm wins,
+/// arms are ordered by `priority` (higher first) and then by attributes order
+fn dispatch(arms: &[Arm], is_nested: bool, statement: impl Fn(&Arm) -> String) -> String {
+    let mut gates: Vec<&str> = vec![];
+    let mut dispatch = String::new();
+    for arm in arms {
+        // the arm is shadowed by the previous one with the same gate
+        if gates.contains(&arm.gate.as_str()) {
+            continue;
+        }
+        if gates.is_empty() && is_nested && arm.gate == mock_gate(arms) {
+            // already inside `#[cfg(test)]` block
+            dispatch.push_str(&statement(arm));
+        } else if gates.is_empty() {
+            dispatch.push_str(&format!("#[cfg({})] {}", arm.gate, statement(arm)));
+        } else {
+            dispatch.push_str(&format!(
+                "#[cfg(all({}, not(any({}))))] {}",
+                arm.gate,
+                gates.join(", "),
+                statement(arm)
+            ));
+        }
+        gates.push(&arm.gate);
+    }
+    dispatch
+}
+
+/// Extracts arguments of the `#[mocked]` attributes stacked on the function
+fn split_stacked_attributes(input: TokenStream) -> Result<(Vec<Params>, TokenStream), Diagnostic> {
+    let mut stacked = vec![];
+    let mut result = vec![];
+
+    let mut iter = input.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        if is_punct(&token, '#') {
+            if let Some(TokenTree::Group(group)) = iter.peek() {
+                if let Some(args) = parse_mocked_attribute(group) {
+                    stacked.push(parse_params(args)?);
+                    iter.next();
+                    continue;
+                }
+            }
+        }
+        let is_fn = matches!(&token, TokenTree::Ident(ident) if ident == "fn");
+        result.push(token);
+        if is_fn {
+            break;
+        }
+    }
+    result.extend(iter);
+
+    Ok((stacked, result.into_iter().collect()))
+}
+
+/// Returns arguments of `[mocked(...)]` or `[covers::mocked(...)]`
+/// Finds `#[mocked(..)]` among the attributes of the item, returns the index
+/// of its `#` and the arguments
+fn find_mocked_attribute(item: &[TokenTree]) -> Option<(usize, TokenStream)> {
+    item.windows(2).enumerate().find_map(|(index, pair)| match pair {
+        [hash, TokenTree::Group(group)] if is_punct(hash, '#') => {
+            parse_mocked_attribute(group).map(|args| (index, args))
+        },
+        _ => None,
+    })
+}
+
+fn parse_mocked_attribute(group: &Group) -> Option<TokenStream> {
+    if group.delimiter() != Delimiter::Bracket {
+        return None;
+    }
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+    let path: String = tokens
+        .iter()
+        .take_while(|token| !matches!(token, TokenTree::Group(_)))
+        .map(|token| token.to_string())
+        .collect();
+    let is_mocked = path == "mocked" || path == "covers::mocked" || path == "::covers::mocked";
+    if !is_mocked {
+        return None;
+    }
+    match tokens.last() {
+        Some(TokenTree::Group(args)) if args.delimiter() == Parenthesis => Some(args.stream()),
+        Some(TokenTree::Group(_)) => None,
+        _ => Some(TokenStream::new()),
+    }
+}
+
+fn split_leading_items(input: TokenStream) -> (TokenStream, TokenStream) {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+
+    let mut index = 0;
+    let mut fn_start = 0;
+    while index < tokens.len() {
+        match &tokens[index] {
+            // attributes and visibility belong to the following item
+            TokenTree::Punct(punct) if punct.as_char() == '#' => index += 2,
+            TokenTree::Ident(ident) if ident == "pub" => {
+                index += 1;
+                if let Some(TokenTree::Group(group)) = tokens.get(index) {
+                    if group.delimiter() == Parenthesis {
+                        index += 1;
+                    }
+                }
+            },
+            TokenTree::Ident(ident)
+                if ident == "static" || (ident == "const" && !is_fn_qualifier(tokens.get(index + 1))) =>
+            {
+                while index < tokens.len() && !is_punct(&tokens[index], ';') {
+                    index += 1;
+                }
+                index += 1;
+                fn_start = index;
+            },
+            _ => break,
+        }
+    }
+
+    let fn_start = fn_start.min(tokens.len());
+    let function = tokens[fn_start..].iter().cloned().collect();
+    let leading_items = tokens.into_iter().take(fn_start).collect();
+    (leading_items, function)
+}
+
+/// Splits a stream of function items by their bodies - top-level `{}` groups
+/// after `fn` keyword
+fn split_fn_items(input: TokenStream) -> Result<Vec<(String, TokenStream)>, Diagnostic> {
+    let mut items = vec![];
+
+    let mut item = vec![];
+    let mut fn_name = None;
+    let mut is_fn_found = false;
+
+    for token in input {
+        match &token {
+            TokenTree::Ident(ident) if !is_fn_found && ident == "fn" => is_fn_found = true,
+            TokenTree::Ident(ident) if is_fn_found && fn_name.is_none() => fn_name = Some(ident.to_string()),
+            TokenTree::Group(group) if group.delimiter() == Brace => {
+                if let Some(fn_name) = fn_name.take() {
+                    item.push(token);
+                    items.push((fn_name, item.drain(..).collect()));
+                    is_fn_found = false;
+                    continue;
+                }
+            },
+            _ => (),
+        }
+        item.push(token);
+    }
+
+    match item.first() {
+        Some(token) => Err((
+            token.span(),
+            diagnostics::not_function_item(item.iter().cloned().collect::<TokenStream>()),
+        )),
+        None => Ok(items),
+    }
+}
+
+/// Splits items of an impl block or a trait definition by their ends,
+/// functions are returned with their names
+fn split_impl_items(input: TokenStream) -> Vec<(Option<String>, Vec<TokenTree>)> {
+    let mut items = vec![];
+
+    let mut item: Vec<TokenTree> = vec![];
+    let mut fn_name = None;
+    let mut iter = input.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        let is_bang = item.last().is_some_and(|last| is_punct(last, '!'));
+        let is_end = match &token {
+            // `fn(u8) -> u8` types of associated consts are not functions
+            TokenTree::Ident(ident) if fn_name.is_none() && ident == "fn" => {
+                if let Some(TokenTree::Ident(name)) = iter.peek() {
+                    fn_name = Some(name.to_string());
+                }
+                false
+            },
+            // the body of a function or a macro invocation, not the output `-> typestate! { Open }`
+            TokenTree::Group(group) => group.delimiter() == Brace && fn_name.is_some() != is_bang,
+            // `;` of array types `[u8; 4]` is nested in a group
+            TokenTree::Punct(punct) => punct.as_char() == ';',
+            _ => false,
+        };
+        item.push(token);
+        if is_end {
+            items.push((fn_name.take(), std::mem::take(&mut item)));
+        }
+    }
+    if !item.is_empty() {
+        items.push((fn_name, item));
+    }
+    items
+}
+
+fn is_fn_qualifier(token: Option<&TokenTree>) -> bool {
+    match token {
+        Some(TokenTree::Ident(ident)) => ident == "fn" || ident == "unsafe" || ident == "extern" || ident == "async",
+        _ => false,
+    }
+}
+
+fn is_punct(token: &TokenTree, char: char) -> bool {
+    matches!(token, TokenTree::Punct(punct) if punct.as_char() == char)
+}
+
+fn parse_params(args: TokenStream) -> Result<Params, Diagnostic> {
+    let mut response = Params::default();
+
+    for (index, param) in split_params(args).into_iter().enumerate() {
+        let is_option = matches!(
+            param.get(1),
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '=' && punct.spacing() == Spacing::Alone
+        );
+        // flags are shortcuts for `flag = true`
+        if let [TokenTree::Ident(flag)] = &param[..] {
+            if index > 0 && FLAGS.contains(&flag.to_string().as_str()) {
+                response.options.insert(flag.to_string(), "true".to_string());
+                response.spans.insert(flag.to_string(), flag.span());
+                continue;
+            }
+        }
+        if !is_option {
+            // reference could be omitted in favor of the manifest
+            ensure!(index == 0, param[0].span(), diagnostics::extra_parameter());
+            response.reference_span = Some(param[0].span());
+            response.reference = param.into_iter().collect::<TokenStream>().to_string();
+            continue;
+        }
+        let name = param[0].to_string().to_lowercase();
+        let mut value = param[2..].iter().cloned().collect::<TokenStream>().to_string();
+        // paths of restricted visibilities, e.g. `original_vis = pub(in crate::Db)`,
+        // keep their case
+        if KEYWORD_OPTIONS.contains(&name.as_str()) && matches!(&param[2..], [TokenTree::Ident(_)]) {
+            value = value.to_lowercase();
+        }
+        response.spans.insert(name.clone(), param[0].span());
+        response.options.insert(name, value);
+    }
+    Ok(response)
+}
+
+/// Splits by top-level commas, a closure value runs until the next `key = ..`
+/// or a flag, so commas of its parameters `|a, b|` and `|` operators of its
+/// body, e.g. `map_original = |r| r | 1`, are kept
+fn split_params(args: TokenStream) -> Vec<Vec<TokenTree>> {
+    let tokens: Vec<TokenTree> = args.into_iter().collect();
+    let mut params = vec![];
+    let mut param = vec![];
+    let mut is_closure = false;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Punct(punct)
+                if punct.as_char() == ',' && (!is_closure || is_param_start(&tokens[index + 1..])) =>
+            {
+                params.push(std::mem::take(&mut param));
+                is_closure = false;
+                continue;
+            },
+            TokenTree::Punct(punct) if punct.as_char() == '|' && !is_closure => {
+                is_closure = match param.last() {
+                    None => true,
+                    Some(TokenTree::Ident(ident)) => ident == "move",
+                    Some(last) => is_punct(last, '='),
+                };
+            },
+            _ => (),
+        }
+        param.push(token.clone());
+    }
+    params.push(param);
+    params.retain(|param| !param.is_empty());
+    params
+}
+
+/// Checks whether the tokens after a comma start the next parameter: `key = ..`
+/// or a flag, trailing commas end the parameter as well
+fn is_param_start(tokens: &[TokenTree]) -> bool {
+    match tokens {
+        [] => true,
+        [TokenTree::Ident(_), TokenTree::Punct(eq), ..] if eq.as_char() == '=' => eq.spacing() == Spacing::Alone,
+        [TokenTree::Ident(flag), rest @ ..] => {
+            FLAGS.contains(&flag.to_string().as_str()) && rest.first().is_none_or(|comma| is_punct(comma, ','))
+        },
+        _ => false,
+    }
+}
+
+/// Returns arguments to forward, the group for the wrapper's signature and
+/// statements destructuring pattern arguments for bodies inlined into it
+fn parse_args(group: &Group) -> Result<(String, Group, String), Diagnostic> {
+    let mut args = vec![];
+    let mut params = vec![];
+    let mut destructuring = String::new();
+
+    // commas of generic arguments `HashMap<K, V>` are not groups
+    for vec in split_top_level(group.stream()) {
+        let (arg, param, pattern) = parse_one_arg(&vec, args.len())?;
+        if !args.is_empty() {
+            params.push(TokenTree::from(Punct::new(',', Spacing::Alone)));
+        }
+        if let Some(pattern) = pattern {
+            destructuring.push_str(&format!("let {} = {};", pattern, arg));
+        }
+        args.push(arg);
+        params.extend(param);
+    }
+
+    let mut params = Group::new(Parenthesis, params.into_iter().collect());
+    params.set_span(group.span());
+    Ok((args.join(", "), params, destructuring))
+}
+
+/// Returns the argument to forward, the parameter of the wrapper and the
+/// pattern replaced with a fresh binding, if any
+fn parse_one_arg(
+    vec: &[TokenTree],
+    position: usize,
+) -> Result<(String, Vec<TokenTree>, Option<TokenStream>), Diagnostic> {
+    if is_receiver(vec) {
+        ensure!(
+            position == 0,
+            vec[0].span(),
+            diagnostics::receiver_not_first(vec.iter().cloned().collect::<TokenStream>())
+        );
+        return Ok(("self".to_string(), vec.to_vec(), None));
+    }
+
+    let (binding, ty) = vec.split_at(type_colon(vec).unwrap_or(vec.len()));
+    for token in binding {
+        ensure!(
+            !matches!(token, TokenTree::Ident(ident) if ident == "self"),
+            token.span(),
+            diagnostics::unsupported_receiver(vec.iter().cloned().collect::<TokenStream>())
+        );
+    }
+
+    // anonymous parameters of 2015 edition (`fn f(u8)`) or a missing binding (`:
+    // u8`)
+    let first = match binding.first() {
+        Some(first) if !ty.is_empty() => first,
+        _ => {
+            let span = vec.first().map_or_else(Span::call_site, TokenTree::span);
+            return Err((
+                span,
+                diagnostics::unsupported_parameter(vec.iter().cloned().collect::<TokenStream>()),
+            ));
+        },
+    };
+
+    Ok(match binding {
+        [TokenTree::Ident(ident)] if ident != "_" => (ident.to_string(), vec.to_vec(), None),
+        // the wrapper keeps `mut` of the binding, only the inlined body could use it
+        [TokenTree::Ident(modifier), TokenTree::Ident(ident)] if modifier == "mut" => {
+            let allow: TokenStream = "#[allow(unused_mut)]".parse().unwrap();
+            (
+                ident.to_string(),
+                allow.into_iter().chain(vec.iter().cloned()).collect(),
+                None,
+            )
+        },
+        // wildcards and patterns could not be forwarded, so the wrapper gets a fresh binding instead
+        _ => {
+            let fresh = Ident::new(&naming::fresh_arg(position), first.span());
+            let param = std::iter::once(TokenTree::from(fresh.clone()))
+                .chain(ty.iter().cloned())
+                .collect();
+            let pattern = match binding {
+                [TokenTree::Ident(ident)] if ident == "_" => None,
+                _ => Some(binding.iter().cloned().collect()),
+            };
+            (fresh.to_string(), param, pattern)
+        },
+    })
+}
+
+/// Replaces `-> impl Trait` output with `-> Box<dyn Trait>` (`Pin<Box<dyn
+/// Future>>` for futures), returns it with the path boxing returned values
+fn boxed_output(tail: &[TokenTree]) -> Option<(Vec<TokenTree>, &'static str)> {
+    let end = tail
+        .iter()
+        .position(|token| matches!(token, TokenTree::Ident(ident) if ident == "where"))
+        .unwrap_or(tail.len());
+    let bounds = match tail {
+        [minus, greater, TokenTree::Ident(ident), ..]
+            if is_punct(minus, '-') && is_punct(greater, '>') && ident == "impl" =>
+        {
+            &tail[3..end]
+        },
+        _ => return None,
+    };
+    // the trait is the last segment of the first bound's path
+    let is_future = bounds
+        .iter()
+        .take_while(|token| !is_punct(token, '<') && !is_punct(token, '+'))
+        .last()
+        .is_some_and(|token| matches!(token, TokenTree::Ident(ident) if ident == "Future"));
+    let bounds = bounds.iter().cloned().collect::<TokenStream>();
+    let (output, boxing) = if is_future {
+        (
+            format!("::std::pin::Pin<::std::boxed::Box<dyn {}>>", bounds),
+            "::std::boxed::Box::pin",
+        )
+    } else {
+        (format!("::std::boxed::Box<dyn {}>", bounds), "::std::boxed::Box::new")
+    };
+    let output: TokenStream = output.parse().unwrap();
+    let tail = tail[..2]
+        .iter()
+        .cloned()
+        .chain(output)
+        .chain(tail[end..].iter().cloned())
+        .collect();
+    Some((tail, boxing))
+}
+
+/// Checks for `Self` in the tokens, nested ones too
+fn mentions_self(tokens: TokenStream) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "Self",
+        TokenTree::Group(group) => mentions_self(group.stream()),
+        _ => false,
+    })
+}
+
+/// The same as [`mentions_self`] skipping items nested into the body, e.g.
+/// `impl` blocks or structs, which have `Self` of their own
+fn body_mentions_self(body: TokenStream) -> bool {
+    let mut is_item = false;
+    for token in body {
+        match token {
+            TokenTree::Ident(ident)
+                if ident == "impl"
+                    || ident == "trait"
+                    || ident == "struct"
+                    || ident == "enum"
+                    || ident == "union"
+                    || ident == "fn" =>
+            {
+                is_item = true
+            },
+            TokenTree::Group(group) if is_item && group.delimiter() == Brace => is_item = false,
+            TokenTree::Punct(punct) if is_item && punct.as_char() == ';' => is_item = false,
+            _ if is_item => (),
+            TokenTree::Ident(ident) if ident == "Self" => return true,
+            TokenTree::Group(group) if body_mentions_self(group.stream()) => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Checks for `impl Trait` in types of the parameters, nested ones too
+fn has_impl_trait(params: TokenStream) -> bool {
+    params.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "impl",
+        TokenTree::Group(group) => has_impl_trait(group.stream()),
+        _ => false,
+    })
+}
+
+/// Checks for references without a lifetime and `'_` in types, nested ones too
+fn has_elided_lifetime(tokens: TokenStream) -> bool {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    tokens.iter().enumerate().any(|(index, token)| match token {
+        TokenTree::Punct(punct) if punct.as_char() == '&' => {
+            !matches!(tokens.get(index + 1), Some(next) if is_punct(next, '\'') || is_punct(next, '&'))
+        },
+        TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+            matches!(tokens.get(index + 1), Some(TokenTree::Ident(ident)) if ident == "_")
+        },
+        TokenTree::Group(group) => has_elided_lifetime(group.stream()),
+        _ => false,
+    })
+}
+
+/// Position of the colon followed by the type of the parameter, paths in
+/// patterns (`geo::Point(x, y): geo::Point`) have `::`
+fn type_colon(param: &[TokenTree]) -> Option<usize> {
+    let mut index = 0;
+    while index < param.len() {
+        match &param[index] {
+            TokenTree::Punct(punct) if punct.as_char() == ':' && punct.spacing() == Spacing::Joint => index += 1,
+            TokenTree::Punct(punct) if punct.as_char() == ':' => return Some(index),
+            _ => (),
+        }
+        index += 1;
+    }
+    None
+}
+
+/// Checks for `self`, `mut self`, `&self`, `&'a self`, `&mut self`, `&'a mut
+/// self`, `self: Type`, `mut self: Type`
+fn is_receiver(vec: &[TokenTree]) -> bool {
+    let mut iter = vec.iter().peekable();
+    if iter.next_if(|token| is_punct(token, '&')).is_some() && iter.next_if(|token| is_punct(token, '\'')).is_some() {
+        iter.next();
+    }
+    iter.next_if(|token| matches!(token, TokenTree::Ident(ident) if ident == "mut"));
+
+    match (iter.next(), iter.next()) {
+        (Some(TokenTree::Ident(ident)), None) => ident == "self",
+        (Some(TokenTree::Ident(ident)), Some(next)) => ident == "self" && is_punct(next, ':'),
+        _ => false,
+    }
+}
+
+#[allow(clippy::clone_on_copy)]
+fn cmp(current: &Stage, expected: Stage) -> i8 {
+    (current.clone() as i8) - (expected as i8)
+}
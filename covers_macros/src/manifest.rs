@@ -0,0 +1,190 @@
+//! Central mock wiring read from a manifest file.
+//!
+//! The path to the manifest is taken from `COVERS_MANIFEST` environment
+//! variable at expansion time. Relative paths are resolved against
+//! `CARGO_MANIFEST_DIR` of the crate being compiled.
+//!
+//! Only a small subset of TOML is supported - `[mocks]` table with strings:
+//! ```toml
+//! # path to the function from the crate root = "fully-qualified reference to mock"
+//! [mocks]
+//! foo = "mock_foo"
+//! "net::bar" = "net::mocks::bar"
+//! "net::Client::baz" = "Client::mock_baz"
+//! ```
+//!
+//! Modules are derived from the source files, e.g. `net::http` for
+//! `src/net/http.rs`, functions of inline `mod` blocks belong to the module of
+//! the file. Methods are listed with the name of the type when the impl block
+//! is marked with `#[mocked]`, otherwise they are listed as functions of the
+//! module.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::diagnostics;
+
+pub const MANIFEST_ENV: &str = "COVERS_MANIFEST";
+
+const MOCKS_TABLE: &str = "mocks";
+
+pub struct Entry {
+    /// Fully-qualified reference to a mock function
+    pub reference: String,
+    /// Absolute path to the manifest, used to track its changes
+    pub path: String,
+}
+
+/// Path of the function from the crate root the function is listed with
+pub fn key(module: &str, self_type: Option<&str>, fn_name: &str) -> String {
+    let name = match self_type {
+        Some(self_type) => format!("{}::{}", self_type, fn_name),
+        None => fn_name.to_string(),
+    };
+    if module.is_empty() {
+        name
+    } else {
+        format!("{}::{}", module, name)
+    }
+}
+
+/// Module of the source file relative to `src` directory, e.g. `net::http`
+/// for `net/http.rs` or `net/http/mod.rs`, binaries in `bin` are crate roots
+pub fn module_of(file: &Path) -> String {
+    let mut segments = file
+        .with_extension("")
+        .iter()
+        .map(|segment| segment.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    if segments.first().is_some_and(|segment| segment == "bin") {
+        segments.drain(..segments.len().min(2));
+    }
+    if segments
+        .last()
+        .is_some_and(|segment| ["mod", "lib", "main"].contains(&segment.as_str()))
+    {
+        segments.pop();
+    }
+    segments.join("::")
+}
+
+/// Looks up a mock for the function by its path.
+///
+/// Returns `None` when `COVERS_MANIFEST` is not set or the function is not
+/// listed, the message of the error when the manifest could not be read.
+pub fn lookup(key: &str) -> Result<Option<Entry>, String> {
+    let path = match crate::env_path(MANIFEST_ENV) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let content = std::fs::read_to_string(&path).map_err(|err| diagnostics::manifest_unreadable(&path, err))?;
+
+    Ok(parse(&content)
+        .map_err(|err| diagnostics::manifest_invalid(&path, err))?
+        .remove(key)
+        .map(|reference| Entry {
+            reference,
+            path: path.to_string_lossy().into_owned(),
+        }))
+}
+
+fn parse(content: &str) -> Result<HashMap<String, String>, String> {
+    let mut mocks = HashMap::new();
+    let mut table = String::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(format!("line {}: unclosed table header", number + 1));
+            }
+            table = line[1..line.len() - 1].trim().to_string();
+            continue;
+        }
+        if table != MOCKS_TABLE {
+            continue;
+        }
+
+        let mut entry = line.splitn(2, '=');
+        let key = entry.next().map(str::trim).map(unquote).unwrap_or_default();
+        let value = entry
+            .next()
+            .map(str::trim)
+            .filter(|value| is_quoted(value))
+            .map(unquote);
+        match value {
+            Some(value) if !key.is_empty() && !value.is_empty() => {
+                let key = key.strip_prefix("crate::").unwrap_or(key);
+                mocks.insert(key.to_string(), value.to_string());
+            },
+            _ => return Err(format!("line {}: expected `function = \"path::to::mock\"`", number + 1)),
+        }
+    }
+    Ok(mocks)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, char) in line.char_indices() {
+        match char {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn is_quoted(value: &str) -> bool {
+    value.len() >= 2 && value.starts_with('"') && value.ends_with('"')
+}
+
+fn unquote(value: &str) -> &str {
+    if is_quoted(value) {
+        value[1..value.len() - 1].trim()
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_of() {
+        assert_eq!(module_of(Path::new("lib.rs")), "");
+        assert_eq!(module_of(Path::new("main.rs")), "");
+        assert_eq!(module_of(Path::new("net.rs")), "net");
+        assert_eq!(module_of(Path::new("net/http.rs")), "net::http");
+        assert_eq!(module_of(Path::new("net/http/mod.rs")), "net::http");
+        assert_eq!(module_of(Path::new("bin/tool.rs")), "");
+        assert_eq!(module_of(Path::new("bin/tool/main.rs")), "");
+        assert_eq!(module_of(Path::new("bin/tool/args.rs")), "args");
+    }
+
+    #[test]
+    fn test_same_names() {
+        let mocks = parse(
+            r#"
+            [mocks]
+            load = "mock_load"
+            "crate::db::load" = "db::mock_load"
+            "net::load" = "net::mock_load"
+            "net::Client::load" = "Client::mock_load"
+            "#,
+        )
+        .unwrap();
+        let find = |module: &str, self_type: Option<&str>| mocks.get(&key(module, self_type, "load")).cloned();
+        assert_eq!(find("", None).unwrap(), "mock_load");
+        assert_eq!(find("db", None).unwrap(), "db::mock_load");
+        assert_eq!(find("net", None).unwrap(), "net::mock_load");
+        assert_eq!(find("net", Some("Client")).unwrap(), "Client::mock_load");
+        // neither the function of the module nor the one of the crate root
+        assert_eq!(find("net", Some("Server")), None);
+        assert_eq!(find("cache", None), None);
+    }
+}
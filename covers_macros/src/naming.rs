@@ -0,0 +1,144 @@
+//! Names of the items generated for mocked functions.
+//!
+//! Every identifier derived from the name of a mocked function is built here,
+//! so `#[mocked]` and the macros referring to the function (e.g.
+//! `with_original!` or `swap_mock!`) agree on it. The originals follow the
+//! policy selected by features: `foo` => `_foo` (default), `__foo` (`__`) or
+//! `_orig_foo` (`_orig_`). Hidden items use reserved `__covers_` prefixes
+//! regardless of the policy.
+//!
+//! A module-based policy (the original as `__covers::foo`) is not provided:
+//! methods and trait impl items could not be moved out of their impl blocks,
+//! and `#[mocked]` on a single function could not declare a module shared by
+//! its siblings, so such names would differ between functions and methods.
+
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+
+use crate::{Diagnostic, diagnostics};
+
+#[cfg(all(feature = "__", feature = "_orig_"))]
+panic!("only single prefix feature could be provided: '__' or '_orig_'. Note: '_' is default value");
+
+#[cfg(all(not(feature = "__"), not(feature = "_orig_")))]
+pub const ORIGINAL_FUNC_PREFIX: &str = "_";
+#[cfg(feature = "__")]
+pub const ORIGINAL_FUNC_PREFIX: &str = "__";
+#[cfg(feature = "_orig_")]
+pub const ORIGINAL_FUNC_PREFIX: &str = "_orig_";
+
+/// Name of the hidden mock in the module of `#[mocked(auto)]`.
+pub const AUTO_RETURN: &str = "__covers_auto";
+
+/// Items generated per mocked function, named after it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Item {
+    /// The original implementation
+    Original,
+    /// The switch used by `with_original!`
+    Switch,
+    /// The tracker of calls used by `calls!`, `expect!` or
+    /// `assert_not_concurrent!`
+    Tracker,
+    /// The per-method registry used by `mock_instance!`
+    Instance,
+    /// The per-method counter used by `instance_calls!`
+    InstanceCalls,
+    /// The entry point used by `scoped_mock!`
+    Scoped,
+    /// The entry point used by `returns!`
+    ScopedReturn,
+    /// The pointer used by `swap_mock!`
+    Swap,
+    /// The attribute mock passed as `next` to runtime overrides
+    Next,
+    /// The diagnostics used by `explain!`
+    Explain,
+}
+
+/// Names of the items accepted by `mangle!`
+const NAMES: &[(&str, Item)] = &[
+    ("original", Item::Original),
+    ("switch", Item::Switch),
+    ("tracker", Item::Tracker),
+    ("instance", Item::Instance),
+    ("instance_calls", Item::InstanceCalls),
+    ("scoped", Item::Scoped),
+    ("scoped_return", Item::ScopedReturn),
+    ("swap", Item::Swap),
+    ("next", Item::Next),
+    ("explain", Item::Explain),
+];
+
+impl Item {
+    /// Looks the item up by its name in `mangle!`, e.g. `swap`
+    pub fn from_name(name: &str) -> Option<Item> {
+        NAMES
+            .iter()
+            .find(|(item_name, _)| *item_name == name)
+            .map(|(_, item)| *item)
+    }
+
+    /// All the names accepted by [`Item::from_name`]
+    pub fn names() -> Vec<&'static str> {
+        NAMES.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Item::Original => ORIGINAL_FUNC_PREFIX,
+            Item::Switch => "__covers_original_",
+            Item::Tracker => "__covers_tracker_",
+            Item::Instance => "__covers_instance_",
+            Item::InstanceCalls => "__covers_instance_calls_",
+            Item::Scoped => "__covers_scoped_",
+            Item::ScopedReturn => "__covers_scoped_return_",
+            Item::Swap => "__covers_swap_",
+            Item::Next => "__covers_next_",
+            Item::Explain => "__covers_explain_",
+        }
+    }
+
+    /// `foo` => `__covers_swap_foo`
+    pub fn name(self, fn_name: &str) -> String {
+        format!("{}{}", self.prefix(), fn_name)
+    }
+
+    /// The same as [`Item::name`] keeping the span of the function's name
+    pub fn ident(self, ident: &Ident) -> TokenTree {
+        TokenTree::from(Ident::new(&self.name(&ident.to_string()), ident.span()))
+    }
+
+    /// `a::foo` => `a::__covers_swap_foo`, the error is reported at the last
+    /// token of the path when it is not a name
+    pub fn path(self, mut path: Vec<TokenTree>) -> Result<TokenStream, Diagnostic> {
+        match path.pop() {
+            Some(TokenTree::Ident(ident)) => path.push(self.ident(&ident)),
+            other => {
+                let span = other.map_or_else(Span::call_site, |token| token.span());
+                return Err((span, diagnostics::not_function_reference()));
+            },
+        }
+        Ok(path.into_iter().collect())
+    }
+}
+
+/// The module configuring `#[mocked(auto)]` mocks: `foo` => `foo_mock`
+pub fn auto_module(fn_name: &str) -> String {
+    format!("{}_mock", fn_name)
+}
+
+/// The struct generated by `#[automock]`: `Storage` => `MockStorage`
+pub fn automock(trait_name: &str) -> String {
+    format!("Mock{}", trait_name)
+}
+
+/// The method registering behavior of the mock: `read` => `on_read`
+pub fn behavior_setter(fn_name: &str) -> String {
+    format!("on_{}", fn_name)
+}
+
+/// Fresh binding of a wildcard or pattern argument: `_` or `(w, h)` =>
+/// `__covers_arg0`
+pub fn fresh_arg(position: usize) -> String {
+    format!("__covers_arg{}", position)
+}
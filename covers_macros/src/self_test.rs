@@ -0,0 +1,2198 @@
+//! String-based entry points to the expansion.
+//!
+//! Unlike `proc_macro` ones, they can be called outside of a procedural macro,
+//! so the crate's own unit tests (and fuzz harnesses) can check the parser
+//! directly instead of relying exclusively on the integration tests crate.
+
+#[cfg(feature = "self-test")]
+use proc_macro2::Literal;
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Renders the expansion, errors are reported by a panic with their message
+/// as the compiler shows them
+fn expanded(result: Result<TokenStream, crate::Diagnostic>) -> String {
+    match result {
+        Ok(expansion) => expansion.to_string(),
+        Err((_, message)) => panic!("{}", message),
+    }
+}
+
+/// Expands `#[mocked(args)]` applied to `input`, errors are returned with
+/// their message instead of a panic, e.g. for fuzz harnesses
+#[cfg(feature = "fuzzing")]
+pub fn try_mocked(args: &str, input: &str) -> Result<String, String> {
+    let (args, input) = match (args.parse(), input.parse()) {
+        (Ok(args), Ok(input)) => (args, input),
+        _ => return Err(format!("Failed to tokenize `{}` or `{}`", args, input)),
+    };
+    match crate::expand_mocked(args, input) {
+        Ok(expansion) => Ok(expansion.to_string()),
+        Err((_, message)) => Err(message),
+    }
+}
+
+/// Expands `#[mocked(args)]` applied to `input`
+pub fn mocked(args: &str, input: &str) -> String {
+    expanded(crate::expand_mocked(parse(args), parse(input)))
+}
+
+/// Expands `#[mocked_impl(args)]` applied to `input`
+pub fn mocked_impl(args: &str, input: &str) -> String {
+    expanded(crate::expand_mocked_impl_list(
+        parse(args),
+        parse(input),
+        crate::MOCKED_IMPL_USAGE,
+    ))
+}
+
+/// Expands `#[mock_trait(args)]` applied to `input`
+pub fn mock_trait(args: &str, input: &str) -> String {
+    expanded(crate::expand_mock_trait(parse(args), parse(input)))
+}
+
+/// Expands `#[automock(args)]` applied to `input`
+pub fn automock(args: &str, input: &str) -> String {
+    expanded(crate::expand_automock(parse(args), parse(input)))
+}
+
+/// Expands `#[mock(args)]` applied to `input`
+pub fn mock(args: &str, input: &str) -> String {
+    expanded(crate::expand_mock(parse(args), parse(input)))
+}
+
+/// Expands `with_original!(input)`
+pub fn with_original(input: &str) -> String {
+    expanded(crate::expand_with_original(parse(input)))
+}
+
+/// Expands `macro_name(args) input` and returns the result as a string literal
+/// in the [`canonical`] layout.
+///
+/// Backs `covers::__expansion!` available with `features = ["self-test"]`.
+#[cfg(feature = "self-test")]
+pub fn expansion(input: TokenStream) -> TokenStream {
+    let mut iter = input.into_iter();
+    let (name, args) = match (iter.next(), iter.next()) {
+        (Some(TokenTree::Ident(name)), Some(TokenTree::Group(args))) => (name.to_string(), args.stream().to_string()),
+        _ => panic!("Usage: `__expansion!(mocked(args) fn ...)`, other function-like macros are supported too"),
+    };
+    let input = iter.collect::<TokenStream>().to_string();
+
+    let expansion = match name.as_str() {
+        "mocked" => mocked(&args, &input),
+        "mocked_impl" => mocked_impl(&args, &input),
+        "automock" => automock(&args, &input),
+        "mock_trait" => mock_trait(&args, &input),
+        "mock" => mock(&args, &input),
+        "with_original" => with_original(&args),
+        "mocked_group" => mocked_group(&args),
+        "mocked_trait" => mocked_trait(&args),
+        "assert_not_concurrent" => assert_not_concurrent(&args),
+        "explain" => explain(&args),
+        "original_of" => original_of(&args),
+        "mangle" => mangle(&args),
+        "mock_instance" => mock_instance(&args),
+        "instance_calls" => instance_calls(&args),
+        "calls" => calls(&args),
+        "captured" => captured(&args),
+        "expect" => expect(&args),
+        "expectation_set" => expectation_set(&args),
+        "tag_next_call" => tag_next_call(&args),
+        "tagged" => tagged(&args),
+        "returned" => returned(&args),
+        "scoped_mock" => scoped_mock(&args),
+        "swap_mock" => swap_mock(&args),
+        "assert_same_signature" => assert_same_signature(&args),
+        "returns" => returns(&args),
+        "returns_ok" => returns_ok(&args),
+        "returns_err" => returns_err(&args),
+        "returns_some" => returns_some(&args),
+        "returns_none" => returns_none(&args),
+        "when_mocking" => when_mocking(&args),
+        _ => panic!("Unknown macro to expand: `{}`", name),
+    };
+    TokenTree::from(Literal::string(&canonical(parse(expansion)))).into()
+}
+
+/// Expands `assert_not_concurrent!(input)`
+pub fn assert_not_concurrent(input: &str) -> String {
+    expanded(crate::expand_assert_not_concurrent(parse(input)))
+}
+
+/// Expands `explain!(input)`
+pub fn explain(input: &str) -> String {
+    expanded(crate::expand_explain(parse(input)))
+}
+
+/// Expands `mangle!(input)`
+pub fn mangle(input: &str) -> String {
+    expanded(crate::expand_mangle(parse(input)))
+}
+
+/// Expands `original_of!(input)`
+pub fn original_of(input: &str) -> String {
+    expanded(crate::expand_original_of(parse(input)))
+}
+
+/// Expands `mock_instance!(input)`
+pub fn mock_instance(input: &str) -> String {
+    expanded(crate::expand_mock_instance(parse(input)))
+}
+
+/// Expands `instance_calls!(input)`
+pub fn instance_calls(input: &str) -> String {
+    expanded(crate::expand_instance_calls(parse(input)))
+}
+
+/// Expands `calls!(input)`
+pub fn calls(input: &str) -> String {
+    expanded(crate::expand_calls(parse(input)))
+}
+
+/// Expands `captured!(input)`
+pub fn captured(input: &str) -> String {
+    expanded(crate::expand_captured(parse(input)))
+}
+
+/// Expands `expect!(input)`
+pub fn expect(input: &str) -> String {
+    expanded(crate::expand_expect(parse(input)))
+}
+
+/// Expands `expectation_set!(input)`
+pub fn expectation_set(input: &str) -> String {
+    expanded(crate::expand_expectation_set(parse(input)))
+}
+
+/// Expands `tag_next_call!(input)`
+pub fn tag_next_call(input: &str) -> String {
+    expanded(crate::expand_tag(parse(input), "tag_next_call"))
+}
+
+/// Expands `tagged!(input)`
+pub fn tagged(input: &str) -> String {
+    expanded(crate::expand_tag(parse(input), "tagged"))
+}
+
+/// Expands `returned!(input)`
+pub fn returned(input: &str) -> String {
+    expanded(crate::expand_returned(parse(input)))
+}
+
+/// Expands `scoped_mock!(input)`
+pub fn scoped_mock(input: &str) -> String {
+    expanded(crate::expand_scoped_mock(parse(input)))
+}
+
+/// Expands `returns!(input)`
+pub fn returns(input: &str) -> String {
+    expanded(crate::expand_returns(parse(input), crate::RETURNS_USAGE, ""))
+}
+
+/// Expands `returns_ok!(input)`
+pub fn returns_ok(input: &str) -> String {
+    expanded(crate::expand_returns(parse(input), crate::RETURNS_OK_USAGE, "Ok"))
+}
+
+/// Expands `returns_err!(input)`
+pub fn returns_err(input: &str) -> String {
+    expanded(crate::expand_returns(parse(input), crate::RETURNS_ERR_USAGE, "Err"))
+}
+
+/// Expands `returns_some!(input)`
+pub fn returns_some(input: &str) -> String {
+    expanded(crate::expand_returns(parse(input), crate::RETURNS_SOME_USAGE, "Some"))
+}
+
+/// Expands `returns_none!(input)`
+pub fn returns_none(input: &str) -> String {
+    expanded(crate::expand_returns_none(parse(input)))
+}
+
+/// Expands `swap_mock!(input)`
+pub fn swap_mock(input: &str) -> String {
+    expanded(crate::expand_swap_mock(parse(input)))
+}
+
+/// Expands `assert_same_signature!(input)`
+pub fn assert_same_signature(input: &str) -> String {
+    expanded(crate::expand_assert_same_signature(parse(input)))
+}
+
+/// Expands `when_mocking!(input)`
+pub fn when_mocking(input: &str) -> String {
+    expanded(crate::expand_when_mocking(parse(input)))
+}
+
+/// Expands `mocked_group!(input)`
+pub fn mocked_group(input: &str) -> String {
+    expanded(crate::expand_mocked_group(parse(input)))
+}
+
+/// Expands `mocked_trait!(input)`
+pub fn mocked_trait(input: &str) -> String {
+    expanded(crate::expand_mocked_trait(parse(input)))
+}
+
+/// Prints tokens separated by a single space, except for operators of two or
+/// more characters (`::`, `->`) and lifetimes (`'a`), and groups as `( .. )`,
+/// `[ .. ]`, `{ .. }`.
+///
+/// Unlike `TokenStream::to_string` the layout does not depend on the version
+/// of the compiler or `proc_macro2`, so snapshots of expansions are stable.
+pub fn canonical(stream: TokenStream) -> String {
+    let mut result = String::new();
+    write_canonical(&mut result, stream);
+    result.trim_end().to_string()
+}
+
+/// Pairs of joint punctuation printed without a space in between
+const OPERATORS: &[&str] = &[
+    "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>",
+    "..", ".=",
+];
+
+fn write_canonical(result: &mut String, stream: TokenStream) {
+    let mut tokens = stream.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("( ", ") "),
+                    Delimiter::Bracket => ("[ ", "] "),
+                    Delimiter::Brace => ("{ ", "} "),
+                    Delimiter::None => ("", ""),
+                };
+                result.push_str(open);
+                write_canonical(result, group.stream());
+                result.push_str(close);
+            },
+            TokenTree::Punct(punct) => {
+                result.push(punct.as_char());
+                let is_glued = punct.as_char() == '\''
+                    || punct.spacing() == Spacing::Joint
+                        && matches!(tokens.peek(), Some(TokenTree::Punct(next))
+                            if OPERATORS.contains(&format!("{}{}", punct.as_char(), next.as_char()).as_str()));
+                if !is_glued {
+                    result.push(' ');
+                }
+            },
+            TokenTree::Ident(ident) => {
+                result.push_str(&ident.to_string());
+                result.push(' ');
+            },
+            TokenTree::Literal(literal) => {
+                result.push_str(&literal.to_string());
+                result.push(' ');
+            },
+        }
+    }
+}
+
+fn parse<S: AsRef<str>>(source: S) -> TokenStream {
+    let source = source.as_ref();
+    source
+        .parse()
+        .unwrap_or_else(|err| panic!("Failed to tokenize `{}`: {:?}", source, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naming::ORIGINAL_FUNC_PREFIX;
+    use crate::{ACTIVE_GATE, MOCK_GATE};
+    use proc_macro2::Span;
+
+    fn assert_expansion(actual: String, expected: &str) {
+        assert_eq!(actual, parse(expected).to_string());
+    }
+
+    /// Docs of the wrapper describing the mock
+    fn wiring_doc(mock: &str) -> String {
+        format!(
+            "#[cfg_attr(any(debug_assertions, test), doc = \"\")] #[cfg_attr(any(debug_assertions, test), doc = \" \
+             Mocked by `{}` under `cfg(test)`.\")]",
+            mock
+        )
+    }
+
+    #[test]
+    fn test_mocked() {
+        let expected = format!(
+            r#"
+            #[cfg(any(debug_assertions, test))]
+            pub fn {prefix}foo(name: &str) -> String {{
+                format!("Foo = {{}}", name)
+            }}
+
+            #[cfg(any(debug_assertions, test))]
+            {wiring_doc}
+            fn foo(name: &str) -> String {{
+                {env_tracking}
+                #[cfg(test)]
+                let _: fn(&str) -> String = module::mock_foo;
+                #[cfg(test)]
+                let _execution = __covers_tracker_foo().enter(__covers_explain_foo);
+                #[cfg(test)]
+                {{
+                    {canary}
+                    if !__covers_original_foo().with(|original| original.get())
+                        && !::covers::runtime::is_disabled(module_path!(), ::std::option::Option::None, "foo")
+                    {{
+                        __covers_tracker_foo().hit();
+                        return module::mock_foo(name);
+                    }}
+                }}
+                return {prefix}foo(name);
+            }}
+
+            #[cfg(test)]
+            #[doc(hidden)]
+            pub fn __covers_original_foo() -> &'static ::std::thread::LocalKey<::std::cell::Cell<bool>> {{
+                ::std::thread_local!(static ORIGINAL: ::std::cell::Cell<bool> = ::std::cell::Cell::new(false));
+                &ORIGINAL
+            }}
+
+            #[cfg(test)]
+            #[doc(hidden)]
+            pub fn __covers_tracker_foo() -> &'static ::covers::runtime::Tracker {{
+                static TRACKER: ::covers::runtime::Tracker = ::covers::runtime::Tracker::new();
+                {report}
+                &TRACKER
+            }}
+
+            #[cfg(any(debug_assertions, test))]
+            #[doc(hidden)]
+            #[allow(unreachable_code)]
+            pub fn __covers_explain_foo() -> ::covers::runtime::Explanation {{
+                let mock = (|| {{
+                    #[cfg(test)]
+                    return "module::mock_foo";
+                    ""
+                }})();
+                let original_override = false;
+                #[cfg(test)]
+                let original_override = __covers_original_foo().with(|original| original.get())
+                    || ::covers::runtime::is_disabled(module_path!(), ::std::option::Option::None, "foo");
+                let calls = 0;
+                #[cfg(test)]
+                let calls = __covers_tracker_foo().calls();
+                ::covers::runtime::Explanation {{
+                    function: "foo",
+                    gate: if cfg!(test) {{
+                        "test"
+                    }} else if cfg!(debug_assertions) {{
+                        "debug_assertions"
+                    }} else {{
+                        "none"
+                    }},
+                    mock,
+                    original: "{prefix}foo",
+                    prefix: "{prefix}",
+                    original_override,
+                    calls,
+                }}
+            }}
+
+            #[cfg(not(any(debug_assertions, test)))]
+            fn foo(name: &str) -> String {{
+                format!("Foo = {{}}", name)
+            }}
+            "#,
+            prefix = ORIGINAL_FUNC_PREFIX,
+            canary = crate::canary(),
+            env_tracking = crate::env_tracking(),
+            report = crate::report("foo", "module::mock_foo"),
+            wiring_doc = wiring_doc("module::mock_foo")
+        );
+
+        let actual = mocked(
+            "module::mock_foo",
+            r#"fn foo(name: &str) -> String { format!("Foo = {}", name) }"#,
+        );
+        assert_expansion(actual, &expected);
+    }
+
+    #[test]
+    fn test_mocked_impl_scope() {
+        let actual = mocked("Struct::mock_baz, scope = impl", "fn baz(a: u8, b: u8) -> u8 { a + b }");
+        assert!(actual.contains(&parse("return Struct::mock_baz(a, b);").to_string()));
+        assert!(actual.contains(&parse(format!("return Self::{}baz(a, b);", ORIGINAL_FUNC_PREFIX)).to_string()));
+
+        let actual = mocked(
+            "module::yyy",
+            "fn xxx(&self, name: &str) -> String { name.to_string() }",
+        );
+        assert!(actual.contains(&parse("return module::yyy(self, name);").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_self_detection() {
+        let tracker = parse("Self::__covers_tracker_new()").to_string();
+        assert!(mocked("mock_new", "fn new(name: &str) -> Self { Self { name } }").contains(&tracker));
+        assert!(mocked("mock_new", "fn new(names: Vec<Self>) {}").contains(&tracker));
+        assert!(mocked("mock_new", "fn new() -> u8 { Self::DEFAULT }").contains(&tracker));
+
+        // nested items have `Self` of their own
+        let actual = mocked(
+            "mock_new",
+            "fn new() -> u8 { struct Node(Option<Box<Self>>); impl Node { } 0 }",
+        );
+        assert!(!actual.contains(&tracker));
+        assert!(!mocked("mock_new", "fn new() { impl A { fn a() -> Self { A } } }").contains(&tracker));
+    }
+
+    #[test]
+    fn test_mocked_self_return() {
+        // everything mentioning `Self` is generated into the impl block, the mock
+        // spells the concrete type
+        let actual = mocked("mocks::new", "pub fn new(name: &str) -> Self { Self { name } }");
+        assert!(actual.contains(&parse("let _: fn(&str) -> Self = mocks::new;").to_string()));
+        assert!(actual.contains(&parse("return mocks::new(name);").to_string()));
+        let fallthrough = format!("return Self::{}new(name);", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(fallthrough).to_string()));
+        assert!(!actual.contains("mod "));
+    }
+
+    #[test]
+    fn test_mocked_hrtb() {
+        let signature = "fn map<F: for<'a> Fn(&'a str) -> String, G>(f: F, g: G) -> String where G: for<'b> Fn(&'b u8)";
+        let actual = mocked("mock_map", &format!("{} {{ f(\"\") }}", signature));
+        assert!(actual.contains(&parse(signature).to_string()));
+        assert!(actual.contains(&parse("return mock_map::<F, G>(f, g);").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_where_clause() {
+        let actual = mocked(
+            "mock_save",
+            "pub fn save<T, E>(value: T) -> Result<String, E> where T: Debug + Send, E: Default { Ok(value) }",
+        );
+        let wrapper = "pub fn save<T, E>(value: T) -> Result<String, E> where T: Debug + Send, E: Default";
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        let original = format!(
+            "pub fn {}save<T, E>(value: T) -> Result<String, E> where T: Debug + Send, E: Default {{ Ok(value) }}",
+            ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(original).to_string()));
+        assert!(actual.contains(&parse("return mock_save::<T, E>(value);").to_string()));
+
+        let actual = mocked(
+            "mock_load, also_blocking = load_blocking",
+            "pub async fn load<T>(key: &str) -> T where T: From<String> { T::from(key.to_string()) }",
+        );
+        let blocking = "pub fn load_blocking<T>(key: &str) -> T where T: From<String>";
+        assert!(actual.contains(&parse(blocking).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_qualified_types() {
+        let signature = "fn first<'a, I: IntoIterator + 'a, const N: usize>(items: I, _: [u8; N]) -> Option<<I as \
+                         IntoIterator>::Item> where I::Item: Clone";
+        let actual = mocked("mock_first", &format!("{} {{ items.into_iter().next() }}", signature));
+        assert!(actual.contains(&parse(signature).to_string()));
+        assert!(actual.contains(&parse("return mock_first::<I, N>(items, __covers_arg1);").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_args_tuple() {
+        let actual = mocked("mock_foo, args = tuple", "fn foo(&self, a: u8, _: &str) -> u8 { a }");
+        assert!(actual.contains(&parse("return mock_foo((self, a, __covers_arg2,));").to_string()));
+        assert!(
+            actual.contains(
+                &parse(format!(
+                    "return Self::{}foo(self, a, __covers_arg2);",
+                    ORIGINAL_FUNC_PREFIX
+                ))
+                .to_string()
+            )
+        );
+
+        let actual = mocked("mock_foo, args = tuple", "fn foo() {}");
+        assert!(actual.contains(&parse("return mock_foo(());").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_per_instance() {
+        let actual = mocked(
+            "mock_get, per_instance = true",
+            "fn get(&mut self, map: Box<dyn Fn(u8) -> u8>, key: u8) -> Option<&String> where Self: Sized { None }",
+        );
+        assert!(
+            actual.contains(
+                &parse("if let Some(mock) = Self::__covers_instance_get(self, None) { return mock(self, map, key); }")
+                    .to_string()
+            )
+        );
+        let fn_type = "fn(&mut Self, Box<dyn Fn(u8) -> u8>, u8) -> Option<&String>";
+        let registry = format!(
+            "pub fn __covers_instance_get(instance: &Self, mock: Option<Option<{0}>>) -> Option<{0}>",
+            fn_type
+        );
+        assert!(actual.contains(&parse(registry).to_string()));
+        assert!(actual.contains(&parse("Self::__covers_instance_calls_get(self, true);").to_string()));
+        let counter = "pub fn __covers_instance_calls_get(instance: &Self, hit: bool) -> usize";
+        assert!(actual.contains(&parse(counter).to_string()));
+    }
+
+    #[test]
+    fn test_instance_calls() {
+        assert_expansion(
+            instance_calls("Client::get, &client"),
+            "Client::__covers_instance_calls_get(&client, false)",
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        assert_expansion(
+            calls("db::Client::write"),
+            "db::Client::__covers_tracker_write().thread_calls()",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `calls!(path::to::function)`")]
+    fn test_calls_without_path() {
+        calls("");
+    }
+
+    #[test]
+    fn test_mocked_capture() {
+        let actual = mocked("mock_send, capture", "fn send(&self, to: &str, (a, b): (u8, u8)) {}");
+        let capture = format!(
+            "#[cfg({})] \
+             Self::__covers_tracker_send().capture(<[::std::string::String]>::join(&[::std::format!(\"{{:?}}\", to), \
+             ::std::format!(\"{{:?}}\", __covers_arg2)], \", \"));",
+            MOCK_GATE
+        );
+        assert!(actual.contains(&parse(capture).to_string()));
+
+        let actual = mocked("mock_ping, capture", "fn ping() {}");
+        let capture = "__covers_tracker_ping().capture(<[::std::string::String]>::join(&[], \", \"));";
+        assert!(actual.contains(&parse(capture).to_string()));
+        assert!(!mocked("mock_ping", "fn ping() {}").contains("capture"));
+    }
+
+    #[test]
+    fn test_mocked_spy() {
+        let actual = mocked("spy", "async fn load(&self, id: u32) -> Option<User> { None }");
+        let spy = format!(
+            "return Self::__covers_tracker_load().spy(Self::{}load(self, id).await);",
+            ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(spy).to_string()));
+        assert!(actual.contains(&parse("Self::__covers_tracker_load().capture").to_string()));
+        assert!(actual.contains(&parse("return \"spy\";").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`spy` is not supported for `load` in a trait impl (`scope = trait`)")]
+    fn test_mocked_spy_trait_scope() {
+        mocked("spy, scope = trait", "fn load(&self) {}");
+    }
+
+    #[test]
+    fn test_returned() {
+        assert_expansion(returned("db::load"), "db::__covers_tracker_load().returned()");
+    }
+
+    #[test]
+    fn test_mocked_capture_projection() {
+        let actual = mocked("mock_send, capture = |to| to.len()", "fn send(&self, to: &str) {}");
+        let capture = format!(
+            "#[cfg({})] Self::__covers_tracker_send().capture(::covers::runtime::project(&to, |to| to.len()));",
+            MOCK_GATE
+        );
+        assert!(actual.contains(&parse(capture).to_string()));
+
+        let actual = mocked(
+            "spy, capture = Message::subject",
+            "fn send(to: &str, message: Message) {}",
+        );
+        let capture =
+            "__covers_tracker_send().capture(::covers::runtime::project((&to, &message,), Message::subject));";
+        assert!(actual.contains(&parse(capture).to_string()));
+    }
+
+    #[test]
+    fn test_captured() {
+        assert_expansion(captured("Mailer::send"), "Mailer::__covers_tracker_send().captured()");
+    }
+
+    #[test]
+    #[should_panic(expected = "`capture` is not supported for `send` in a trait impl (`scope = trait`)")]
+    fn test_mocked_capture_trait_scope() {
+        mocked("mock_send, capture, scope = trait", "fn send(&self) {}");
+    }
+
+    #[test]
+    fn test_expect() {
+        assert_expansion(
+            expect("db::Client::write"),
+            "::covers::runtime::expect(\"db::Client::write\", db::Client::__covers_tracker_write())",
+        );
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_expansion(
+            tag_next_call("db::save, \"after-retry\""),
+            "db::__covers_tracker_save().tag_next_call(\"after-retry\")",
+        );
+        assert_expansion(
+            tagged("Mailer::send, TAG"),
+            "Mailer::__covers_tracker_send().tagged(TAG)",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `tagged!(path::to::function, tag)`")]
+    fn test_tagged_without_tag() {
+        tagged("Mailer::send");
+    }
+
+    #[test]
+    fn test_expectation_set() {
+        assert_expansion(
+            expectation_set("pub(crate) happy_path { net::fetch.times(1); db::Client::save; }"),
+            &format!(
+                "#[cfg({})] pub(crate) fn happy_path() {{ ::covers::runtime::expect(\"net::fetch\", \
+                 net::__covers_tracker_fetch()).times(1); ::covers::runtime::expect(\"db::Client::save\", \
+                 db::Client::__covers_tracker_save()); }}",
+                MOCK_GATE
+            ),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `expectation_set! { name { path::to::function.times(1); .. } }`")]
+    fn test_expectation_set_without_semicolon() {
+        expectation_set("happy_path { net::fetch.times(1) }");
+    }
+
+    #[test]
+    fn test_mocked_per_instance_typed_receiver() {
+        let actual = mocked(
+            "mock_get, per_instance = true",
+            "fn get(self: Rc<Self>, key: u8) -> u8 { key }",
+        );
+        let dispatch = "if let Some(mock) = Self::__covers_instance_get(&*self, None) { return mock(self, key); }";
+        assert!(actual.contains(&parse(dispatch).to_string()));
+        assert!(actual.contains(&parse("Self::__covers_instance_calls_get(&*self, true);").to_string()));
+        let registry = "pub fn __covers_instance_get(instance: &Self, mock: Option<Option<fn(Rc<Self>, u8) -> u8>>)";
+        assert!(actual.contains(&parse(registry).to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "mocks require `&self`, `&mut self` or a pointer to `Self` receiver of `get`")]
+    fn test_mocked_per_instance_by_value() {
+        mocked("mock_get, per_instance = true", "fn get(self) {}");
+    }
+
+    #[test]
+    #[should_panic(expected = "mocks require `&self`, `&mut self` or a pointer to `Self` receiver of `get`")]
+    fn test_mocked_per_instance_typed_by_value() {
+        mocked("mock_get, per_instance = true", "fn get(mut self: Self) {}");
+    }
+
+    #[test]
+    fn test_mocked_scoped() {
+        let actual = mocked(
+            "mock_take, scoped = true",
+            "fn take(mut self, key: &str) -> Vec<u8> { vec![] }",
+        );
+        let fn_type = "fn(Self, &str) -> Vec<u8>";
+        assert!(
+            actual.contains(
+                &parse(format!(
+                    "if let Some(mock) = ::covers::runtime::scoped_mock::<{}>(Self::__covers_tracker_take()) {{ \
+                     return mock(self, key); }}",
+                    fn_type
+                ))
+                .to_string()
+            )
+        );
+        assert!(
+            actual.contains(
+                &parse(format!(
+                    "pub fn __covers_scoped_take(mock: {}) -> ::covers::runtime::ScopedMock",
+                    fn_type
+                ))
+                .to_string()
+            )
+        );
+        let stub = "if let Some(value) = ::covers::runtime::scoped_return::<Vec<u8>>(Self::__covers_tracker_take()) { \
+                    return value; }";
+        assert!(actual.contains(&parse(stub).to_string()));
+        let entry = "pub fn __covers_scoped_return_take(stub: ::std::boxed::Box<dyn Fn() -> Vec<u8>>,) -> \
+                     ::covers::runtime::ScopedMock";
+        assert!(actual.contains(&parse(entry).to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`scoped` mocks are not supported for generic or `async` function `load`")]
+    fn test_mocked_scoped_generic() {
+        mocked("mock_load, scoped = true", "fn load<T>() {}");
+    }
+
+    #[test]
+    fn test_scoped_mock() {
+        assert_expansion(
+            scoped_mock("net::Client::fetch, |_| None"),
+            "net::Client::__covers_scoped_fetch(|_| None)",
+        );
+    }
+
+    #[test]
+    fn test_mocked_hot_swap() {
+        let actual = mocked("mock_get, hot_swap = true", "fn get(&self, key: &str) -> u8 { 0 }");
+        assert!(actual.contains(
+            &parse("if let Some(mock) = Self::__covers_swap_get(None) { return mock(self, key); }").to_string()
+        ));
+        assert!(
+            actual.contains(
+                &parse(
+                    "pub fn __covers_swap_get(mock: Option<Option<fn(&Self, &str) -> u8>>) -> Option<fn(&Self, &str) \
+                     -> u8>"
+                )
+                .to_string()
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`hot_swap` mocks are not supported for generic or `async` function `load`")]
+    fn test_mocked_hot_swap_async() {
+        mocked("mock_load, hot_swap = true", "async fn load() {}");
+    }
+
+    #[test]
+    fn test_mocked_keep_in_release() {
+        let actual = mocked("mock_read, keep_in_release", "fn read(port: u16) -> u8 { 0 }");
+        let dispatch = "if let Some(mock) = __covers_swap_read(None) { return mock(port); }";
+        let release = format!(
+            "#[cfg(not(any(debug_assertions, test)))] fn read(port: u16) -> u8 {{ {{ {} }} 0 }}",
+            dispatch
+        );
+        assert!(actual.contains(&parse(&release).to_string()));
+        let wrapper = format!(
+            "#[cfg(not({}))] {{ {} }} return {}read(port);",
+            MOCK_GATE, dispatch, ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(&wrapper).to_string()));
+        let registry = "#[doc(hidden)] pub fn __covers_swap_read(mock: Option<Option<fn(u16) -> u8>>)";
+        assert!(actual.contains(&parse(registry).to_string()));
+        assert!(!actual.contains(&parse(format!("#[cfg({})] {}", MOCK_GATE, registry)).to_string()));
+
+        let actual = mocked("mock_read", "fn read(port: u16) -> u8 { 0 }");
+        assert!(!actual.contains("__covers_swap_read"));
+    }
+
+    #[test]
+    fn test_mocked_overrides_wrap() {
+        let actual = mocked(
+            "mock_get, scoped = true, hot_swap = true, overrides = wrap",
+            "#[inline] pub fn get(&self, key: u8) -> u8 { key }",
+        );
+        let next = format!(
+            "#[cfg({})] #[doc(hidden)] #[allow(unused_variables)] pub fn __covers_next_get(&self, key: u8) -> u8 {{ \
+             {{ return mock_get(self, key); }} }}",
+            MOCK_GATE
+        );
+        assert!(actual.contains(&parse(next).to_string()));
+        let fn_type = "fn(fn(&Self, u8) -> u8, &Self, u8) -> u8";
+        let scoped = format!(
+            "if let Some(mock) = ::covers::runtime::scoped_mock::<{}>(Self::__covers_tracker_get()) {{ return \
+             mock(Self::__covers_next_get, self, key); }}",
+            fn_type
+        );
+        assert!(actual.contains(&parse(scoped).to_string()));
+        let swap =
+            "if let Some(mock) = Self::__covers_swap_get(None) { return mock(Self::__covers_next_get, self, key); }";
+        assert!(actual.contains(&parse(swap).to_string()));
+        let registry = format!("pub fn __covers_swap_get(mock: Option<Option<{}>>)", fn_type);
+        assert!(actual.contains(&parse(registry).to_string()));
+
+        let actual = mocked("mock_now, scoped = true, overrides = wrap", "fn now() -> u64 { 0 }");
+        assert!(actual.contains(&parse("return mock(__covers_next_now);").to_string()));
+        assert!(actual.contains(&parse("scoped_mock::<fn(fn() -> u64) -> u64>").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`overrides = wrap` requires `per_instance`, `scoped` or `hot_swap` mocks of `now`")]
+    fn test_mocked_overrides_wrap_without_overrides() {
+        mocked("mock_now, overrides = wrap", "fn now() -> u64 { 0 }");
+    }
+
+    #[test]
+    #[should_panic(expected = "`scoped` could not be combined with `overrides = forbid` of `now`")]
+    fn test_mocked_overrides_forbid() {
+        mocked("mock_now, overrides = forbid", "fn now() -> u64 { 0 }");
+        mocked("mock_now, scoped = true, overrides = forbid", "fn now() -> u64 { 0 }");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown `overrides = merge`, expected `replace` (default), `wrap` or `forbid`")]
+    fn test_mocked_overrides_unknown() {
+        mocked("mock_now, overrides = merge", "fn now() -> u64 { 0 }");
+    }
+
+    #[test]
+    fn test_returns() {
+        assert_expansion(
+            returns("db::find, User::default()"),
+            "db::__covers_scoped_return_find(::std::boxed::Box::new({ let value = User::default(); move || \
+             (::std::clone::Clone::clone(&value)) }))",
+        );
+        assert_expansion(
+            returns_ok("net::fetch, vec![1]"),
+            "net::__covers_scoped_return_fetch(::std::boxed::Box::new({ let value = vec![1]; move || \
+             Ok(::std::clone::Clone::clone(&value)) }))",
+        );
+        assert_expansion(
+            returns_err("net::fetch, Error::Offline"),
+            "net::__covers_scoped_return_fetch(::std::boxed::Box::new({ let value = Error::Offline; move || \
+             Err(::std::clone::Clone::clone(&value)) }))",
+        );
+        assert_expansion(
+            returns_some("cache::get, 7"),
+            "cache::__covers_scoped_return_get(::std::boxed::Box::new({ let value = 7; move || \
+             Some(::std::clone::Clone::clone(&value)) }))",
+        );
+        assert_expansion(
+            returns_none("cache::get"),
+            "cache::__covers_scoped_return_get(::std::boxed::Box::new(|| None))",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `returns_ok!(path::to::function, value)`")]
+    fn test_returns_without_value() {
+        returns_ok("net::fetch");
+    }
+
+    #[test]
+    fn test_swap_mock() {
+        assert_expansion(
+            swap_mock("net::fetch, |_| None"),
+            "net::__covers_swap_fetch(Some(Some(|_| None)))",
+        );
+        assert_expansion(swap_mock("net::fetch, None"), "net::__covers_swap_fetch(Some(None))");
+    }
+
+    #[test]
+    fn test_assert_same_signature() {
+        assert_expansion(
+            assert_same_signature("db::load, Db::mock_load"),
+            r#"::covers::runtime::assert_same_signature(db::load, Db::mock_load, "db::load", "Db::mock_load")"#,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `assert_same_signature!(path::to::function, path::to::mock)`")]
+    fn test_assert_same_signature_without_mock() {
+        assert_same_signature("db::load");
+    }
+
+    #[test]
+    fn test_when_mocking() {
+        assert_expansion(
+            when_mocking("let fixture = load(); register(fixture);"),
+            "#[cfg(test)] ::covers::__when_mocking! { let fixture = load(); register(fixture); }",
+        );
+    }
+
+    #[test]
+    fn test_mocked_auto() {
+        let actual = mocked("auto", "pub(crate) fn profile(id: u32) -> Profile { Profile::new(id) }");
+        assert!(actual.contains(&parse("return profile_mock::__covers_auto();").to_string()));
+        assert!(actual.contains(&parse("pub(crate) mod profile_mock").to_string()));
+        assert!(actual.contains(&parse("pub fn return_value(value: Profile)").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`#[mocked(auto)]` is not supported for generic functions and methods, got `load`")]
+    fn test_mocked_auto_generic() {
+        mocked("auto", "fn load<T: Default>() -> T { T::default() }");
+    }
+
+    #[test]
+    fn test_mock_instance() {
+        assert_expansion(
+            mock_instance("Client::get, &client, |_, _| None"),
+            "{ Client::__covers_instance_get(&client, Some(Some(|_, _| None))); }",
+        );
+        assert_expansion(
+            mock_instance("Client::get, &client, None"),
+            "{ Client::__covers_instance_get(&client, Some(None)); }",
+        );
+    }
+
+    #[test]
+    fn test_mocked_attributes_order() {
+        let actual = mocked(
+            "mock_foo",
+            r#"#[inline] #[cfg(unix)] #[cfg(any(debug_assertions, test))] #[inline] #[cfg(feature = "a")] fn foo() {}"#,
+        );
+        let attributes = r#"#[cfg(any(debug_assertions, test))] #[cfg(feature = "a")] #[cfg(unix)] #[inline]"#;
+        assert!(actual.contains(&parse(format!("{} pub fn {}foo()", attributes, ORIGINAL_FUNC_PREFIX)).to_string()));
+        let wrapper = format!(
+            r#"#[cfg(any(debug_assertions, test))] #[cfg(feature = "a")] #[cfg(unix)] {} #[inline] fn foo()"#,
+            wiring_doc("mock_foo")
+        );
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert_eq!(
+            mocked("mock_foo", "#[cfg(unix)] #[cfg(windows)] fn foo() {}"),
+            mocked("mock_foo", "#[cfg(windows)] #[cfg(unix)] fn foo() {}")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "canary")]
+    fn test_mocked_canary() {
+        let actual = mocked("mock_foo", "fn foo() {}");
+        assert!(
+            actual.contains(
+                &parse("#[used] static CANARY: [u8; ::covers::canary::CANARY.len()] = *::covers::canary::CANARY;")
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_unparsable_expansion() {
+        let span = Span::call_site();
+        let (_, message) = crate::parse_generated("fn foo() { \"unterminated }", "foo", span).unwrap_err();
+        assert!(message.starts_with("Failed to parse the code generated for `foo`"));
+        assert!(message.ends_with("fn foo() { \"unterminated }"));
+    }
+
+    #[test]
+    fn test_unparsable_macro_input() {
+        // a lone quote is not re-lexed from the generated string
+        let quote = TokenTree::from(proc_macro2::Punct::new('\'', Spacing::Alone));
+        let input = vec![quote, TokenTree::from(proc_macro2::Ident::new("a", Span::call_site()))];
+        let (_, message) = crate::expand_calls(input.into_iter().collect()).unwrap_err();
+        assert!(message.starts_with("Failed to parse the code generated for `calls!`"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported parameter `: u8`, expected `pattern: Type`")]
+    fn test_mocked_parameter_without_binding() {
+        mocked("mock_foo", "fn foo(: u8) {}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported parameter `u8`, expected `pattern: Type`")]
+    fn test_automock_anonymous_parameter() {
+        automock("", "trait Storage { fn read(&self, u8) -> u8; }");
+    }
+
+    #[test]
+    fn test_mocked_impl_method_error() {
+        let input = parse("impl Client { #[mocked(mock_get, wrap = sometimes)] fn get(&self) -> u8 { 0 } }");
+        let (_, message) = crate::expand_mocked(TokenStream::new(), input).unwrap_err();
+        assert!(message.starts_with("Unknown `wrap = sometimes`"));
+    }
+
+    #[test]
+    fn test_canonical() {
+        assert_eq!(
+            canonical(parse("pub fn a<'a>(x :&'a str)->&'a str{x}")),
+            "pub fn a < 'a > ( x : & 'a str ) -> & 'a str { x }"
+        );
+        assert_eq!(
+            canonical(parse(r#"#[doc = "a b"] ::covers::f(1u8, 'c')..=x"#)),
+            r#"# [ doc = "a b" ] :: covers :: f ( 1u8 , 'c' ) ..= x"#
+        );
+    }
+
+    #[test]
+    fn test_mocked_generics() {
+        let actual = mocked(
+            "mock_find",
+            "pub fn find<T: Ord>(items: &[T]) -> Option<&T> { items.iter().max() }",
+        );
+        assert!(actual.contains(&parse("pub fn find<T: Ord>(items: &[T]) -> Option<&T>").to_string()));
+        assert!(actual.contains(&parse("return mock_find::<T>(items);").to_string()));
+        let original = format!("return {}find::<T>(items);", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(original).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_lifetimes() {
+        let signature = "fn pick<'a: 'b, 'b, T: AsRef<str> + 'a>(items: &'a [T], fallback: &'b str) -> &'b str";
+        let actual = mocked("mock_pick", &format!("{} {{ fallback }}", signature));
+        assert!(actual.contains(&parse(signature).to_string()));
+        assert!(actual.contains(&parse("return mock_pick::<T>(items, fallback);").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_gnarly_body() {
+        let body = r#"{
+            macro_rules! twice { ($e:expr) => {{ fn inner() {} $e * 2 }}; }
+            fn nested() -> impl Fn() -> u8 { || 1 }
+            let future = async { fn in_block() {} };
+            twice!(nested()())
+        }"#;
+        let actual = mocked("mock_foo", &format!("fn foo() -> u8 {}", body));
+        assert!(actual.contains(&parse("return mock_foo();").to_string()));
+        let original = format!("pub fn {}foo() -> u8 {}", ORIGINAL_FUNC_PREFIX, body);
+        assert!(actual.contains(&parse(original).to_string()));
+        // the original and the release copy only
+        assert_eq!(actual.matches("fn nested").count(), 2);
+    }
+
+    #[test]
+    fn test_mocked_macro_output() {
+        for (signature, call) in &[
+            ("fn open() -> typestate! { Door<Open> }", "return mock_open();"),
+            ("fn open() -> Door<{ OPEN }>", "return mock_open();"),
+            (
+                "fn open<T>() -> wrapped![T] where T: Default",
+                "return mock_open::<T>();",
+            ),
+        ] {
+            let actual = mocked("mock_open", &format!("{} {{ Door::new() }}", signature));
+            assert!(actual.contains(&parse(signature).to_string()));
+            assert!(actual.contains(&parse(call).to_string()));
+        }
+    }
+
+    #[test]
+    fn test_mocked_generics_with_receiver() {
+        let actual = mocked(
+            "mock_convert",
+            "fn convert<'a, T: From<&'a Self> + Into<Vec<u8>>, const N: usize>(&'a self) -> T { T::from(self) }",
+        );
+        assert!(actual.contains(&parse("return mock_convert::<T, N>(self);").to_string()));
+        assert!(
+            actual.contains(&parse(format!("return Self::{}convert::<T, N>(self);", ORIGINAL_FUNC_PREFIX)).to_string())
+        );
+    }
+
+    #[test]
+    fn test_mocked_anonymous_lifetimes() {
+        let signature = "pub fn rest(&mut self, parser: &Parser<'_>) -> Option<&'_ str>";
+        let actual = mocked("Parser::mock_rest", &format!("{} {{ None }}", signature));
+        assert!(actual.contains(&parse(signature).to_string()));
+        assert!(actual.contains(&parse("return Parser::mock_rest(self, parser);").to_string()));
+        let original = format!("return Self::{}rest(self, parser);", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(original).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_trait_scope() {
+        let actual = mocked("mock_size, scope = trait", "fn size(&self) -> usize { self.len() }");
+        assert!(actual.contains(&parse("return mock_size(self);").to_string()));
+        assert!(actual.contains(&parse("{ self.len() }").to_string()));
+        assert!(!actual.contains(&parse(format!("fn {}size", ORIGINAL_FUNC_PREFIX)).to_string()));
+        assert!(!actual.contains("__covers_"));
+    }
+
+    #[test]
+    #[should_panic(expected = "`per_instance` is not supported for `size` in a trait impl (`scope = trait`)")]
+    fn test_mocked_trait_scope_per_instance() {
+        mocked(
+            "mock_size, scope = trait, per_instance = true",
+            "fn size(&self) -> usize { 0 }",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported impl form")]
+    fn test_mocked_impl_block() {
+        mocked("mock_size", "impl<T: Io> Ext for T { fn size(&self) -> usize { 0 } }");
+    }
+
+    #[test]
+    fn test_mocked_trait_impl() {
+        let actual = mocked(
+            "",
+            "#[cfg(unix)] impl<T: Copy> Greeter<T> for English<T> where T: Debug { type Name = &'static str; \
+             #[mocked(English::mock_greet)] fn greet(&self, name: T) -> String { format!(\"{:?}\", name) } fn \
+             shout(&self) {} }",
+        );
+        let blocks: Vec<String> = parse(actual.clone())
+            .into_iter()
+            .filter_map(|token| match token {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => Some(group.stream().to_string()),
+                _ => None,
+            })
+            .collect();
+        let trait_impl = "#[cfg(unix)] impl<T: Copy> Greeter<T> for English<T> where T: Debug";
+        assert!(actual.starts_with(&parse(trait_impl).to_string()));
+        assert!(blocks[0].starts_with(&parse("type Name = &'static str;").to_string()));
+        assert!(blocks[0].contains(&parse(format!("#[cfg({})]", ACTIVE_GATE)).to_string()));
+        assert!(blocks[0].contains(&parse("fn greet(&self, name: T) -> String").to_string()));
+        assert!(blocks[0].contains(&parse("return English::mock_greet(self, name);").to_string()));
+        assert!(blocks[0].ends_with(&parse("fn shout(&self) {}").to_string()));
+
+        let inherent = "#[cfg(unix)] impl<T: Copy> English<T> where T: Debug";
+        assert!(actual.contains(&parse(inherent).to_string()));
+        let original = format!("pub fn {}greet(&self, name: T) -> String", ORIGINAL_FUNC_PREFIX);
+        assert!(blocks[1].contains(&parse(original).to_string()));
+        assert!(!blocks[0].contains(&parse(format!("fn {}greet", ORIGINAL_FUNC_PREFIX)).to_string()));
+
+        let actual = mocked(
+            "",
+            "impl Farewell for English { #[mocked(mock_wave)] async fn wave(&self) {} }",
+        );
+        assert!(actual.contains(&parse(format!("pub async fn {}wave(&self) {{}}", ORIGINAL_FUNC_PREFIX)).to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "could not move the originals of `impl Trait for & English` to an inherent impl")]
+    fn test_mocked_trait_impl_of_reference() {
+        mocked(
+            "",
+            "impl Greeter for &English { #[mocked(mock_greet)] fn greet(&self) {} }",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`#[mocked]` could not move the originals of `impl Trait for T` to an inherent impl")]
+    fn test_mocked_trait_impl_blanket() {
+        mocked(
+            "",
+            "impl<T: Io> Greeter for T { #[mocked(mock_greet)] fn greet(&self) {} }",
+        );
+    }
+
+    #[test]
+    fn test_mocked_impl() {
+        let actual = mocked_impl(
+            "baz => Struct::mock_baz, xxx => module::yyy",
+            "impl Struct { fn baz(name: &str) -> String { name.to_string() } fn mock_baz(name: &str) -> String { \
+             name.to_string() } #[mocked(scoped = true)] fn xxx(self) {} }",
+        );
+        assert!(actual.starts_with(&parse("impl Struct").to_string()));
+        assert!(!actual.contains("} impl"));
+        assert!(actual.contains(&parse("return Struct::mock_baz(name);").to_string()));
+        let original = format!("pub fn {}baz(name: &str) -> String", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(original).to_string()));
+        assert!(actual.contains(&parse("fn mock_baz(name: &str) -> String { name.to_string() }").to_string()));
+        assert!(actual.contains(&parse("return module::yyy(self);").to_string()));
+        assert!(actual.contains("__covers_scoped_xxx"));
+        assert!(!actual.contains("mocked"));
+
+        let actual = mocked_impl(
+            "greet => English::mock_greet",
+            "impl Greeter for English { fn greet(&self) {} }",
+        );
+        assert!(actual.contains("} impl English {"));
+        assert!(actual.contains(&parse(format!("pub fn {}greet(&self) {{}}", ORIGINAL_FUNC_PREFIX)).to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Method `greet` listed with its mock is not found in the impl block")]
+    fn test_mocked_impl_unknown_method() {
+        mocked_impl("greet => mock_greet", "impl English { fn shout(&self) {} }");
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `#[mocked_impl(method => path::to::mock, ..)] impl Type { .. }`")]
+    fn test_mocked_impl_without_pairs() {
+        mocked_impl("mock_greet", "impl English { fn greet(&self) {} }");
+    }
+
+    #[test]
+    fn test_mock_trait() {
+        let input = "impl Store for Client { fn get(&self) -> u8 { 0 } fn put(&self) {} }";
+        let actual = mock_trait("get => mocks::get", input);
+        assert!(actual.starts_with(&parse("impl Store for Client").to_string()));
+        assert!(actual.contains(&parse("return mocks::get(self);").to_string()));
+        assert!(actual.contains("} impl Client {"));
+        let original = format!("pub fn {}get(&self) -> u8 {{ 0 }}", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(original).to_string()));
+        assert!(actual.contains(&parse("fn put(&self) {}").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `#[mock_trait(method => path::to::mock, ..)] impl Trait for Type { .. }`")]
+    fn test_mock_trait_inherent_impl() {
+        mock_trait("get => mocks::get", "impl Client { fn get(&self) -> u8 { 0 } }");
+    }
+
+    #[test]
+    fn test_automock() {
+        let actual = automock(
+            "",
+            "pub(crate) trait Storage: Send { const LIMIT: usize = 8; fn read(&self, key: &str) -> Option<String>; fn \
+             write(&mut self, (key, value): (&str, u8)) { self.flush(key) } async fn flush(&self, key: &str); fn \
+             open() -> Self where Self: Sized { unimplemented!() } }",
+        );
+        assert!(actual.starts_with(&parse("pub(crate) trait Storage: Send").to_string()));
+        let fields = "pub(crate) struct MockStorage { read: ::std::option::Option<::std::boxed::Box<dyn Fn(&str) -> \
+                      Option<String>>>, write: ::std::option::Option<::std::boxed::Box<dyn Fn((&str, u8)) -> ()>>, \
+                      flush: ::std::option::Option<::std::boxed::Box<dyn Fn(&str) -> ()>>, }";
+        assert!(actual.contains(&parse(format!("#[cfg({})] #[derive(Default)]", MOCK_GATE)).to_string()));
+        assert!(actual.contains(&parse(fields).to_string()));
+        let setter = "pub fn on_read(mut self, behavior: impl Fn(&str) -> Option<String> + 'static) -> Self";
+        assert!(actual.contains(&parse(setter).to_string()));
+        assert!(actual.contains(&parse("impl Storage for MockStorage").to_string()));
+        assert!(actual.contains(&parse("return behavior(key);").to_string()));
+        let fallback = "::covers::runtime::missing_behavior(\"MockStorage::read\", \"on_read\")";
+        assert!(actual.contains(&parse(fallback).to_string()));
+        let default = "fn write(&mut self, __covers_arg1: (&str, u8))";
+        assert!(actual.contains(&parse(default).to_string()));
+        assert!(actual.contains(&parse("let (key, value) = __covers_arg1; { self.flush(key) }").to_string()));
+        assert!(actual.contains(&parse("async fn flush(&self, key: &str)").to_string()));
+        assert_eq!(actual.matches("fn open").count(), 2);
+        assert!(!actual.contains("on_open"));
+    }
+
+    #[test]
+    #[should_panic(expected = "`#[automock]` is expected on trait definitions without generic parameters")]
+    fn test_automock_generic_trait() {
+        automock("", "trait Storage<K> { fn read(&self, key: K) -> String; }");
+    }
+
+    #[test]
+    #[should_panic(expected = "`#[automock]` could not mock `read`: generic methods, `impl Trait` types")]
+    fn test_automock_generic_method() {
+        automock("", "trait Storage { fn read<K>(&self, key: K) -> String; }");
+    }
+
+    #[test]
+    #[should_panic(expected = "`#[automock]` could not implement `type Key ;`")]
+    fn test_automock_associated_type() {
+        automock(
+            "",
+            "trait Storage { type Key; fn read(&self, key: Self::Key) -> String; }",
+        );
+    }
+
+    #[test]
+    fn test_mocked_tuples_and_nested_generics() {
+        let signature = "fn split(s: &str, limits: HashMap<(u8, u8), Vec<Option<usize>>>, (a, b): (u8, u8)) -> \
+                         (Vec<String>, HashMap<String, usize>)";
+        let actual = mocked("mock_split", &format!("{} {{ unimplemented!() }}", signature));
+        let original = signature.replace("fn split", &format!("fn {}split", ORIGINAL_FUNC_PREFIX));
+        assert!(actual.contains(&parse(original).to_string()));
+        let wrapper = signature.replace("(a, b): (u8, u8)", "__covers_arg2: (u8, u8)");
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return mock_split(s, limits, __covers_arg2);").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_impl_trait_args() {
+        let wrapper = "fn handle(reader: impl Read, sink: &mut (impl Write + ?Sized), f: Box<impl Fn() -> u8>) -> u8";
+        let actual = mocked("mock_handle", &format!("{} {{ 0 }}", wrapper));
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return mock_handle(reader, sink, f);").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_box_return() {
+        let input = "fn stream(limit: u8) -> impl Iterator<Item = u8> + Send where u8: Copy { 0..limit }";
+        let actual = mocked("mock_stream, box_return", input);
+        let wrapper = "fn stream(limit: u8) -> ::std::boxed::Box<dyn Iterator<Item = u8> + Send> where u8: Copy";
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return ::std::boxed::Box::new(mock_stream(limit));").to_string()));
+        let fallthrough = format!("return ::std::boxed::Box::new({}stream(limit));", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(fallthrough).to_string()));
+        let original = format!(
+            "fn {}stream(limit: u8) -> impl Iterator<Item = u8> + Send",
+            ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(original).to_string()));
+
+        let input = "fn fetch() -> impl std::future::Future<Output = u8> { async { 0 } }";
+        let actual = mocked("mock_fetch, box_return", input);
+        let wrapper = "fn fetch() -> ::std::pin::Pin<::std::boxed::Box<dyn std::future::Future<Output = u8>>>";
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return ::std::boxed::Box::pin(mock_fetch());").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`box_return` requires `-> impl Trait` output of `stream`")]
+    fn test_mocked_box_return_without_impl() {
+        mocked("mock_stream, box_return", "fn stream() -> Vec<u8> { vec![] }");
+    }
+
+    #[test]
+    #[should_panic(expected = "`box_return` could not be combined with the body of `stream` inlined into the wrapper")]
+    fn test_mocked_box_return_inlined() {
+        mocked(
+            "mock_stream, box_return, unused_original = strip",
+            "fn stream() -> impl Iterator<Item = u8> { 0..1 }",
+        );
+    }
+
+    #[test]
+    fn test_mocked_mut_trait_objects() {
+        let actual = mocked(
+            "mock_log, scoped = true",
+            "fn log(sink: &mut dyn Write, line: &str) -> usize { 0 }",
+        );
+        assert!(actual.contains(&parse("fn log(sink: &mut dyn Write, line: &str) -> usize").to_string()));
+        assert!(actual.contains(&parse("return mock_log(sink, line);").to_string()));
+        assert!(actual.contains(&parse("scoped_mock::<fn(&mut dyn Write, &str) -> usize>").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`scoped` mocks are not supported for generic or `async` function `handle`")]
+    fn test_mocked_impl_trait_args_scoped() {
+        mocked(
+            "mock_handle, scoped = true",
+            "fn handle(sink: &mut (impl Write + ?Sized)) {}",
+        );
+    }
+
+    #[test]
+    fn test_mocked_mut_args() {
+        let input = "fn run(mut cfg: Config, limit: u8) -> u8 { cfg.limit = limit; 0 }";
+        let actual = mocked("mock_run, hot_swap = true", input);
+        let wrapper = "fn run(#[allow(unused_mut)] mut cfg: Config, limit: u8) -> u8";
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return mock_run(cfg, limit);").to_string()));
+        let original = format!("fn {}run(mut cfg: Config, limit: u8)", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(original).to_string()));
+        assert!(actual.contains(&parse("Option<fn(Config, u8) -> u8>").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_pattern_args() {
+        let input =
+            "fn area((w, h): (u32, u32), &Size { depth, .. }: &Size, geo::Point(x, _): geo::Point) -> u32 { w * h }";
+        let actual = mocked("mock_area", input);
+        let wrapper = "fn area(__covers_arg0: (u32, u32), __covers_arg1: &Size, __covers_arg2: geo::Point) -> u32";
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return mock_area(__covers_arg0, __covers_arg1, __covers_arg2);").to_string()));
+        let fallthrough = format!(
+            "return {}area(__covers_arg0, __covers_arg1, __covers_arg2);",
+            ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(fallthrough).to_string()));
+
+        // the body inlined into the wrapper gets the bindings of the patterns
+        let actual = mocked("mock_area, unused_original = strip", input);
+        let body = "{ let (w, h) = __covers_arg0; let &Size { depth, .. } = __covers_arg1; let geo::Point(x, _) = \
+                    __covers_arg2; w * h }";
+        assert!(actual.contains(&parse(body).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_wildcard_args() {
+        let actual = mocked("mock_foo", "fn foo(_: u8, _unused: &str, _: Vec<u8>) {}");
+        let wrapper = "fn foo(__covers_arg0: u8, _unused: &str, __covers_arg2: Vec<u8>)";
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return mock_foo(__covers_arg0, _unused, __covers_arg2);").to_string()));
+        assert!(
+            actual.contains(
+                &parse(format!(
+                    "fn {}foo(_: u8, _unused: &str, _: Vec<u8>)",
+                    ORIGINAL_FUNC_PREFIX
+                ))
+                .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_mocked_extern() {
+        let actual = mocked(
+            "mock_callback",
+            r#"pub extern "C" fn callback(data: *const u8, len: usize) -> i32 { 0 }"#,
+        );
+        let wrapper = r#"pub extern "C" fn callback(data: *const u8, len: usize) -> i32"#;
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return mock_callback(data, len);").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_bindgen_output() {
+        let signature = r#"
+            #[doc = " Registers the callback"]
+            #[allow(non_snake_case, clippy::missing_safety_doc, clippy::too_many_arguments)]
+            #[must_use]
+            pub unsafe extern "C" fn SDL_AddCallback(
+                callback: ::std::option::Option<unsafe extern "C" fn(userdata: *mut c_void, code: c_int) -> c_int>,
+                userdata: *mut ::std::os::raw::c_void,
+                name: *const ::std::os::raw::c_char,
+            ) -> ::std::os::raw::c_int
+        "#;
+        let actual = mocked("mock_SDL_AddCallback", &format!("{} {{ 0 }}", signature));
+        assert!(actual.contains(&parse(signature).to_string()));
+        let wrapper = format!(
+            r#"#[doc = " Registers the callback"] {}
+            #[allow(non_snake_case, clippy::missing_safety_doc, clippy::too_many_arguments)]
+            #[must_use]
+            pub unsafe extern "C" fn SDL_AddCallback"#,
+            wiring_doc("mock_SDL_AddCallback"),
+        );
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(actual.contains(&parse("return mock_SDL_AddCallback(callback, userdata, name);").to_string()));
+        let original = format!(
+            "return unsafe {{ {}SDL_AddCallback(callback, userdata, name) }};",
+            ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(original).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_unsafe() {
+        let actual = mocked(
+            "mock_read, scoped = true",
+            "pub unsafe fn read(data: *const u8) -> u8 { *data }",
+        );
+        assert!(actual.contains(&parse("pub unsafe fn read(data: *const u8) -> u8").to_string()));
+        assert!(actual.contains(&parse("#[cfg(test)] unsafe").to_string()));
+        let original = format!("return unsafe {{ {}read(data) }};", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(original).to_string()));
+        let scoped = "pub fn __covers_scoped_read(mock: unsafe fn(*const u8) -> u8)";
+        assert!(actual.contains(&parse(scoped).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_extern_hot_swap() {
+        let actual = mocked(
+            "mock_callback, hot_swap = true",
+            r#"pub unsafe extern "C" fn callback(len: usize) -> i32 { 0 }"#,
+        );
+        assert!(actual.contains(&parse(r#"pub unsafe extern "C" fn callback(len: usize) -> i32"#).to_string()));
+        let original = format!(
+            r#"pub unsafe extern "C" fn {}callback(len: usize) -> i32"#,
+            ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(original).to_string()));
+        let swap = r#"pub fn __covers_swap_callback(mock: Option<Option<unsafe extern "C" fn(usize) -> i32>>)"#;
+        assert!(actual.contains(&parse(swap).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_receivers() {
+        for receiver in &[
+            "self",
+            "mut self",
+            "&self",
+            "&'a self",
+            "&mut self",
+            "&'a mut self",
+            "self: Box<Self>",
+        ] {
+            let actual = mocked("mock_foo", &format!("fn foo({}, x: u8) {{}}", receiver));
+            assert!(
+                actual.contains(&parse("return mock_foo(self, x);").to_string()),
+                "{}",
+                receiver
+            );
+        }
+
+        let actual = mocked("mock_foo", "fn foo(x: self::Type, y: &Self) {}");
+        assert!(actual.contains(&parse("return mock_foo(x, y);").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`self` is allowed only as the first argument")]
+    fn test_mocked_receiver_not_first() {
+        mocked("mock_foo", "fn foo(x: u8, &self) {}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported receiver `ref self`")]
+    fn test_mocked_receiver_unsupported() {
+        mocked("mock_foo", "fn foo(ref self) {}");
+    }
+
+    #[test]
+    fn test_mocked_plugin() {
+        let actual = mocked("mock_foo, plugin = telemetry::count_calls", "fn foo() {}");
+        let wrapper = parse(
+            r#"
+            telemetry::count_calls! {
+                #[cfg(any(debug_assertions, test))]
+                WIRING
+                fn foo() {
+                    ENV_TRACKING
+                    #[cfg(test)]
+                    let _: fn() -> () = mock_foo;
+                    #[cfg(test)]
+                    let _execution = __covers_tracker_foo().enter(__covers_explain_foo);
+                    #[cfg(test)]
+                    {
+                        CANARY
+                        if !__covers_original_foo().with(|original| original.get())
+                            && !::covers::runtime::is_disabled(module_path!(), ::std::option::Option::None, "foo")
+                        {
+                            __covers_tracker_foo().hit();
+                            return mock_foo();
+                        }
+                    }
+                    return _foo();
+                }
+            }
+            "#
+            .replace("return _foo", &format!("return {}foo", ORIGINAL_FUNC_PREFIX))
+            .replace("CANARY", crate::canary())
+            .replace("ENV_TRACKING", &crate::env_tracking())
+            .replace("WIRING", &wiring_doc("mock_foo")),
+        );
+        assert!(actual.contains(&wrapper.to_string()));
+    }
+
+    #[test]
+    fn test_mocked_disabled_method() {
+        let actual = mocked("mock_baz, scope = impl", "fn baz() {}");
+        let is_disabled = "::covers::runtime::is_disabled(module_path!(), \
+                           ::std::option::Option::Some(::std::any::type_name::<Self>()), \"baz\")";
+        assert!(actual.contains(&parse(format!("&& !{}", is_disabled)).to_string()));
+
+        let actual = mocked("mock_baz, scope = trait", "fn baz(&self) {}");
+        assert!(actual.contains(&parse(format!("if !{} {{ return mock_baz(self); }}", is_disabled)).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_map_original() {
+        let actual = mocked(
+            "map_original = |(a, b)| a == b, scope = impl",
+            "fn foo(x: u8) -> (u8, u8) { (x, x) }",
+        );
+        let call = format!("return (|(a, b)| a == b)(Self::{}foo(x));", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(call).to_string()));
+
+        // `|` of the body is not a closure's parameter list
+        let actual = mocked(
+            "map_original = |r| r | 1, scope = impl, priority = 1",
+            "fn foo() -> u8 { 2 }",
+        );
+        let call = format!("return (|r| r | 1)(Self::{}foo());", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(call).to_string()));
+
+        let actual = mocked(
+            "map_original = |(a, b)| a.min(b), scope = impl",
+            "fn foo() -> (u8, u8) { (1, 2) }",
+        );
+        let call = format!("return (|(a, b)| a.min(b))(Self::{}foo());", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(call).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_closure_params() {
+        let actual = mocked(
+            "mock_send, capture = |to| to.is_empty() || to.len() > 8, keep_in_release",
+            "fn send(&self, to: &str) {}",
+        );
+        let capture = "::covers::runtime::project(&to, |to| to.is_empty() || to.len() > 8)";
+        assert!(actual.contains(&parse(capture).to_string()));
+        assert!(actual.contains("__covers_swap_send"));
+
+        let actual = scoped_mock("net::fetch, |a, b| a + b");
+        assert_expansion(actual, "net::__covers_scoped_fetch(|a, b| a + b)");
+    }
+
+    #[test]
+    #[should_panic(expected = "Either a reference to mock or `map_original` should be provided")]
+    fn test_mocked_map_original_with_mock() {
+        mocked("mock_foo, map_original = |r| r", "fn foo() {}");
+    }
+
+    #[test]
+    fn test_mocked_const_passthrough() {
+        let actual = mocked(
+            "mock_len, const_passthrough = runtime_len",
+            "pub const fn len() -> usize { 4 }",
+        );
+        // the const function is kept as is for all builds
+        assert!(actual.ends_with(&parse("pub const fn len() -> usize { 4 }").to_string()));
+        assert!(actual.contains(&parse(format!("pub const fn {}len() -> usize", ORIGINAL_FUNC_PREFIX)).to_string()));
+        assert!(actual.contains(&parse("pub fn runtime_len() -> usize").to_string()));
+        assert!(!actual.contains(&parse("const fn runtime_len").to_string()));
+        assert!(actual.contains(&parse("return mock_len();").to_string()));
+        assert!(actual.contains(&parse("return len();").to_string()));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "`const fn len` could not dispatch to mocks in const contexts, pass `const_passthrough = \
+                    len_runtime`"
+    )]
+    fn test_mocked_const_without_passthrough() {
+        mocked("mock_len", "pub const fn len() -> usize { 4 }");
+    }
+
+    #[test]
+    fn test_mocked_unused_original() {
+        let actual = mocked("mock_foo, unused_original = allow", "fn foo(a: u8) -> u8 { a }");
+        assert!(
+            actual.contains(
+                &parse(format!(
+                    "#[cfg(any(debug_assertions, test))] #[allow(dead_code)] pub fn {}foo(a: u8) -> u8",
+                    ORIGINAL_FUNC_PREFIX
+                ))
+                .to_string()
+            )
+        );
+
+        let actual = mocked("mock_foo, unused_original = strip", "fn foo(a: u8) -> u8 { a + 1 }");
+        assert!(!actual.contains(&parse(format!("fn {}foo", ORIGINAL_FUNC_PREFIX)).to_string()));
+        // the body follows the dispatch to mocks
+        assert!(actual.contains("return mock_foo (a) ; } } { a + 1 } }"));
+    }
+
+    #[test]
+    #[should_panic(expected = "`unused_original = strip` could not be combined with `map_original`")]
+    fn test_mocked_unused_original_mapped() {
+        mocked(
+            "map_original = |a| a, unused_original = strip",
+            "fn foo(a: u8) -> u8 { a }",
+        );
+    }
+
+    #[test]
+    fn test_mocked_inline() {
+        let input = "#[inline(always)] fn foo() {}";
+        let wrapper = format!("{} #[inline(always)] fn foo()", wiring_doc("mock_foo"));
+        let wrapper = parse(wrapper).to_string();
+        let original = parse(format!(
+            "#[cfg(any(debug_assertions, test))] #[inline(always)] pub fn {}foo()",
+            ORIGINAL_FUNC_PREFIX
+        ))
+        .to_string();
+        let release = parse("#[cfg(not(any(debug_assertions, test)))] #[inline(always)] fn foo()").to_string();
+
+        let actual = mocked("mock_foo", input);
+        assert!(actual.contains(&wrapper) && actual.contains(&original));
+
+        let actual = mocked("mock_foo, inline = wrapper", input);
+        assert!(actual.contains(&wrapper) && !actual.contains(&original));
+
+        let actual = mocked("mock_foo, inline = original", input);
+        assert!(!actual.contains(&wrapper) && actual.contains(&original));
+
+        let actual = mocked("mock_foo, inline = drop", input);
+        assert_eq!(actual.matches("inline").count(), 1);
+        assert!(actual.contains(&release));
+    }
+
+    #[test]
+    fn test_mocked_docs() {
+        let input = "/// Docs\n#[must_use] fn foo() -> u8 { 0 }";
+        let original = format!(
+            "#[cfg(any(debug_assertions, test))] #[doc(hidden)] #[must_use] pub fn {}foo()",
+            ORIGINAL_FUNC_PREFIX
+        );
+        let wrapper = format!(r#"#[doc = " Docs"] {} #[must_use] fn foo()"#, wiring_doc("mock_foo"));
+
+        let actual = mocked("mock_foo", input);
+        assert!(actual.contains(&parse(&original).to_string()));
+        assert!(actual.contains(&parse(&wrapper).to_string()));
+        assert_eq!(actual.matches("Docs").count(), 2);
+
+        let actual = mocked("mock_foo, docs = both", input);
+        let original = format!(r#"#[doc = " Docs"] #[must_use] pub fn {}foo()"#, ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(&original).to_string()));
+        assert_eq!(actual.matches("Docs").count(), 3);
+    }
+
+    #[test]
+    fn test_mocked_cfg_attr() {
+        let input = "#[cfg_attr(feature = \"fast\", inline)] #[cfg_attr(all(), doc = \"Docs\")] #[cfg_attr(test, \
+                     must_use, inline(always))] fn foo() -> u8 { 0 }";
+        let original = format!(
+            "#[cfg(any(debug_assertions, test))] #[doc(hidden)] #[cfg_attr(test, must_use)] pub fn {}foo()",
+            ORIGINAL_FUNC_PREFIX
+        );
+        let wrapper = format!(
+            r#"#[cfg(any(debug_assertions, test))] #[cfg_attr(feature = "fast", inline)]
+               #[cfg_attr(all(), doc = "Docs")] {} #[cfg_attr(test, must_use, inline(always))] fn foo()"#,
+            wiring_doc("mock_foo")
+        );
+        let release = "#[cfg(not(any(debug_assertions, test)))] #[cfg_attr(feature = \"fast\", inline)] \
+                       #[cfg_attr(all(), doc = \"Docs\")] #[cfg_attr(test, must_use, inline(always))] fn foo()";
+
+        let actual = mocked("mock_foo, inline = wrapper", input);
+        assert!(actual.contains(&parse(&original).to_string()));
+        assert!(actual.contains(&parse(&wrapper).to_string()));
+        assert!(actual.contains(&parse(release).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_coverage() {
+        let input = "pub async fn foo() -> u8 { 0 }";
+        let actual = mocked(
+            "mock_foo, coverage = off, scoped = false, also_blocking = foo_blocking",
+            input,
+        );
+        let coverage = parse("#[cfg_attr(coverage_nightly, coverage(off))]").to_string();
+        // wrapper, switch, tracker, explanation and the blocking variant
+        assert_eq!(actual.matches(&coverage).count(), 5);
+        let original = format!(
+            "#[cfg(any(debug_assertions, test))] pub async fn {}foo()",
+            ORIGINAL_FUNC_PREFIX
+        );
+        assert!(actual.contains(&parse(original).to_string()));
+
+        assert!(!mocked("mock_foo", input).contains("coverage"));
+        assert!(!mocked("mock_foo, coverage = on", input).contains("coverage"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown `coverage = none`, expected `on` (default) or `off`")]
+    fn test_mocked_coverage_unknown() {
+        mocked("mock_foo, coverage = none", "fn foo() {}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown `docs = none`, expected `wrapper` (default) or `both`")]
+    fn test_mocked_docs_unknown() {
+        mocked("mock_foo, docs = none", "fn foo() {}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown `inline = never`")]
+    fn test_mocked_inline_unknown() {
+        mocked("mock_foo, inline = never", "fn foo() {}");
+    }
+
+    #[test]
+    fn test_mocked_also_blocking() {
+        let actual = mocked(
+            "mock_fetch, also_blocking = fetch_blocking",
+            "pub async fn fetch(id: u32) -> String { id.to_string() }",
+        );
+        assert!(actual.contains(&parse("return mock_fetch(id).await;").to_string()));
+        assert!(actual.contains(&parse(format!("return {}fetch(id).await;", ORIGINAL_FUNC_PREFIX)).to_string()));
+        assert!(actual.contains(
+            &parse("pub fn fetch_blocking(id: u32) -> String { ::covers::runtime::block_on(fetch(id)) }").to_string()
+        ));
+
+        let actual = mocked(
+            "mock_fetch, also_blocking = fetch_blocking, block_on = tokio_test::block_on",
+            "async fn fetch() {}",
+        );
+        assert!(actual.contains(&parse("fn fetch_blocking() { tokio_test::block_on(fetch()) }").to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`also_blocking` is expected for `async fn`, got `fetch`")]
+    fn test_mocked_also_blocking_not_async() {
+        mocked("mock_fetch, also_blocking = fetch_blocking", "fn fetch() {}");
+    }
+
+    #[test]
+    fn test_mocked_wrap_always() {
+        let actual = mocked("mock_foo, wrap = always", "fn foo() {}");
+        assert!(!actual.contains("cfg (any"));
+        assert!(!actual.contains("cfg (not"));
+    }
+
+    #[test]
+    fn test_mocked_test_only() {
+        let actual = mocked("mock_builder", "#[cfg(test)] fn builder() -> u8 { 1 }");
+        assert!(!actual.contains("cfg (any"));
+        assert!(!actual.contains("cfg (not"));
+        let wrapper = format!("#[cfg(test)] {} fn builder() -> u8", wiring_doc("mock_builder"));
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        assert!(!actual.contains(&parse("#[cfg(test)] #[cfg(test)]").to_string()));
+        let original = format!("#[cfg(test)] pub fn {}builder() -> u8", ORIGINAL_FUNC_PREFIX);
+        assert!(actual.contains(&parse(original).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_leading_items() {
+        let actual = mocked(
+            "mock_foo, const_passthrough = foo_runtime",
+            r#"
+            #[allow(dead_code)]
+            pub(crate) const LIMIT: usize = { let limit = 1; limit };
+            static mut COUNTER: [u8; 2] = [0; 2];
+            const _: () = ();
+            #[inline]
+            pub const fn foo() -> usize { LIMIT }
+            "#,
+        );
+        let leading_items = parse(
+            r#"
+            #[allow(dead_code)]
+            pub(crate) const LIMIT: usize = { let limit = 1; limit };
+            static mut COUNTER: [u8; 2] = [0; 2];
+            const _: () = ();
+            "#,
+        )
+        .to_string();
+
+        assert!(actual.starts_with(&leading_items));
+        assert_eq!(actual.matches("LIMIT :").count(), 1);
+        assert!(actual.contains(&parse("#[inline] pub const fn foo() -> usize").to_string()));
+        assert!(actual.contains(&parse("return mock_foo();").to_string()));
+    }
+
+    #[test]
+    fn test_assert_not_concurrent() {
+        assert_expansion(
+            assert_not_concurrent("db::Client::write"),
+            r#"db::Client::__covers_tracker_write().assert_not_concurrent("db :: Client :: write")"#,
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        assert_expansion(explain("db::Client::write"), "db::Client::__covers_explain_write()");
+    }
+
+    #[test]
+    fn test_original_of() {
+        assert_expansion(
+            original_of("crate::db::Client::write"),
+            &format!("crate::db::Client::{}write", ORIGINAL_FUNC_PREFIX),
+        );
+    }
+
+    #[test]
+    fn test_mangle() {
+        assert_expansion(mangle("swap, net::fetch"), "net::__covers_swap_fetch");
+        assert_expansion(
+            mangle("instance_calls, Client::get"),
+            "Client::__covers_instance_calls_get",
+        );
+        assert_expansion(
+            mangle("original, db::write"),
+            &format!("db::{}write", ORIGINAL_FUNC_PREFIX),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The first argument should be a reference to the function denoted as `#[mocked]`!")]
+    fn test_original_of_not_function() {
+        original_of("crate::db::Client::<u8>");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown generated item `mock`, expected one of: original, switch, tracker")]
+    fn test_mangle_unknown() {
+        mangle("mock, net::fetch");
+    }
+
+    #[test]
+    fn test_mocked_explain_stacked() {
+        let actual = mocked(
+            "mock_a",
+            "#[mocked(map_original = |n| n + 1, priority = 1)] fn foo(n: u8) -> u8 { n }",
+        );
+        assert!(actual.contains(&parse(r#"return "map_original = | n | n + 1";"#).to_string()));
+        assert!(!actual.contains(r#""mock_a""#));
+    }
+
+    #[test]
+    fn test_mocked_wiring_doc() {
+        let actual = mocked(
+            "mock_a",
+            "/// Docs\n#[mocked(mock_b, priority = 1)] #[inline] fn foo() {}",
+        );
+        let wrapper = format!("#[doc = \" Docs\"] {} #[inline] fn foo()", wiring_doc("mock_b"));
+        assert!(actual.contains(&parse(wrapper).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_stacked() {
+        let actual = mocked(
+            "mock_a",
+            "#[inline] #[mocked(mock_b, priority = 1)] #[covers::mocked(mock_c, scope = impl)] fn foo() {}",
+        );
+        assert!(!actual.contains("mocked"));
+        assert!(actual.contains(&parse("return mock_b();").to_string()));
+        assert!(!actual.contains("mock_a"));
+        assert!(!actual.contains("mock_c"));
+        assert!(actual.contains(&parse(format!("return Self::{}foo();", ORIGINAL_FUNC_PREFIX)).to_string()));
+        assert_eq!(actual.matches(&parse("#[inline] fn foo()").to_string()).count(), 2);
+    }
+
+    #[test]
+    fn test_expansion_disabled() {
+        for value in &["1", "true", " yes "] {
+            assert!(crate::is_enabling(value));
+        }
+        for value in &["", "0", "false", " 0 "] {
+            assert!(!crate::is_enabling(value));
+        }
+    }
+
+    #[test]
+    fn test_env_tracking() {
+        let tracking = crate::env_tracking();
+        for name in &[
+            "COVERS_DISABLE",
+            "COVERS_PLUGIN",
+            "COVERS_UNUSED_ORIGINAL",
+            "COVERS_MANIFEST",
+        ] {
+            assert!(tracking.contains(&format!("::core::option_env!({:?})", name)));
+        }
+
+        // the input of the disabled expansion keeps the tracking too
+        let function = crate::with_env_tracking(parse("fn foo() { bar() }")).to_string();
+        assert_eq!(
+            function,
+            parse(format!("fn foo() {{ {} bar() }}", tracking)).to_string()
+        );
+        let block = crate::with_env_tracking(parse("impl Foo { fn foo() {} }")).to_string();
+        assert_eq!(
+            block,
+            parse(format!("{} impl Foo {{ fn foo() {{}} }}", tracking)).to_string()
+        );
+    }
+
+    #[test]
+    fn test_active_feature() {
+        // opted in by `covers::build::active_feature()` of the crate's build script
+        let gate = format!("any({}, feature = \"covers-active\")", MOCK_GATE);
+        assert_eq!(crate::env_mock_gate(|name| name == "COVERS_ACTIVE_FEATURE"), gate);
+        assert_eq!(crate::env_mock_gate(|_| false), MOCK_GATE);
+    }
+
+    #[test]
+    fn test_testing_cfg() {
+        // emitted per crate by `covers::build::testing()` of its build script
+        let gate = format!("any({}, covers_testing)", MOCK_GATE);
+        assert_eq!(crate::env_mock_gate(|name| name == "COVERS_TESTING"), gate);
+        let gate = format!("any({}, covers_testing, feature = \"covers-active\")", MOCK_GATE);
+        assert_eq!(crate::env_mock_gate(|_| true), gate);
+    }
+
+    #[test]
+    fn test_testing_gate_expansion() {
+        // the gate of the wrapper follows the environment of the crate being compiled
+        let gate = crate::env_mock_gate(|name| name == "COVERS_TESTING");
+        assert_eq!(
+            crate::active_gate(&gate),
+            "any(any(debug_assertions, test), any(test, covers_testing))"
+        );
+    }
+
+    #[test]
+    fn test_mocked_prefix() {
+        let actual = mocked(r#"mock_foo, prefix = "orig_""#, "fn foo(a: u8) -> u8 { a }");
+        assert!(actual.contains(&parse("pub fn orig_foo(a: u8) -> u8 { a }").to_string()));
+        assert!(actual.contains(&parse("return orig_foo(a);").to_string()));
+        assert!(actual.contains(&parse(r#"prefix: "orig_""#).to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`prefix = \"1_\"` should be a string of identifier characters")]
+    fn test_mocked_invalid_prefix() {
+        mocked(r#"mock_foo, prefix = "1_""#, "fn foo() {}");
+    }
+
+    #[test]
+    fn test_mocked_original_vis() {
+        let original = format!("fn {}foo(a: u8) -> u8 {{ a }}", ORIGINAL_FUNC_PREFIX);
+        let actual = mocked("mock_foo, original_vis = keep", "fn foo(a: u8) -> u8 { a }");
+        assert!(actual.contains(&parse(format!("#[cfg({})] {}", ACTIVE_GATE, original)).to_string()));
+        assert!(!actual.contains(&parse(format!("pub {}", original)).to_string()));
+
+        let actual = mocked(
+            "mock_foo, original_vis = pub(crate)",
+            "pub(super) fn foo(a: u8) -> u8 { a }",
+        );
+        assert!(actual.contains(&parse(format!("pub(crate) {}", original)).to_string()));
+        assert!(actual.contains(&parse("pub(super) fn foo(a: u8) -> u8").to_string()));
+    }
+
+    #[test]
+    fn test_mocked_keywords_case_insensitive() {
+        assert_expansion(
+            mocked(
+                "Struct::mock_baz, scope = IMPL, inline = Wrapper",
+                "fn baz(a: u8) -> u8 { a }",
+            ),
+            &mocked(
+                "Struct::mock_baz, scope = impl, inline = wrapper",
+                "fn baz(a: u8) -> u8 { a }",
+            ),
+        );
+
+        let actual = mocked("mock_foo, original_vis = pub(in crate::Db)", "fn foo() {}");
+        assert!(actual.contains(&parse(format!("pub(in crate::Db) fn {}foo()", ORIGINAL_FUNC_PREFIX)).to_string()));
+    }
+
+    #[test]
+    fn test_compile_error() {
+        let (span, message) =
+            crate::expand_mocked(parse("mock_foo, wrap = sometimes"), parse("fn foo() {}")).unwrap_err();
+        assert!(message.starts_with("Unknown `wrap = sometimes`"));
+        assert_eq!(span.start().column, "mock_foo, ".len());
+
+        let actual = crate::compile_error(&message, span).to_string();
+        assert_eq!(
+            actual,
+            parse(format!("::core::compile_error! {{ {:?} }}", message)).to_string()
+        );
+    }
+
+    #[test]
+    fn test_compile_error_span() {
+        let error = |args: &str, input: &str| crate::expand_mocked(parse(args), parse(input)).unwrap_err().0;
+
+        // the option the error is about
+        let span = error("mock_foo, priority = high", "fn foo() {}");
+        assert_eq!(span.start().column, "mock_foo, ".len());
+        let span = error("spy, scope = trait", "fn foo() {}");
+        assert_eq!(span.start().column, 0);
+        // the option of the stacked attribute
+        let span = error("mock_foo", "#[mocked(mock_bar, overrides = merge)] fn foo() {}");
+        assert_eq!(span.start().column, "#[mocked(mock_bar, ".len());
+        // the argument
+        let span = error("mock_foo", "fn foo(a: u8, self) {}");
+        assert_eq!(span.start().column, "fn foo(a: u8, ".len());
+
+        let span = crate::expand_mangle(parse("mock, foo")).unwrap_err().0;
+        assert_eq!(span.start().column, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown `original_vis = crate`")]
+    fn test_mocked_unknown_original_vis() {
+        mocked("mock_foo, original_vis = crate", "fn foo() {}");
+    }
+
+    #[test]
+    fn test_mocked_signature_check() {
+        let actual = mocked(
+            "mock_find, cfg = miri",
+            "unsafe fn find<T: Ord>(items: &[T], _: u8) -> Option<&T> { None }",
+        );
+        let check = "#[cfg(miri)] let _: unsafe fn(&[T], u8) -> Option<&T> = mock_find::<T>;";
+        assert!(actual.contains(&parse(check).to_string()));
+
+        let actual = mocked("Self::mock_get, scope = impl", "fn get(&self, key: u8) -> u8 { key }");
+        assert!(actual.contains(&parse("let _: fn(&Self, u8) -> u8 = Self::mock_get;").to_string()));
+
+        for (args, input) in &[
+            ("mock_foo, args = tuple", "fn foo(a: u8) {}"),
+            ("mock_foo", "async fn foo() {}"),
+            ("mock_foo", "extern \"C\" fn foo() {}"),
+            ("mock_foo", "fn foo(a: impl Into<u8>) {}"),
+            ("mock_foo", "fn foo(&self) -> &str { \"\" }"),
+            ("spy", "fn foo() {}"),
+        ] {
+            assert!(!mocked(args, input).contains("let _ :"), "{}", input);
+        }
+        assert!(mocked("mock_foo", "fn foo(a: &str) -> &str { a }").contains("let _ :"));
+
+        let actual = mocked("mock_a", "#[mocked(mock_b)] fn foo() {}");
+        assert!(actual.contains(&parse("let _: fn() -> () = mock_a;").to_string()));
+        assert!(!actual.contains("mock_b"));
+    }
+
+    #[test]
+    fn test_mocked_cfg() {
+        let actual = mocked(r#"mock_foo, cfg = feature = "mocks""#, "fn foo() {}");
+        let wrapper = format!(r#"#[cfg(any({}, feature = "mocks"))]"#, ACTIVE_GATE);
+        assert!(actual.contains(&parse(wrapper).to_string()));
+        let dispatch = r#"#[cfg(feature = "mocks")] let _execution = __covers_tracker_foo()"#;
+        assert!(actual.contains(&parse(dispatch).to_string()));
+        assert!(actual.contains(&parse("return mock_foo();").to_string()));
+        assert!(!actual.contains(&parse(format!("#[cfg({})]", MOCK_GATE)).to_string()));
+
+        let actual = mocked("mock_a, cfg = miri", "#[mocked(mock_b)] fn foo() {}");
+        let mock_gate = format!("any(miri, {})", MOCK_GATE);
+        assert!(actual.contains(&parse(format!("#[cfg({})] let _execution", mock_gate)).to_string()));
+        assert!(actual.contains(&parse("#[cfg(miri)] return mock_a();").to_string()));
+        let fallback = format!("#[cfg(all({}, not(any(miri))))] return mock_b();", MOCK_GATE);
+        assert!(actual.contains(&parse(fallback).to_string()));
+    }
+
+    #[test]
+    fn test_mocked_group() {
+        let actual = mocked_group(
+            r#"
+            super::mocks => {
+                #[inline]
+                fn foo(a: u8) -> u8 { a }
+                pub(crate) fn bar<T: Into<u8>>(t: T) -> u8 { t.into() }
+            }
+            "#,
+        );
+        assert!(actual.contains(&parse("return super::mocks::foo(a);").to_string()));
+        assert!(actual.contains(&parse("return super::mocks::bar::<T>(t);").to_string()));
+        assert!(
+            actual.contains(&parse(format!("#[inline] pub fn {}foo(a: u8) -> u8", ORIGINAL_FUNC_PREFIX)).to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Usage: `mocked_group!")]
+    fn test_mocked_group_without_mocks() {
+        mocked_group("{ fn foo() {} }");
+    }
+
+    #[test]
+    fn test_mocked_trait() {
+        let actual = mocked_trait(
+            r#"
+            pub(crate) trait Storage for DefaultStorage {
+                #[mocked(mocks::read)]
+                pub async fn read<T: From<String>>(key: &str) -> T { T::from(key.to_string()) }
+                fn flush() {}
+            }
+            "#,
+        );
+        assert!(actual.starts_with(&parse("#[mocked(mocks::read)] pub async fn read").to_string()));
+        assert!(
+            actual.contains(
+                &parse(
+                    "pub(crate) trait Storage { async fn read<T: From<String>>(&self, key: &str) -> T; fn \
+                     flush(&self); }"
+                )
+                .to_string()
+            )
+        );
+        assert!(
+            actual.contains(
+                &parse(
+                    "impl Storage for DefaultStorage { async fn read<T: From<String>>(&self, key: &str) -> T { \
+                     read::<T>(key).await } fn flush(&self) { flush() } }"
+                )
+                .to_string()
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only functions without `self` are expected by `mocked_trait!`, got `read`")]
+    fn test_mocked_trait_receiver() {
+        mocked_trait("trait Storage for DefaultStorage { fn read(&self) {} }");
+    }
+
+    #[test]
+    fn test_mock() {
+        let actual = mock("", "fn mock_foo() {}");
+        let expected = if cfg!(feature = "no-pub") {
+            "#[cfg(any(debug_assertions, test))] fn mock_foo() {}"
+        } else {
+            "#[cfg(any(debug_assertions, test))] pub fn mock_foo() {}"
+        };
+        assert_expansion(actual, expected);
+    }
+
+    #[test]
+    fn test_mock_vis() {
+        let actual = mock("pub(crate)", "pub fn mock_foo() {}");
+        assert_expansion(
+            actual,
+            "#[cfg(any(debug_assertions, test))] pub(crate) fn mock_foo() {}",
+        );
+
+        let actual = mock("vis = private", "pub(super) async fn mock_foo() {}");
+        assert_expansion(actual, "#[cfg(any(debug_assertions, test))] async fn mock_foo() {}");
+
+        let actual = mock("vis = pub", "fn mock_foo() {}");
+        assert_expansion(actual, "#[cfg(any(debug_assertions, test))] pub fn mock_foo() {}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown visibility `crate` of `#[mock]`")]
+    fn test_mock_unknown_vis() {
+        mock("crate", "fn mock_foo() {}");
+    }
+
+    #[test]
+    fn test_with_original() {
+        let actual = with_original("module::foo, { foo(1) }");
+        assert!(actual.contains(&parse("let switch = module::__covers_original_foo();").to_string()));
+        assert!(actual.contains(&parse("{ foo(1) }").to_string()));
+    }
+}
@@ -0,0 +1,39 @@
+//! List of mockable `extern` functions for cross-language test harnesses.
+//!
+//! With `features = ["symbols"]` the wrapper of every `extern` function
+//! embeds the name of the function prefixed with [`MARKER`]. The names are
+//! collected from the built artifact by a post-build step with
+//! `covers::symbols::collect`, so nothing is written during expansion and
+//! removed functions disappear from the list with the next build.
+
+/// Prefix of the embedded names, the same bytes as `covers::symbols::MARKER`
+pub const MARKER: &str = "__covers_extern_symbol:";
+
+/// Statement embedding the name of the function into the build, empty unless
+/// the feature is enabled
+pub fn embed(symbol: &str) -> String {
+    if cfg!(feature = "symbols") {
+        embedded(symbol)
+    } else {
+        String::new()
+    }
+}
+
+fn embedded(symbol: &str) -> String {
+    // null-terminated, so the collector knows where the name ends
+    let len = MARKER.len() + symbol.len() + 1;
+    format!("#[used] static SYMBOL: [u8; {}] = *b\"{}{}\\0\";", len, MARKER, symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded() {
+        assert_eq!(
+            embedded("on_event"),
+            "#[used] static SYMBOL: [u8; 32] = *b\"__covers_extern_symbol:on_event\\0\";"
+        );
+    }
+}